@@ -1,4 +1,4 @@
-use crate::{move_flag::MoveFlag, square::Square};
+use crate::{impl_error, impl_error_from, move_flag::{MoveFlag, Promotion}, move_generation::MoveGeneration, position::Position, square::{Square, SquareParseError}};
 use core::fmt;
 use std::{cmp::Ordering, fmt::Display, hash::Hash};
 
@@ -14,14 +14,24 @@ const PIECE_MASK: u32 =   0b0000_0000_0000_0000_1111_0000_0000_0000;
 #[cfg(feature = "board_representation_bitboard")]
 const CAPTURE_MASK: u32 = 0b0000_0000_0000_1111_0000_0000_0000_0000;
 #[cfg(feature = "board_representation_bitboard")]
-const FLAG_MASK: u32 =    0b0000_0000_1111_0000_0000_0000_0000_0000;
+const FLAG_MASK: u32 =    0b0000_0000_0011_0000_0000_0000_0000_0000;
+#[cfg(feature = "board_representation_bitboard")]
+const PROMOTION_MASK: u32 = 0b0000_0000_1100_0000_0000_0000_0000_0000;
 
 #[cfg(feature = "board_representation_array")]
 const SOURCE_MASK: u16 =  0b0000_0000_0011_1111;
 #[cfg(feature = "board_representation_array")]
 const TARGET_MASK: u16 =  0b0000_1111_1100_0000;
 #[cfg(feature = "board_representation_array")]
-const FLAG_MASK: u16 =    0b1111_0000_0000_0000;
+const FLAG_MASK: u16 =    0b0011_0000_0000_0000;
+#[cfg(feature = "board_representation_array")]
+const PROMOTION_MASK: u16 = 0b1100_0000_0000_0000;
+
+#[derive(Debug)]
+pub struct MoveParseError(pub &'static str);
+
+impl_error!(MoveParseError);
+impl_error_from!(SquareParseError, MoveParseError);
 
 pub trait Move: Copy + Default + Eq + Hash { }
 
@@ -40,6 +50,31 @@ pub struct BitMove(u16);
 impl BitMove {
     pub const EMPTY: BitMove = BitMove(0);
 
+    // The encoded move, which fits in 24 bits with either board representation
+    #[cfg(feature = "board_representation_bitboard")]
+    #[inline(always)]
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    #[cfg(feature = "board_representation_array")]
+    #[inline(always)]
+    pub fn bits(self) -> u32 {
+        self.0 as u32
+    }
+
+    #[cfg(feature = "board_representation_bitboard")]
+    #[inline(always)]
+    pub fn from_bits(bits: u32) -> BitMove {
+        BitMove(bits)
+    }
+
+    #[cfg(feature = "board_representation_array")]
+    #[inline(always)]
+    pub fn from_bits(bits: u32) -> BitMove {
+        BitMove(bits as u16)
+    }
+
     #[inline(always)]
     pub fn source(&self) -> Square {
         Square::from((self.0 & SOURCE_MASK) as u8)
@@ -75,6 +110,27 @@ impl BitMove {
         MoveFlag::from(((self.0 & FLAG_MASK) >> 12) as u8)
     }
 
+    // NOTE: The promotion field is only meaningful for moves with the promotion flag
+    #[cfg(feature = "board_representation_bitboard")]
+    #[inline(always)]
+    pub fn promotion_field(&self) -> Promotion {
+        Promotion::from(((self.0 & PROMOTION_MASK) >> 22) as u8)
+    }
+
+    #[cfg(feature = "board_representation_array")]
+    #[inline(always)]
+    pub fn promotion_field(&self) -> Promotion {
+        Promotion::from(((self.0 & PROMOTION_MASK) >> 14) as u8)
+    }
+
+    #[inline(always)]
+    pub fn promotion(&self) -> Option<Promotion> {
+        match self.flag() {
+            MoveFlag::Promotion => Some(self.promotion_field()),
+            _ => None,
+        }
+    }
+
     #[cfg(feature = "board_representation_bitboard")]
     #[inline(always)]
     pub fn encode(
@@ -93,6 +149,19 @@ impl BitMove {
         )
     }
 
+    #[cfg(feature = "board_representation_bitboard")]
+    #[inline(always)]
+    pub fn encode_promotion(
+        source: Square, 
+        target: Square, 
+        piece: PieceType, 
+        capture: PieceType, 
+        promotion: Promotion
+    ) -> BitMove {
+        let BitMove(bits) = Self::encode(source, target, piece, capture, MoveFlag::Promotion);
+        BitMove(bits | (promotion as u32) << 22)
+    }
+
     #[cfg(feature = "board_representation_array")]
     #[inline(always)]
     pub fn encode(
@@ -107,6 +176,17 @@ impl BitMove {
         )
     }
 
+    #[cfg(feature = "board_representation_array")]
+    #[inline(always)]
+    pub fn encode_promotion(
+        source: Square, 
+        target: Square, 
+        promotion: Promotion
+    ) -> BitMove {
+        let BitMove(bits) = Self::encode(source, target, MoveFlag::Promotion);
+        BitMove(bits | (promotion as u16) << 14)
+    }
+
     #[cfg(feature = "board_representation_bitboard")]
     #[inline(always)]
     pub fn decode(&self) -> (Square, Square, PieceType, PieceType, MoveFlag) {
@@ -127,7 +207,7 @@ impl BitMove {
             self.target(),
             self.piece(),
             self.capture(),
-            self.flag()
+            self.flag_description()
         )
     }
 
@@ -139,23 +219,56 @@ impl BitMove {
             self.target(),
             "",
             "",
-            self.flag()
+            self.flag_description()
         )
     }
 
+    fn flag_description(self) -> String {
+        match self.promotion() {
+            Some(promotion) => format!("{} Promotion", promotion),
+            None => self.flag().to_string(),
+        }
+    }
+
     pub fn to_uci_string(self) -> String {
-        format!(
-            "{}{}{}",
-            self.source(),
-            self.target(),
-            match self.flag() {
-                MoveFlag::PromoN => "n",
-                MoveFlag::PromoB => "b",
-                MoveFlag::PromoR => "r",
-                MoveFlag::PromoQ => "q",
-                _ => "",
-            }
-        )
+        match self.promotion() {
+            Some(promotion) => format!("{}{}{}", self.source(), self.target(), promotion.to_char()),
+            None => format!("{}{}", self.source(), self.target()),
+        }
+    }
+
+    // Finds the legal move of the position written in coordinate notation, like e2e4 or e7e8q, so
+    // the piece, capture and flag fields are the ones the move generation fills in
+    pub fn from_uci(position: &Position, move_string: &str) -> Result<BitMove, MoveParseError> {
+        if !move_string.is_ascii() || move_string.len() != 4 && move_string.len() != 5 {
+            return Err(MoveParseError("Move needs a source square, a target square and an optional promotion piece!"));
+        }
+
+        let source = Square::try_from(&move_string[0..2])?;
+        let target = Square::try_from(&move_string[2..4])?;
+        let promotion = match move_string.chars().nth(4).map(|promotion| promotion.to_ascii_lowercase()) {
+            Some(promotion) if !"nbrq".contains(promotion) => return Err(MoveParseError("Found illegal promotion piece!")),
+            promotion => promotion,
+        };
+
+        let candidates: Vec<BitMove> = MoveGeneration::generate_pseudo_legal_moves(position)
+            .into_iter()
+            .filter(|m| m.source() == source && m.target() == target)
+            .collect();
+        if candidates.is_empty() {
+            return Err(MoveParseError("No piece can make this move!"));
+        }
+
+        let bit_move = match (candidates.iter().find(|m| m.promotion().map(|p| p.to_char()) == promotion), promotion) {
+            (Some(&bit_move), _) => bit_move,
+            (None, None) => return Err(MoveParseError("Promotion is missing a promotion piece!")),
+            (None, Some(_)) => return Err(MoveParseError("Move isn't a promotion!")),
+        };
+
+        if !position.clone().make_move(bit_move) {
+            return Err(MoveParseError("Move leaves the king in check!"));
+        }
+        Ok(bit_move)
     }
 }
 
@@ -181,7 +294,7 @@ impl Display for BitMove {
             self.target(),
             self.piece(),
             self.capture(),
-            self.flag()
+            self.flag_description()
         ))
     }
 }
@@ -198,7 +311,7 @@ impl Display for BitMove {
             self.0,
             self.source(),
             self.target(),
-            self.flag()
+            self.flag_description()
         ))
     }
 }
@@ -263,6 +376,12 @@ mod tests {
         assert_eq!(piece, PieceType::WP);
         assert_eq!(capture, PieceType::None);
         assert_eq!(flag, MoveFlag::None);
+        assert_eq!(bit_move.promotion(), None);
+
+        let promotion = BitMove::encode_promotion(Square::H7, Square::G8, PieceType::WP, PieceType::BN, Promotion::Rook);
+        assert_eq!(promotion.decode(), (Square::H7, Square::G8, PieceType::WP, PieceType::BN, MoveFlag::Promotion));
+        assert_eq!(promotion.promotion(), Some(Promotion::Rook));
+        assert_eq!(promotion.to_uci_string(), "h7g8r");
     }
 
     #[test]
@@ -274,5 +393,37 @@ mod tests {
         assert_eq!(source, Square::A1);
         assert_eq!(target, Square::B1);
         assert_eq!(flag, MoveFlag::None);
+        assert_eq!(bit_move.promotion(), None);
+
+        let promotion = BitMove::encode_promotion(Square::H7, Square::G8, Promotion::Rook);
+        assert_eq!(promotion.decode(), (Square::H7, Square::G8, MoveFlag::Promotion));
+        assert_eq!(promotion.promotion(), Some(Promotion::Rook));
+        assert_eq!(promotion.to_uci_string(), "h7g8r");
+    }
+
+    #[test]
+    fn from_uci_finds_the_generated_move() {
+        crate::move_masks::init();
+        let position = crate::fen::Fen::parse("r3k3/1P6/8/3pP2b/8/8/8/R3K2R w KQq d6 0 1").unwrap();
+
+        for (move_string, flag) in [("e5d6", MoveFlag::EnPassant), ("e1g1", MoveFlag::Castle), ("b7a8n", MoveFlag::Promotion), ("b7b8Q", MoveFlag::Promotion), ("a1a8", MoveFlag::None)] {
+            let bit_move = BitMove::from_uci(&position, move_string).unwrap();
+            assert!(MoveGeneration::generate_legal_moves(&position).iter().any(|&m| m == bit_move), "{move_string}");
+            assert_eq!(bit_move.to_uci_string(), move_string.to_ascii_lowercase());
+            assert_eq!(bit_move.flag(), flag, "{move_string}");
+        }
+
+        for (move_string, error) in [
+            ("e2e4", "No piece can make this move!"),
+            ("b7b8", "Promotion is missing a promotion piece!"),
+            ("a1a2q", "Move isn't a promotion!"),
+            ("b7b8k", "Found illegal promotion piece!"),
+            ("e1e2", "Move leaves the king in check!"),
+            ("e1", "Move needs a source square, a target square and an optional promotion piece!"),
+            ("é1e2", "Move needs a source square, a target square and an optional promotion piece!"),
+        ] {
+            assert_eq!(BitMove::from_uci(&position, move_string).unwrap_err().0, error, "{move_string}");
+        }
+        assert!(BitMove::from_uci(&position, "i1e2").is_err());
     }
 }
@@ -1,6 +1,7 @@
+// NOTE: count_ones compiles to popcnt when the target has it
 #[inline(always)]
 pub fn count_bits(data: u64) -> u8 {
-    count_bits_manual(data)
+    count_bits_rust(data)
 }
 
 #[inline(always)]
@@ -8,6 +9,11 @@ pub fn get_lsb(data: u64) -> u8 {
     get_lsb_rust(data)
 }
 
+#[inline(always)]
+pub fn get_msb(data: u64) -> u8 {
+    get_msb_rust(data)
+}
+
 #[inline(always)]
 fn count_bits_manual(mut data: u64) -> u8 {
     let mut count = 0;
@@ -35,6 +41,11 @@ fn get_lsb_rust(data: u64) -> u8 {
     data.trailing_zeros() as u8
 }
 
+#[inline(always)]
+fn get_msb_rust(data: u64) -> u8 {
+    63 - data.leading_zeros() as u8
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,4 +1,4 @@
-use crate::{bit_twiddles, square::Square};
+use crate::{bb, bit_twiddles, file::File, rank::Rank, square::Square};
 use core::fmt;
 use std::{mem::transmute, ops::*};
 
@@ -52,6 +52,12 @@ impl Bitboard {
         Square::from(bit_twiddles::get_lsb(self.0))
     }
 
+    #[inline(always)]
+    pub fn get_msb(self) -> Square {
+        debug_assert_ne!(self.count_bits(), 0);
+        Square::from(bit_twiddles::get_msb(self.0))
+    }
+
     #[inline(always)]
     pub fn pop_lsb(&mut self) -> Square {
         let lsb = self.get_lsb();
@@ -64,6 +70,26 @@ impl Bitboard {
         debug_assert_eq!(self.count_bits(), 1);
         self.get_lsb()
     }
+
+    pub const fn from_squares(squares: &[Square]) -> Bitboard {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < squares.len() {
+            bits |= 1 << squares[i] as u8;
+            i += 1;
+        }
+        Bitboard(bits)
+    }
+
+    #[inline(always)]
+    pub const fn file(file: File) -> Bitboard {
+        Bitboard(Bitboard::FILE_A.0 << file as u8)
+    }
+
+    #[inline(always)]
+    pub const fn rank(rank: Rank) -> Bitboard {
+        Bitboard(Bitboard::RANK_8.0 << (rank as u8 * 8))
+    }
 }
 
 impl Bitboard {
@@ -76,6 +102,17 @@ impl Bitboard {
     pub const FILE_G: Bitboard = Bitboard(0x4040404040404040);
     pub const FILE_H: Bitboard = Bitboard(0x8080808080808080);
 
+    pub const FILES: [Bitboard; 8] = [
+        Bitboard::FILE_A,
+        Bitboard::FILE_B,
+        Bitboard::FILE_C,
+        Bitboard::FILE_D,
+        Bitboard::FILE_E,
+        Bitboard::FILE_F,
+        Bitboard::FILE_G,
+        Bitboard::FILE_H,
+    ];
+
     pub const RANK_8: Bitboard = Bitboard(0xFF);
     pub const RANK_7: Bitboard = Bitboard(0xFF00);
     pub const RANK_6: Bitboard = Bitboard(0xFF0000);
@@ -100,24 +137,35 @@ impl Bitboard {
     pub const EDGES: Bitboard = Bitboard(0xFF818181818181FF);
     pub const EMPTY: Bitboard = Bitboard(0x0);
 
-    pub const W_KING_SIDE_MASK: Bitboard = Bitboard(0x6000000000000000);
-    pub const W_QUEEN_SIDE_MASK: Bitboard = Bitboard(0xE00000000000000);
-    pub const B_KING_SIDE_MASK: Bitboard = Bitboard(0x60);
-    pub const B_QUEEN_SIDE_MASK: Bitboard = Bitboard(0xE);
+    pub const CENTER: Bitboard = bb!(D4, E4, D5, E5);
+    pub const EXTENDED_CENTER: Bitboard = bb!(
+        C6, D6, E6, F6,
+        C5, D5, E5, F5,
+        C4, D4, E4, F4,
+        C3, D3, E3, F3
+    );
+    pub const LONG_DIAGONALS: Bitboard = bb!(
+        A8, B7, C6, D5, E4, F3, G2, H1,
+        H8, G7, F6, E5, D4, C3, B2, A1
+    );
+
+    // Squares where a minor piece is usually only challenged by pawns, seen from each side
+    pub const WHITE_OUTPOST_ZONE: Bitboard = bb!(ranks: R4, R5, R6);
+    pub const BLACK_OUTPOST_ZONE: Bitboard = bb!(ranks: R3, R4, R5);
 
     pub const BP: Bitboard = Bitboard::RANK_7;
-    pub const BN: Bitboard = Bitboard(0x42);
-    pub const BB: Bitboard = Bitboard(0x24);
-    pub const BR: Bitboard = Bitboard(0x81);
-    pub const BQ: Bitboard = Bitboard(0x8);
-    pub const BK: Bitboard = Bitboard(0x10);
+    pub const BN: Bitboard = bb!(B8, G8);
+    pub const BB: Bitboard = bb!(C8, F8);
+    pub const BR: Bitboard = bb!(A8, H8);
+    pub const BQ: Bitboard = bb!(D8);
+    pub const BK: Bitboard = bb!(E8);
 
     pub const WP: Bitboard = Bitboard::RANK_2;
-    pub const WN: Bitboard = Bitboard(0x4200000000000000);
-    pub const WB: Bitboard = Bitboard(0x2400000000000000);
-    pub const WR: Bitboard = Bitboard(0x8100000000000000);
-    pub const WQ: Bitboard = Bitboard(0x800000000000000);
-    pub const WK: Bitboard = Bitboard(0x1000000000000000);
+    pub const WN: Bitboard = bb!(B1, G1);
+    pub const WB: Bitboard = bb!(C1, F1);
+    pub const WR: Bitboard = bb!(A1, H1);
+    pub const WQ: Bitboard = bb!(D1);
+    pub const WK: Bitboard = bb!(E1);
 }
 
 macro_rules! impl_bb_op {
@@ -184,3 +232,35 @@ impl fmt::Display for Bitboard {
         f.pad(&s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hand_written_masks_match_builders() {
+        for file in 0..8 {
+            assert!(Bitboard::FILES[file as usize] == Bitboard::file(File::from(file)));
+        }
+        let ranks = [Bitboard::RANK_8, Bitboard::RANK_7, Bitboard::RANK_6, Bitboard::RANK_5, Bitboard::RANK_4, Bitboard::RANK_3, Bitboard::RANK_2, Bitboard::RANK_1];
+        for (rank, &rank_bb) in ranks.iter().enumerate() {
+            assert!(rank_bb == Bitboard::rank(Rank::from(rank as u8)));
+        }
+
+        assert!(Bitboard::NOT_A == !bb!(files: FA));
+        assert!(Bitboard::NOT_AB == !bb!(files: FA, FB));
+        assert!(Bitboard::NOT_H == !bb!(files: FH));
+        assert!(Bitboard::NOT_GH == !bb!(files: FG, FH));
+        assert!(Bitboard::EDGES == bb!(files: FA, FH) | bb!(ranks: R1, R8));
+        assert!(Bitboard::WHITE_STARTING_PIECES == bb!(ranks: R1, R2));
+        assert!(Bitboard::BLACK_STARTING_PIECES == bb!(ranks: R7, R8));
+        assert!(Bitboard::ALL_STARTING_PIECES == bb!(ranks: R1, R2, R7, R8));
+
+        let light_squares = Square::ALL_SQUARES
+            .iter()
+            .filter(|sq| (sq.rank_as_u8() + sq.file_as_u8()) % 2 == 0)
+            .fold(Bitboard::EMPTY, |acc, &sq| acc | sq.to_bb());
+        assert!(Bitboard::WHITE_SQUARES == light_squares);
+        assert!(Bitboard::BLACK_SQUARES == !light_squares);
+    }
+}
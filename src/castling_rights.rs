@@ -1,18 +1,6 @@
-use crate::square::Square;
+use crate::{bitboard::Bitboard, color::Color, square::Square};
 use core::fmt;
 
-// Castling right update constants
-const INDEX_2_CASTLING_RIGHTS: [u8; 64] = [
-    0b0111, 0b1111, 0b1111, 0b1111, 0b0011, 0b1111, 0b1111, 0b1011,
-    0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111,
-    0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111,
-    0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111,
-    0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111,
-    0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111,
-    0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111, 0b1111,
-    0b1101, 0b1111, 0b1111, 0b1111, 0b1100, 0b1111, 0b1111, 0b1110
-];
-
 #[derive(Clone, Copy, PartialEq)]
 pub struct CastlingRights(pub u8);
 
@@ -26,8 +14,8 @@ impl CastlingRights {
     pub const BQ: CastlingRights = CastlingRights(0b1000);
 
     #[inline(always)]
-    pub fn update(&mut self, source: Square, target: Square) {
-        self.0 &= INDEX_2_CASTLING_RIGHTS[source] & INDEX_2_CASTLING_RIGHTS[target];
+    pub fn update(&mut self, squares: &CastlingSquares, source: Square, target: Square) {
+        self.0 &= squares.rights_masks[source] & squares.rights_masks[target];
     }
 
     #[inline(always)]
@@ -65,3 +53,145 @@ impl fmt::Display for CastlingRights {
         }
     }
 }
+
+// The squares the king and rooks castle from, which the other castling squares are derived from
+#[derive(Clone, Copy, PartialEq)]
+pub struct CastlingSquares {
+    pub king: [Square; 2],
+    pub king_side_rook: [Square; 2],
+    pub queen_side_rook: [Square; 2],
+    // The rights that survive a move from or to each square, which takes away the rights of a king
+    // or rook that castles from it
+    rights_masks: [u8; 64],
+}
+
+impl CastlingSquares {
+    pub const STANDARD: CastlingSquares = CastlingSquares::new(
+        [Square::E1, Square::E8],
+        [Square::H1, Square::H8],
+        [Square::A1, Square::A8],
+    );
+
+    pub const fn new(king: [Square; 2], king_side_rook: [Square; 2], queen_side_rook: [Square; 2]) -> CastlingSquares {
+        let mut rights_masks = [CastlingRights::DEFAULT.0; 64];
+        let rights = [(CastlingRights::WK.0, CastlingRights::WQ.0), (CastlingRights::BK.0, CastlingRights::BQ.0)];
+        let mut color = 0;
+        while color < 2 {
+            let (king_side, queen_side) = rights[color];
+            rights_masks[king[color] as usize] &= !(king_side | queen_side);
+            rights_masks[king_side_rook[color] as usize] &= !king_side;
+            rights_masks[queen_side_rook[color] as usize] &= !queen_side;
+            color += 1;
+        }
+        CastlingSquares { king, king_side_rook, queen_side_rook, rights_masks }
+    }
+
+    #[inline(always)]
+    fn back_rank_square(&self, color: Color, file: u8) -> Square {
+        Square::from((self.king[color] as u8 & !0b111) + file)
+    }
+
+    #[inline(always)]
+    pub fn king_side_king_target(&self, color: Color) -> Square {
+        self.back_rank_square(color, 6)
+    }
+
+    #[inline(always)]
+    pub fn queen_side_king_target(&self, color: Color) -> Square {
+        self.back_rank_square(color, 2)
+    }
+
+    #[inline(always)]
+    pub fn king_side_rook_target(&self, color: Color) -> Square {
+        self.back_rank_square(color, 5)
+    }
+
+    #[inline(always)]
+    pub fn queen_side_rook_target(&self, color: Color) -> Square {
+        self.back_rank_square(color, 3)
+    }
+
+    // The source and target square of the rook castling along with the king moving to the target square
+    #[inline(always)]
+    pub fn rook_move(&self, color: Color, king_target: Square) -> (Square, Square) {
+        if king_target == self.king_side_king_target(color) {
+            (self.king_side_rook[color], self.king_side_rook_target(color))
+        } else {
+            (self.queen_side_rook[color], self.queen_side_rook_target(color))
+        }
+    }
+
+    // Squares that have to be empty, apart from the castling king and rook themselves
+    #[inline(always)]
+    pub fn king_side_empty_mask(&self, color: Color) -> Bitboard {
+        Self::empty_mask(self.king[color], self.king_side_king_target(color), self.king_side_rook[color], self.king_side_rook_target(color))
+    }
+
+    #[inline(always)]
+    pub fn queen_side_empty_mask(&self, color: Color) -> Bitboard {
+        Self::empty_mask(self.king[color], self.queen_side_king_target(color), self.queen_side_rook[color], self.queen_side_rook_target(color))
+    }
+
+    // Squares the king passes through, including its source and target, which can't be attacked
+    #[inline(always)]
+    pub fn king_side_safe_mask(&self, color: Color) -> Bitboard {
+        Self::span(self.king[color], self.king_side_king_target(color))
+    }
+
+    #[inline(always)]
+    pub fn queen_side_safe_mask(&self, color: Color) -> Bitboard {
+        Self::span(self.king[color], self.queen_side_king_target(color))
+    }
+
+    #[inline(always)]
+    fn empty_mask(king_source: Square, king_target: Square, rook_source: Square, rook_target: Square) -> Bitboard {
+        (Self::span(king_source, king_target) | Self::span(rook_source, rook_target)) & !king_source.to_bb() & !rook_source.to_bb()
+    }
+
+    // NOTE: Squares on the same rank have consecutive indices, so the span between two of them is a
+    // contiguous run of bits
+    #[inline(always)]
+    fn span(a: Square, b: Square) -> Bitboard {
+        let (low, high) = if (a as u8) < (b as u8) { (a as u8, b as u8) } else { (b as u8, a as u8) };
+        Bitboard((u64::MAX >> (63 - high)) & (u64::MAX << low))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_castling_masks_are_derived_correctly() {
+        let squares = CastlingSquares::STANDARD;
+
+        assert_eq!(squares.king_side_empty_mask(Color::White).0, 0x6000000000000000);
+        assert_eq!(squares.queen_side_empty_mask(Color::White).0, 0xE00000000000000);
+        assert_eq!(squares.king_side_empty_mask(Color::Black).0, 0x60);
+        assert_eq!(squares.queen_side_empty_mask(Color::Black).0, 0xE);
+
+        assert_eq!(squares.king_side_safe_mask(Color::White).0, 0x7000000000000000);
+        assert_eq!(squares.queen_side_safe_mask(Color::Black).0, 0x1C);
+        assert_eq!(squares.queen_side_king_target(Color::White), Square::C1);
+        assert_eq!(squares.king_side_rook_target(Color::Black), Square::F8);
+
+        assert_eq!(squares.rights_masks[Square::A1], 0b1101);
+        assert_eq!(squares.rights_masks[Square::E8], 0b0011);
+        assert_eq!(squares.rights_masks[Square::D4], 0b1111);
+    }
+
+    #[test]
+    fn rights_follow_non_standard_castling_squares() {
+        let squares = CastlingSquares::new([Square::F1, Square::B8], [Square::G1, Square::H8], [Square::B1, Square::A8]);
+
+        let mut rights = CastlingRights::DEFAULT;
+        rights.update(&squares, Square::G1, Square::G2);
+        assert_eq!(rights.0, 0b1110);
+        rights.update(&squares, Square::E4, Square::B1);
+        assert_eq!(rights.0, 0b1100);
+        rights.update(&squares, Square::H1, Square::A1);
+        assert_eq!(rights.0, 0b1100);
+        rights.update(&squares, Square::B8, Square::C8);
+        assert_eq!(rights.0, CastlingRights::NONE.0);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{fs, io, path::Path};
+
+use crate::{bit_move::BitMove, fen::{Fen, FenError}, impl_error, impl_error_from, position::Position};
+
+#[derive(Debug)]
+pub struct CheckpointError(pub &'static str);
+
+impl_error!(CheckpointError);
+impl_error_from!(FenError, CheckpointError);
+
+impl From<io::Error> for CheckpointError {
+    fn from(_: io::Error) -> Self {
+        CheckpointError("Couldn't access checkpoint!")
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CheckpointLine {
+    pub score: i16,
+    pub pv: Vec<BitMove>,
+}
+
+// The lines of a long analysis after its last completed iteration, so it can be resumed after a restart
+#[derive(Clone, PartialEq, Debug)]
+pub struct AnalysisCheckpoint {
+    pub fen: String,
+    pub depth: u16,
+    pub time: u128,
+    pub nodes: u64,
+    pub lines: Vec<CheckpointLine>,
+}
+
+impl AnalysisCheckpoint {
+    pub fn position(&self) -> Result<Position, CheckpointError> {
+        Ok(Fen::parse(&self.fen)?)
+    }
+
+    // One "key value" line per statistic, followed by "line <score> <moves>" for each line
+    pub fn format(&self) -> String {
+        let mut contents = format!(
+            "# Sisyphus32 {} analysis checkpoint\nfen {}\ndepth {}\ntime {}\nnodes {}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.fen,
+            self.depth,
+            self.time,
+            self.nodes
+        );
+        for line in &self.lines {
+            let moves: Vec<String> = line.pv.iter().map(|bit_move| bit_move.to_uci_string()).collect();
+            contents.push_str(&format!("line {} {}\n", line.score, moves.join(" ")));
+        }
+        contents
+    }
+
+    pub fn parse(contents: &str) -> Result<AnalysisCheckpoint, CheckpointError> {
+        let mut checkpoint = AnalysisCheckpoint { fen: String::new(), depth: 0, time: 0, nodes: 0, lines: Vec::new() };
+        let mut line_strings = Vec::new();
+
+        for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (key, value) = line.split_once(' ').ok_or(CheckpointError("Found checkpoint entry without value!"))?;
+            match key {
+                "fen" => checkpoint.fen = value.to_owned(),
+                "depth" => checkpoint.depth = value.parse().map_err(|_| CheckpointError("Couldn't parse checkpoint depth!"))?,
+                "time" => checkpoint.time = value.parse().map_err(|_| CheckpointError("Couldn't parse checkpoint time!"))?,
+                "nodes" => checkpoint.nodes = value.parse().map_err(|_| CheckpointError("Couldn't parse checkpoint nodes!"))?,
+                "line" => line_strings.push(value),
+                _ => return Err(CheckpointError("Found unknown checkpoint entry!")),
+            }
+        }
+
+        // NOTE: The moves can only be read once the position is known, which comes first when written
+        let position = checkpoint.position()?;
+        for line in line_strings {
+            let mut words = line.split_whitespace();
+            let score = words
+                .next()
+                .and_then(|score| score.parse::<i16>().ok())
+                .ok_or(CheckpointError("Couldn't parse checkpoint line score!"))?;
+            let mut scratch_position = position.clone();
+            let mut pv = Vec::new();
+            for move_string in words {
+                let bit_move = BitMove::from_uci(&scratch_position, move_string)
+                    .map_err(|_| CheckpointError("Found illegal move in checkpoint line!"))?;
+                scratch_position.make_move(bit_move);
+                pv.push(bit_move);
+            }
+            checkpoint.lines.push(CheckpointLine { score, pv });
+        }
+
+        if checkpoint.lines.iter().all(|line| line.pv.is_empty()) {
+            return Err(CheckpointError("Checkpoint has no lines to resume from!"));
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn read(path: &Path) -> Result<AnalysisCheckpoint, CheckpointError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // NOTE: The checkpoint is written next to the file and then moved over it, so an analysis
+    // that is killed while writing still leaves the previous checkpoint behind
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut temporary_path = path.as_os_str().to_owned();
+        temporary_path.push(".tmp");
+        fs::write(&temporary_path, self.format())?;
+        fs::rename(&temporary_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    #[test]
+    fn checkpoint_round_trips_through_file() {
+        move_masks::init();
+
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let contents = format!("fen {}\ndepth 7\ntime 81234\nnodes 9876543\nline 25 e2e4 e7e5 g1f3\nline -10 a2a3\n", fen);
+        let checkpoint = AnalysisCheckpoint::parse(&contents).unwrap();
+        assert_eq!(checkpoint.depth, 7);
+        assert_eq!(checkpoint.lines[0].score, 25);
+        assert_eq!(checkpoint.lines[0].pv.len(), 3);
+        assert_eq!(checkpoint.lines[1].pv[0].to_uci_string(), "a2a3");
+
+        let path = std::env::temp_dir().join("sisyphus32-checkpoint-test");
+        checkpoint.write(&path).unwrap();
+        let read_checkpoint = AnalysisCheckpoint::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read_checkpoint, checkpoint);
+
+        assert!(AnalysisCheckpoint::parse(&format!("fen {}\nline 0 e2e5\n", fen)).is_err());
+        assert!(AnalysisCheckpoint::parse(&format!("fen {}\ndepth 3\n", fen)).is_err());
+    }
+}
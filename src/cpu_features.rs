@@ -0,0 +1,198 @@
+use core::fmt;
+
+use crate::{impl_error, move_masks, nnue, pl};
+
+#[derive(Debug)]
+pub struct BackendError(pub &'static str);
+
+impl_error!(BackendError);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BitCountBackend {
+    Portable,
+    Popcnt,
+}
+
+impl BitCountBackend {
+    // NOTE: Bit counting is too frequent for a runtime check, so the build decides it
+    pub const COMPILED: BitCountBackend = match cfg!(target_feature = "popcnt") {
+        true => BitCountBackend::Popcnt,
+        false => BitCountBackend::Portable,
+    };
+}
+
+impl fmt::Display for BitCountBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            BitCountBackend::Portable => "portable",
+            BitCountBackend::Popcnt => "popcnt",
+        })
+    }
+}
+
+// NOTE: Only the slider lookups can be switched at runtime
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SliderBackend {
+    OnTheFly,
+    Magic,
+    Pext,
+}
+
+impl SliderBackend {
+    #[cfg(feature = "sliders_magic_bitboards")]
+    const PORTABLE: SliderBackend = SliderBackend::Magic;
+
+    #[cfg(feature = "sliders_on_the_fly")]
+    const PORTABLE: SliderBackend = SliderBackend::OnTheFly;
+}
+
+impl fmt::Display for SliderBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            SliderBackend::OnTheFly => "on the fly",
+            SliderBackend::Magic => "magic bitboards",
+            SliderBackend::Pext => "pext bitboards",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NnueBackend {
+    Portable,
+    Avx2,
+}
+
+impl fmt::Display for NnueBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            NnueBackend::Portable => "portable",
+            NnueBackend::Avx2 => "avx2",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Backends {
+    pub bit_count: BitCountBackend,
+    pub sliders: SliderBackend,
+    pub nnue: NnueBackend,
+}
+
+impl Backends {
+    pub const PORTABLE: Backends = Backends {
+        bit_count: BitCountBackend::COMPILED,
+        sliders: SliderBackend::PORTABLE,
+        nnue: NnueBackend::Portable,
+    };
+}
+
+pub struct CpuFeatures {
+    pub popcnt: bool,
+    pub bmi2: bool,
+    pub avx2: bool,
+}
+
+impl CpuFeatures {
+    #[cfg(target_arch = "x86_64")]
+    pub fn detect() -> CpuFeatures {
+        CpuFeatures {
+            popcnt: is_x86_feature_detected!("popcnt"),
+            bmi2: is_x86_feature_detected!("bmi2"),
+            avx2: is_x86_feature_detected!("avx2"),
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn detect() -> CpuFeatures {
+        CpuFeatures {
+            popcnt: false,
+            bmi2: false,
+            avx2: false,
+        }
+    }
+
+    // Picks the fastest backends the CPU supports, unless some are forced for benchmarking.
+    // NOTE: The forced backends are a comma separated list like portable,pext, where portable
+    // resets every kind of backend and the later names win
+    pub fn select_backends(&self, forced_backends: Option<&str>) -> Result<Backends, BackendError> {
+        let mut backends = Backends {
+            bit_count: BitCountBackend::COMPILED,
+            sliders: if self.bmi2 && SliderBackend::PORTABLE == SliderBackend::Magic { SliderBackend::Pext } else { SliderBackend::PORTABLE },
+            nnue: if self.avx2 { NnueBackend::Avx2 } else { NnueBackend::Portable },
+        };
+
+        for name in forced_backends.into_iter().flat_map(|names| names.split(',')) {
+            match name {
+                "portable" => backends = Backends::PORTABLE,
+                "magic" | "pext" if SliderBackend::PORTABLE != SliderBackend::Magic => {
+                    return Err(BackendError("Can't force a slider backend when the sliders are computed on the fly!"));
+                }
+                "magic" => backends.sliders = SliderBackend::Magic,
+                "pext" if !self.bmi2 => return Err(BackendError("Can't force pext on a CPU without bmi2!")),
+                "pext" => backends.sliders = SliderBackend::Pext,
+                "avx2" if !self.avx2 => return Err(BackendError("Can't force avx2 on a CPU without it!")),
+                "avx2" => backends.nnue = NnueBackend::Avx2,
+                _ => return Err(BackendError("Unknown backend, expected portable, magic, pext or avx2!")),
+            }
+        }
+
+        Ok(backends)
+    }
+
+    // Detects the CPU features, activates the chosen backends and reports them
+    pub fn init(forced_backends: Option<&str>) {
+        let cpu_features = Self::detect();
+        let backends = cpu_features.select_backends(forced_backends).unwrap_or_else(|BackendError(msg)| {
+            eprintln!("{msg}");
+            Backends::PORTABLE
+        });
+        move_masks::set_pext_sliders(backends.sliders == SliderBackend::Pext);
+        nnue::set_avx2_kernels(backends.nnue == NnueBackend::Avx2);
+
+        pl!(format!(
+            "info string cpu features popcnt {} bmi2 {} avx2 {}, bit counting uses {}, sliders use {}, nnue uses {}",
+            cpu_features.popcnt, cpu_features.bmi2, cpu_features.avx2, backends.bit_count, backends.sliders, backends.nnue,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forced_backend_is_validated() {
+        let with_popcnt = CpuFeatures { popcnt: true, bmi2: false, avx2: false };
+
+        assert_eq!(with_popcnt.select_backends(None).unwrap().bit_count, BitCountBackend::COMPILED);
+        assert!(with_popcnt.select_backends(Some("popcnt")).is_err());
+        assert!(with_popcnt.select_backends(Some("avx512")).is_err());
+    }
+
+    #[test]
+    fn fastest_supported_backends_are_chosen_unless_forced() {
+        let without_features = CpuFeatures { popcnt: false, bmi2: false, avx2: false };
+        let with_features = CpuFeatures { popcnt: true, bmi2: true, avx2: true };
+
+        assert_eq!(without_features.select_backends(None).unwrap(), Backends::PORTABLE);
+        assert_eq!(with_features.select_backends(Some("portable")).unwrap(), Backends::PORTABLE);
+        assert!(without_features.select_backends(Some("avx2")).is_err());
+        assert!(without_features.select_backends(Some("portable,pext")).is_err());
+
+        let fastest = with_features.select_backends(None).unwrap();
+        assert_eq!(fastest.nnue, NnueBackend::Avx2);
+        assert_eq!(with_features.select_backends(Some("portable,avx2")).unwrap().nnue, NnueBackend::Avx2);
+
+        #[cfg(feature = "sliders_magic_bitboards")]
+        {
+            assert_eq!(fastest.sliders, SliderBackend::Pext);
+            assert_eq!(with_features.select_backends(Some("magic")).unwrap().sliders, SliderBackend::Magic);
+        }
+
+        #[cfg(feature = "sliders_on_the_fly")]
+        {
+            assert_eq!(fastest.sliders, SliderBackend::OnTheFly);
+            assert!(with_features.select_backends(Some("pext")).is_err());
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, fs::{File, OpenOptions}, io::{self, Write}, path::Path, sync::Mutex};
+
+use rand::Rng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{color::Color, impl_error, move_generation::MoveGeneration, move_ordering::MoveOrdering, pl, position::Position, search::{Search, SearchOutput}, timer::Timer};
+
+#[derive(Debug)]
+pub struct DatagenError(pub &'static str);
+
+impl_error!(DatagenError);
+
+impl From<io::Error> for DatagenError {
+    fn from(_: io::Error) -> Self {
+        DatagenError("Couldn't write training data file!")
+    }
+}
+
+// NOTE: Games that reach this length are adjudicated as draws, since they rarely go anywhere
+const MAX_PLIES: u32 = 400;
+
+const MAX_RECORDED_SCORE: i16 = 3000;
+
+pub struct DatagenSettings {
+    pub games: u32,
+    pub nodes: u64,
+    // The random moves played from the starting position, so the games don't all start alike
+    pub random_plies: u32,
+}
+
+impl Default for DatagenSettings {
+    fn default() -> Self {
+        DatagenSettings { games: 100, nodes: 5_000, random_plies: 8 }
+    }
+}
+
+// One line of "<fen> | <score> | <result>" from white's perspective
+pub struct TrainingRecord {
+    pub fen: String,
+    pub score: i16,
+}
+
+impl TrainingRecord {
+    pub fn format(&self, result: f32) -> String {
+        format!("{} | {} | {:.1}\n", self.fen, self.score, result)
+    }
+}
+
+// Plays self-play games at a fixed node count and appends each finished game to the file
+// NOTE: Only quiet positions without mate scores are recorded
+pub struct Datagen { }
+
+impl Datagen {
+    pub fn run(path: &Path, settings: &DatagenSettings) -> Result<(), DatagenError> {
+        let timer = Timer::new();
+        let file = Mutex::new(OpenOptions::new().create(true).append(true).open(path)?);
+        let results: Vec<Result<usize, DatagenError>> = (0..settings.games)
+            .into_par_iter()
+            .map(|_| {
+                let (records, result) = Self::play_game(settings, &mut rand::rng());
+                Self::append(&file, &records, result)?;
+                Ok(records.len())
+            })
+            .collect();
+
+        let mut positions = 0;
+        for result in results {
+            positions += result?;
+        }
+        pl!(format!("info string wrote {} positions from {} games to {} in {} milliseconds", positions, settings.games, path.display(), timer.get_time_passed_millis()));
+        Ok(())
+    }
+
+    fn append(file: &Mutex<File>, records: &[TrainingRecord], result: f32) -> Result<(), DatagenError> {
+        let lines: String = records.iter().map(|record| record.format(result)).collect();
+        let mut file = file.lock().map_err(|_| DatagenError("Couldn't lock training data file!"))?;
+        file.write_all(lines.as_bytes())?;
+        Ok(())
+    }
+
+    // The recorded positions of one game and its result, where the opening is drawn from the rng
+    pub fn play_game(settings: &DatagenSettings, rng: &mut impl Rng) -> (Vec<TrainingRecord>, f32) {
+        let mut position = Self::random_opening(settings.random_plies, rng);
+        let mut records = Vec::new();
+        let mut repetitions: HashMap<u64, u8> = HashMap::new();
+
+        for _ in 0..MAX_PLIES {
+            let moves = MoveGeneration::generate_legal_moves(&position);
+            if moves.len() == 0 {
+                let result = match (position.in_check(), position.side) {
+                    (false, _) => 0.5,
+                    (true, Color::White) => 0.0,
+                    (true, Color::Black) => 1.0,
+                };
+                return (records, result);
+            }
+
+            let repetition_count = repetitions.entry(position.zobrist_key).or_insert(0);
+            *repetition_count += 1;
+            if *repetition_count >= 3 || position.halfmove_clock >= 100 || position.is_dead_position() {
+                break;
+            }
+
+            let mut search = Search::new(u128::MAX)
+                .with_output(SearchOutput::Silent)
+                .with_node_limit(settings.nodes);
+            let best_move = search.go_isolated(&position, Search::MAX_DEPTH);
+            let score = search.best_score();
+            let white_score = match position.side {
+                Color::White => score,
+                Color::Black => -score,
+            };
+
+            // NOTE: Mates found by the search decide the game, which saves playing them out
+            if Search::is_mate_score(score) {
+                return (records, if white_score > 0 { 1.0 } else { 0.0 });
+            }
+
+            if !position.in_check() && MoveOrdering::is_quiet(&position, best_move) && score.abs() <= MAX_RECORDED_SCORE {
+                records.push(TrainingRecord {
+                    fen: position.to_fen(),
+                    score: white_score,
+                });
+            }
+
+            // NOTE: No earlier position can come back after a capture or pawn move
+            position.make_move(best_move);
+            if position.halfmove_clock == 0 {
+                repetitions.clear();
+            }
+        }
+
+        (records, 0.5)
+    }
+
+    // Plays random legal moves from the starting position, starting over whenever the game ends
+    // before all of them are played
+    fn random_opening(random_plies: u32, rng: &mut impl Rng) -> Position {
+        'opening: loop {
+            let mut position = Position::starting_position();
+            for _ in 0..random_plies {
+                let moves = MoveGeneration::generate_legal_moves(&position);
+                if moves.len() == 0 {
+                    continue 'opening;
+                }
+                position.make_move(moves[rng.random_range(0..moves.len())]);
+            }
+            if MoveGeneration::generate_legal_moves(&position).len() > 0 {
+                return position;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::Fen;
+
+    use super::*;
+
+    #[test]
+    fn records_use_the_trainer_text_format() {
+        let record = TrainingRecord { fen: Fen::STARTING_POSITION.to_owned(), score: -25 };
+        assert_eq!(record.format(1.0), format!("{} | -25 | 1.0\n", Fen::STARTING_POSITION));
+    }
+
+    // NOTE: The random search doesn't count nodes, so it never reaches the node limit
+    #[cfg(not(feature = "search_random"))]
+    #[test]
+    fn self_play_records_quiet_positions_with_the_result() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        crate::move_masks::init();
+        let settings = DatagenSettings { games: 1, nodes: 200, random_plies: 4 };
+        let (records, result) = Datagen::play_game(&settings, &mut StdRng::seed_from_u64(7));
+        assert!([0.0, 0.5, 1.0].contains(&result));
+        assert!(!records.is_empty());
+        let (replayed_records, replayed_result) = Datagen::play_game(&settings, &mut StdRng::seed_from_u64(7));
+        assert_eq!(replayed_result, result);
+        assert_eq!(replayed_records.iter().map(|record| &record.fen).collect::<Vec<_>>(), records.iter().map(|record| &record.fen).collect::<Vec<_>>());
+        for record in &records {
+            let position = Fen::parse(&record.fen).unwrap();
+            assert!(!position.in_check(), "{}", record.fen);
+            assert!(record.score.abs() <= MAX_RECORDED_SCORE);
+        }
+    }
+}
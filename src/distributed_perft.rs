@@ -0,0 +1,220 @@
+use std::{io::{self, BufRead, BufReader, Write}, net::{TcpListener, TcpStream}, sync::Mutex, thread};
+
+use crate::{fen::Fen, impl_error, move_generation::MoveGeneration, perft::Perft, pl, position::Position, timer::Timer};
+
+// NOTE: "task <id> <depth> <fen>" is answered with "result <id> <nodes>" or "error <id> <message>"
+
+#[derive(Debug)]
+pub struct DistributedPerftError(pub &'static str);
+
+impl_error!(DistributedPerftError);
+
+impl From<io::Error> for DistributedPerftError {
+    fn from(_: io::Error) -> Self {
+        DistributedPerftError("Lost connection to worker!")
+    }
+}
+
+struct PerftTask {
+    id: usize,
+    depth: u8,
+    fen: String,
+}
+
+pub struct PerftWorker { }
+
+impl PerftWorker {
+    // Answers tasks until the input ends or a quit line is received
+    pub fn serve<R: BufRead, W: Write>(reader: R, mut writer: W) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("task") => {
+                    let id = words.next().unwrap_or("?").to_owned();
+                    let response = match Self::run_task(words.next(), &words.collect::<Vec<_>>().join(" ")) {
+                        Ok(nodes) => format!("result {} {}", id, nodes),
+                        Err(DistributedPerftError(msg)) => format!("error {} {}", id, msg),
+                    };
+                    writeln!(writer, "{}", response)?;
+                    writer.flush()?;
+                }
+                Some("quit") => break,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // Serves every connection in turn, so one worker machine runs one task at a time
+    pub fn listen(address: &str) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        pl!(format!("info string perft worker listening on {}", listener.local_addr()?));
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let reader = BufReader::new(stream.try_clone()?);
+            if let Err(error) = Self::serve(reader, stream) {
+                eprintln!("{error}");
+            }
+        }
+        Ok(())
+    }
+
+    fn run_task(depth_string: Option<&str>, fen: &str) -> Result<u64, DistributedPerftError> {
+        let depth = depth_string
+            .and_then(|depth_string| depth_string.parse::<u8>().ok())
+            .ok_or(DistributedPerftError("Couldn't parse depth!"))?;
+        let position = Fen::parse(fen).map_err(|_| DistributedPerftError("Couldn't parse FEN!"))?;
+        Ok(Self::count_nodes(&position, depth))
+    }
+
+    fn count_nodes(position: &Position, depth: u8) -> u64 {
+        if depth == 0 {
+            1
+        } else {
+            Perft::perft_test(position, depth, false).nodes
+        }
+    }
+}
+
+pub struct PerftCoordinator { }
+
+impl PerftCoordinator {
+    // Splits the perft at the root and hands one task per legal move to the workers. A worker is
+    // either the address of a running TCP worker or "local", which counts in this process.
+    // Tasks of workers that drop out are picked up by the remaining ones.
+    pub fn split_perft(position: &Position, depth: u8, workers: &[&str]) -> Result<u64, DistributedPerftError> {
+        if depth == 0 {
+            return Ok(1);
+        }
+        if workers.is_empty() {
+            return Err(DistributedPerftError("No workers given!"));
+        }
+
+        let timer = Timer::new();
+        let legal_moves = MoveGeneration::generate_legal_moves(position);
+        let tasks: Vec<PerftTask> = legal_moves
+            .iter()
+            .enumerate()
+            .map(|(id, &bit_move)| {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                PerftTask { id, depth: depth - 1, fen: position_copy.to_fen() }
+            })
+            .collect();
+        let task_count = tasks.len();
+
+        let queue = Mutex::new(tasks);
+        let results = Mutex::new(vec![None; task_count]);
+
+        thread::scope(|scope| {
+            let (queue, results) = (&queue, &results);
+            for &worker in workers {
+                scope.spawn(move || {
+                    let run = if worker == "local" {
+                        Self::run_local_worker(queue, results)
+                    } else {
+                        Self::run_remote_worker(worker, queue, results)
+                    };
+                    if let Err(DistributedPerftError(msg)) = run {
+                        eprintln!("Worker {worker} stopped: {msg}");
+                    }
+                });
+            }
+        });
+
+        let results = results.into_inner().unwrap();
+        if results.iter().any(Option::is_none) {
+            return Err(DistributedPerftError("Not all tasks could be completed!"));
+        }
+
+        let mut total_nodes = 0;
+        for (bit_move, nodes) in legal_moves.iter().zip(results.into_iter().flatten()) {
+            pl!(format!("  Move: {:<5} Nodes: {}", bit_move.to_uci_string(), nodes));
+            total_nodes += nodes;
+        }
+        pl!(format!("
+    Depth: {}
+    Nodes: {}
+    Time: {} milliseconds
+    Workers: {}\n",
+            depth,
+            total_nodes,
+            timer.get_time_passed_millis(),
+            workers.len()
+        ));
+
+        Ok(total_nodes)
+    }
+
+    fn next_task(queue: &Mutex<Vec<PerftTask>>) -> Option<PerftTask> {
+        queue.lock().unwrap().pop()
+    }
+
+    fn run_local_worker(queue: &Mutex<Vec<PerftTask>>, results: &Mutex<Vec<Option<u64>>>) -> Result<(), DistributedPerftError> {
+        while let Some(task) = Self::next_task(queue) {
+            let position = Fen::parse(&task.fen).map_err(|_| DistributedPerftError("Couldn't parse FEN!"))?;
+            results.lock().unwrap()[task.id] = Some(PerftWorker::count_nodes(&position, task.depth));
+        }
+        Ok(())
+    }
+
+    fn run_remote_worker(address: &str, queue: &Mutex<Vec<PerftTask>>, results: &Mutex<Vec<Option<u64>>>) -> Result<(), DistributedPerftError> {
+        let mut stream = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        while let Some(task) = Self::next_task(queue) {
+            let nodes = Self::send_task(&mut stream, &mut reader, &task);
+            match nodes {
+                Ok(nodes) => results.lock().unwrap()[task.id] = Some(nodes),
+                Err(error) => {
+                    queue.lock().unwrap().push(task);
+                    return Err(error);
+                }
+            }
+        }
+
+        writeln!(stream, "quit")?;
+        Ok(())
+    }
+
+    fn send_task(stream: &mut TcpStream, reader: &mut BufReader<TcpStream>, task: &PerftTask) -> Result<u64, DistributedPerftError> {
+        writeln!(stream, "task {} {} {}", task.id, task.depth, task.fen)?;
+        stream.flush()?;
+
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(DistributedPerftError("Lost connection to worker!"));
+        }
+
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["result", id, nodes] if *id == task.id.to_string() => {
+                nodes.parse::<u64>().map_err(|_| DistributedPerftError("Couldn't parse node count!"))
+            }
+            _ => Err(DistributedPerftError("Worker sent an unexpected response!")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::move_masks;
+
+    use super::*;
+
+    #[test]
+    fn worker_answers_tasks_and_coordinator_sums_them() {
+        move_masks::init();
+
+        let input = format!("task 7 2 {}\ntask 8 x {}\n", Fen::STARTING_POSITION, Fen::STARTING_POSITION);
+        let mut output = Vec::new();
+        PerftWorker::serve(Cursor::new(input), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "result 7 400\nerror 8 Couldn't parse depth!\n");
+
+        let position = Position::starting_position();
+        assert_eq!(PerftCoordinator::split_perft(&position, 3, &["local", "local"]).unwrap(), 8902);
+    }
+}
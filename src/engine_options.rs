@@ -0,0 +1,277 @@
+use core::fmt;
+
+use crate::{impl_error, timer::TimeLimits, tt::TranspositionTable};
+
+#[derive(Debug)]
+pub struct OptionError(pub &'static str);
+
+impl_error!(OptionError);
+
+#[derive(Clone, Copy)]
+pub enum OptionKind {
+    Spin { default: i64, min: i64, max: i64 },
+    Check { default: bool },
+    String { default: &'static str, is_valid: fn(&str) -> bool },
+    Combo { default: &'static str, vars: &'static [&'static str] },
+    Button,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    String(String),
+    Button,
+}
+
+struct EngineOption {
+    name: &'static str,
+    kind: OptionKind,
+    value: OptionValue,
+}
+
+pub struct EngineOptions {
+    options: Vec<EngineOption>,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions { options: Vec::new() }
+            .declare(Self::MULTIPV, OptionKind::Spin { default: 1, min: 1, max: 256 })
+            .declare(Self::ROOT_TEMPERATURE, OptionKind::Spin { default: 0, min: 0, max: 1000 })
+            .declare(Self::SEED, OptionKind::String { default: "", is_valid: |value| value.parse::<u64>().is_ok() })
+            .declare(Self::NPS_LIMIT, OptionKind::Spin { default: 0, min: 0, max: 100_000_000 })
+            .declare(Self::PONDER, OptionKind::Check { default: false })
+            .declare(Self::PRESET, OptionKind::Combo { default: "Max", vars: &["Beginner", "Club", "Master", "Max"] })
+            .declare(Self::CHECKPOINT_FILE, OptionKind::String { default: "", is_valid: |_| true })
+            .declare(Self::CHECKPOINT_INTERVAL, OptionKind::Spin { default: 60, min: 1, max: 86_400 })
+            .declare(Self::CONTEMPT, OptionKind::Spin { default: 0, min: -100, max: 100 })
+            .declare(Self::USE_NNUE, OptionKind::Check { default: true })
+            .declare(Self::EVAL_FILE, OptionKind::String { default: "", is_valid: |_| true })
+            .declare(Self::EVAL_BLEND, OptionKind::Combo { default: "Off", vars: &["Off", "Weighted", "Imbalance"] })
+            .declare(Self::EVAL_BLEND_WEIGHT, OptionKind::Spin { default: 50, min: 0, max: 100 })
+            .declare(Self::EVAL_BLEND_IMBALANCE, OptionKind::Spin { default: 500, min: 0, max: 4_000 })
+            .declare(Self::SHOW_WDL, OptionKind::Check { default: false })
+            .declare(Self::MOVE_OVERHEAD, OptionKind::Spin { default: TimeLimits::DEFAULT_MOVE_OVERHEAD as i64, min: 0, max: 5_000 })
+            .declare(Self::THREADS, OptionKind::Spin { default: 1, min: 1, max: 256 })
+            .declare(Self::HASH, OptionKind::Spin { default: TranspositionTable::DEFAULT_SIZE_MB as i64, min: 1, max: 65_536 })
+            .declare(Self::CLEAR_HASH, OptionKind::Button)
+    }
+}
+
+impl EngineOptions {
+    pub const MULTIPV: &'static str = "MultiPV";
+    pub const ROOT_TEMPERATURE: &'static str = "RootTemperature";
+    pub const SEED: &'static str = "Seed";
+    pub const NPS_LIMIT: &'static str = "NpsLimit";
+    pub const PONDER: &'static str = "Ponder";
+    pub const PRESET: &'static str = "Preset";
+    pub const CHECKPOINT_FILE: &'static str = "CheckpointFile";
+    pub const CHECKPOINT_INTERVAL: &'static str = "CheckpointInterval";
+    pub const CONTEMPT: &'static str = "Contempt";
+    pub const USE_NNUE: &'static str = "UseNNUE";
+    pub const EVAL_FILE: &'static str = "EvalFile";
+    pub const EVAL_BLEND: &'static str = "EvalBlend";
+    pub const EVAL_BLEND_WEIGHT: &'static str = "EvalBlendWeight";
+    pub const EVAL_BLEND_IMBALANCE: &'static str = "EvalBlendImbalance";
+    pub const SHOW_WDL: &'static str = "UCI_ShowWDL";
+    pub const MOVE_OVERHEAD: &'static str = "Move Overhead";
+    pub const THREADS: &'static str = "Threads";
+    pub const HASH: &'static str = "Hash";
+    pub const CLEAR_HASH: &'static str = "Clear Hash";
+
+    // Name, NpsLimit and RootTemperature
+    const PRESETS: [(&'static str, i64, i64); 4] = [
+        ("Beginner", 2_000, 150),
+        ("Club", 20_000, 50),
+        ("Master", 200_000, 10),
+        ("Max", 0, 0),
+    ];
+
+    pub fn declare(mut self, name: &'static str, kind: OptionKind) -> EngineOptions {
+        let value = match kind {
+            OptionKind::Spin { default, .. } => OptionValue::Spin(default),
+            OptionKind::Check { default } => OptionValue::Check(default),
+            OptionKind::String { default, .. } | OptionKind::Combo { default, .. } => OptionValue::String(default.to_owned()),
+            OptionKind::Button => OptionValue::Button,
+        };
+        self.options.push(EngineOption { name, kind, value });
+        self
+    }
+
+    // NOTE: Option names are matched case-insensitively and spin values are clamped to their range
+    pub fn set(&mut self, name: &str, value: &str) -> Result<&'static str, OptionError> {
+        let option = self.options
+            .iter_mut()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or(OptionError("Couldn't find option!"))?;
+
+        option.value = match option.kind {
+            OptionKind::Spin { min, max, .. } => {
+                let spin = value.parse::<i64>().map_err(|_| OptionError("Couldn't parse spin value!"))?;
+                OptionValue::Spin(spin.clamp(min, max))
+            }
+            OptionKind::Check { .. } => match value {
+                "true" => OptionValue::Check(true),
+                "false" => OptionValue::Check(false),
+                _ => return Err(OptionError("Couldn't parse check value!")),
+            },
+            OptionKind::String { is_valid, .. } => match value {
+                "" | "<empty>" => OptionValue::String(String::new()),
+                _ if is_valid(value) => OptionValue::String(value.to_owned()),
+                _ => return Err(OptionError("Found invalid string value!")),
+            },
+            OptionKind::Combo { vars, .. } => {
+                let var = vars
+                    .iter()
+                    .find(|var| var.eq_ignore_ascii_case(value))
+                    .ok_or(OptionError("Found unknown combo value!"))?;
+                OptionValue::String((*var).to_owned())
+            }
+            OptionKind::Button => OptionValue::Button,
+        };
+
+        let name = option.name;
+        if name == Self::PRESET {
+            self.apply_preset();
+        }
+        Ok(name)
+    }
+
+    pub fn apply_setoption(&mut self, line: &str) -> Result<&'static str, OptionError> {
+        let name_index = line.find("name").ok_or(OptionError("Didn't find option name!"))?;
+        let (name, value) = match line.find("value") {
+            Some(value_index) => (&line[name_index + 4..value_index], &line[value_index + 5..]),
+            None => (&line[name_index + 4..], ""),
+        };
+
+        self.set(name.trim(), value.trim())
+    }
+
+    fn apply_preset(&mut self) {
+        let preset = self.string(Self::PRESET).to_owned();
+        if let Some(&(_, nps_limit, temperature)) = Self::PRESETS.iter().find(|(name, _, _)| *name == preset) {
+            self.set_value(Self::NPS_LIMIT, OptionValue::Spin(nps_limit));
+            self.set_value(Self::ROOT_TEMPERATURE, OptionValue::Spin(temperature));
+        }
+    }
+
+    fn set_value(&mut self, name: &str, value: OptionValue) {
+        match self.options.iter_mut().find(|option| option.name == name) {
+            Some(option) => option.value = value,
+            None => panic!("Option {name} isn't declared!"),
+        }
+    }
+
+    pub fn spin(&self, name: &str) -> i64 {
+        match self.value(name) {
+            OptionValue::Spin(spin) => *spin,
+            _ => panic!("Option {name} isn't a spin option!"),
+        }
+    }
+
+    pub fn check(&self, name: &str) -> bool {
+        match self.value(name) {
+            OptionValue::Check(check) => *check,
+            _ => panic!("Option {name} isn't a check option!"),
+        }
+    }
+
+    pub fn string(&self, name: &str) -> &str {
+        match self.value(name) {
+            OptionValue::String(string) => string,
+            _ => panic!("Option {name} isn't a string or combo option!"),
+        }
+    }
+
+    // NOTE: The preset and buttons are left out
+    pub fn setoption_commands(&self) -> Vec<String> {
+        self.options
+            .iter()
+            .filter(|option| option.name != Self::PRESET && !matches!(option.kind, OptionKind::Button))
+            .map(|option| {
+                let value = match &option.value {
+                    OptionValue::Spin(spin) => spin.to_string(),
+                    OptionValue::Check(check) => check.to_string(),
+                    OptionValue::String(string) if string.is_empty() => "<empty>".to_owned(),
+                    OptionValue::String(string) => string.clone(),
+                    OptionValue::Button => String::new(),
+                };
+                format!("setoption name {} value {}", option.name, value)
+            })
+            .collect()
+    }
+
+    fn value(&self, name: &str) -> &OptionValue {
+        match self.options.iter().find(|option| option.name == name) {
+            Some(option) => &option.value,
+            None => panic!("Option {name} isn't declared!"),
+        }
+    }
+}
+
+impl fmt::Display for EngineOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines: Vec<String> = self.options
+            .iter()
+            .map(|option| match option.kind {
+                OptionKind::Spin { default, min, max } => {
+                    format!("option name {} type spin default {} min {} max {}", option.name, default, min, max)
+                }
+                OptionKind::Check { default } => {
+                    format!("option name {} type check default {}", option.name, default)
+                }
+                OptionKind::String { default, .. } => {
+                    let default = if default.is_empty() { "<empty>" } else { default };
+                    format!("option name {} type string default {}", option.name, default)
+                }
+                OptionKind::Combo { default, vars } => {
+                    let vars: Vec<String> = vars.iter().map(|var| format!(" var {}", var)).collect();
+                    format!("option name {} type combo default {}{}", option.name, default, vars.concat())
+                }
+                OptionKind::Button => format!("option name {} type button", option.name),
+            })
+            .collect();
+        f.pad(&lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_printed_parsed_and_validated() {
+        let mut options = EngineOptions::default()
+            .declare("Style", OptionKind::Combo { default: "Normal", vars: &["Solid", "Normal", "Risky"] });
+
+        let printed = options.to_string();
+        assert!(printed.contains("option name MultiPV type spin default 1 min 1 max 256"));
+        assert!(printed.contains("option name Seed type string default <empty>"));
+        assert!(printed.contains("option name Style type combo default Normal var Solid var Normal var Risky"));
+        assert!(printed.contains("option name Clear Hash type button"));
+
+        options.set("multipv", "1000").unwrap();
+        options.set("Ponder", "true").unwrap();
+        options.set("style", "risky").unwrap();
+        options.set("Seed", "42").unwrap();
+        assert_eq!(options.apply_setoption("setoption name clear hash").unwrap(), EngineOptions::CLEAR_HASH);
+        assert!(!options.setoption_commands().iter().any(|command| command.contains(EngineOptions::CLEAR_HASH)));
+        assert_eq!(options.spin(EngineOptions::MULTIPV), 256);
+        assert!(options.check(EngineOptions::PONDER));
+        assert_eq!(options.string("Style"), "Risky");
+        assert_eq!(options.string(EngineOptions::SEED), "42");
+
+        assert!(options.set("Seed", "abc").is_err());
+        assert!(options.set("Ponder", "yes").is_err());
+        assert!(options.set("Style", "Wild").is_err());
+        assert!(options.set("SyzygyPath", "tb").is_err());
+        assert_eq!(options.string(EngineOptions::SEED), "42");
+
+        options.set("Preset", "club").unwrap();
+        assert_eq!(options.spin(EngineOptions::NPS_LIMIT), 20_000);
+        assert_eq!(options.spin(EngineOptions::ROOT_TEMPERATURE), 50);
+        options.set("RootTemperature", "0").unwrap();
+        assert_eq!(options.spin(EngineOptions::ROOT_TEMPERATURE), 0);
+    }
+}
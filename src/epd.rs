@@ -0,0 +1,199 @@
+use std::{fs, io, path::Path};
+
+use crate::{bit_move::BitMove, fen::{Fen, FenError}, impl_error, impl_error_from, legality::{Legality, LegalityChecker}, position::Position};
+
+#[derive(Debug)]
+pub struct EpdError(pub &'static str);
+
+impl_error!(EpdError);
+impl_error_from!(FenError, EpdError);
+
+impl From<io::Error> for EpdError {
+    fn from(_: io::Error) -> Self {
+        EpdError("Couldn't read EPD file!")
+    }
+}
+
+// A position followed by operations, like: <board> <side> <castling> <en-passant> bm Qg6; id "WAC.001";
+pub struct Epd {
+    pub position: Position,
+    pub id: Option<String>,
+    pub best_moves: Vec<BitMove>,
+    pub avoid_moves: Vec<BitMove>,
+    // The number of moves the side to move needs to mate
+    pub direct_mate: Option<u8>,
+    pub principal_variation: Vec<BitMove>,
+    operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    // NOTE: Empty lines and lines starting with # are left out
+    pub fn load(path: &Path) -> Result<Vec<Epd>, EpdError> {
+        fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::parse)
+            .collect()
+    }
+
+    pub fn parse(epd: &str) -> Result<Epd, EpdError> {
+        let mut fields = Vec::new();
+        let mut rest = epd.trim();
+        for _ in 0..4 {
+            let (field, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            fields.push(field);
+            rest = remainder.trim_start();
+        }
+
+        let mut position = Fen::parse(&fields.join(" "))?;
+        let operations = Self::parse_operations(rest)?;
+        let operands = |opcode: &str| operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice());
+
+        // NOTE: The clocks come before the moves, since they are part of the position the moves are made in
+        if let Some(halfmove_clock) = operands("hmvc").and_then(|operands| operands.first()) {
+            position.halfmove_clock = halfmove_clock.parse().map_err(|_| EpdError("Invalid halfmove clock!"))?;
+        }
+        if let Some(fullmove_number) = operands("fmvn").and_then(|operands| operands.first()) {
+            position.fullmove_number = fullmove_number.parse().map_err(|_| EpdError("Invalid fullmove number!"))?;
+        }
+
+        let best_moves = Self::parse_moves(&position, operands("bm").unwrap_or_default())?;
+        let avoid_moves = Self::parse_moves(&position, operands("am").unwrap_or_default())?;
+        let principal_variation = Self::parse_line(&position, operands("pv").unwrap_or_default())?;
+        let direct_mate = match operands("dm").and_then(|operands| operands.first()) {
+            Some(moves) => Some(moves.parse().map_err(|_| EpdError("Invalid direct mate length!"))?),
+            None => None,
+        };
+        let id = operands("id").and_then(|operands| operands.first()).cloned();
+
+        Ok(Epd { position, id, best_moves, avoid_moves, direct_mate, principal_variation, operations })
+    }
+
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    // Operations are an opcode and its operands, ended by a semicolon. Operands can be strings in
+    // quotes, which may hold spaces and semicolons.
+    // NOTE: The semicolon after the last operation is often left out, so it is optional
+    fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+        let mut operations = Vec::new();
+        let mut words: Vec<String> = Vec::new();
+        let mut word = String::new();
+        let mut chars = text.chars();
+
+        loop {
+            let ch = chars.next();
+            match ch {
+                Some('"') => {
+                    let mut string = String::new();
+                    loop {
+                        match chars.next().ok_or(EpdError("String operand isn't closed!"))? {
+                            '"' => break,
+                            ch => string.push(ch),
+                        }
+                    }
+                    words.push(string);
+                }
+                Some(ch) if !ch.is_whitespace() && ch != ';' => word.push(ch),
+                _ => {
+                    if !word.is_empty() {
+                        words.push(std::mem::take(&mut word));
+                    }
+                    if (ch.is_none() || ch == Some(';')) && !words.is_empty() {
+                        let opcode = words.remove(0);
+                        if !opcode.starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+                            return Err(EpdError("Operation has no opcode!"));
+                        }
+                        operations.push((opcode, std::mem::take(&mut words)));
+                    }
+                    if ch.is_none() {
+                        return Ok(operations);
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_move(position: &Position, move_string: &str) -> Result<BitMove, EpdError> {
+        match LegalityChecker::check(position, move_string) {
+            Ok(Legality::Legal(bit_move)) => Ok(bit_move),
+            _ => Err(EpdError("Found illegal move in operation!")),
+        }
+    }
+
+    // Alternative moves in the same position, like the moves of bm or am
+    fn parse_moves(position: &Position, move_strings: &[String]) -> Result<Vec<BitMove>, EpdError> {
+        move_strings.iter().map(|move_string| Self::parse_move(position, move_string)).collect()
+    }
+
+    // Moves that are played one after the other, like the moves of pv
+    fn parse_line(position: &Position, move_strings: &[String]) -> Result<Vec<BitMove>, EpdError> {
+        let mut position = position.clone();
+        let mut line = Vec::new();
+        for move_string in move_strings {
+            let bit_move = Self::parse_move(&position, move_string)?;
+            position.make_move(bit_move);
+            line.push(bit_move);
+        }
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    fn uci_moves(moves: &[BitMove]) -> Vec<String> {
+        moves.iter().map(|m| m.to_uci_string()).collect()
+    }
+
+    #[test]
+    fn parse_reads_the_common_opcodes() {
+        move_masks::init();
+
+        let epd = Epd::parse("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";").unwrap();
+        assert_eq!(uci_moves(&epd.best_moves), ["g3g6"]);
+        assert_eq!(epd.id.as_deref(), Some("WAC.001"));
+        assert!(epd.avoid_moves.is_empty() && epd.principal_variation.is_empty() && epd.direct_mate.is_none());
+
+        let epd = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra7 Kf1; dm 1; pv Ra8#; c0 \"back rank; mate\"; hmvc 3; fmvn 20").unwrap();
+        assert_eq!(uci_moves(&epd.avoid_moves), ["a1a7", "g1f1"]);
+        assert_eq!(epd.direct_mate, Some(1));
+        assert_eq!(uci_moves(&epd.principal_variation), ["a1a8"]);
+        assert_eq!(epd.operands("c0"), Some(&["back rank; mate".to_owned()][..]));
+        assert_eq!((epd.position.halfmove_clock, epd.position.fullmove_number), (3, 20));
+    }
+
+    #[test]
+    fn parse_reads_perft_suites() {
+        move_masks::init();
+
+        let epd = Epd::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - ;D1 20 ;D2 400 ;D3 8902").unwrap();
+        assert_eq!(epd.operands("D2"), Some(&["400".to_owned()][..]));
+        assert_eq!(epd.operands("D3"), Some(&["8902".to_owned()][..]));
+        assert_eq!(epd.operands("D4"), None);
+    }
+
+    #[test]
+    fn parse_rejects_broken_operations() {
+        move_masks::init();
+
+        for epd in [
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra9;",
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Kh2 Rb8;",
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - id \"unclosed;",
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - dm one;",
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 20 bm Ra8;",
+            "6k1/5ppp/8/8/8/8/8/R5K1 w -",
+        ] {
+            assert!(Epd::parse(epd).is_err(), "{epd}");
+        }
+    }
+}
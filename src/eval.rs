@@ -1,15 +1,786 @@
-use crate::{bit_move::ScoringMove, color::Color, position::Position, square::Square};
+use crate::{bit_move::ScoringMove, bitboard::Bitboard, color::Color, move_masks, nnue::{Accumulator, Network}, piece::PieceType, position::Position, square::Square};
 
 static PIECE_SCORES: [i16; 13] = [100, 300, 301, 500, 900, 10000, -100, -300, -301, -500, -900, -10000, 0];
 
+// Middlegame and endgame piece-square tables from white's perspective, from a8 to h1 like the bitboards
+#[rustfmt::skip]
+static MIDDLEGAME_TABLES: [[i16; 64]; 6] = [
+    // Pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Knight
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // Bishop
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Rook
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   5,   5,   0,   0,   0,
+    ],
+    // Queen
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+          0,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   5,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // King
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+];
+
+#[rustfmt::skip]
+static ENDGAME_TABLES: [[i16; 64]; 6] = [
+    // Pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         80,  80,  80,  80,  80,  80,  80,  80,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         30,  30,  30,  30,  30,  30,  30,  30,
+         15,  15,  15,  15,  15,  15,  15,  15,
+          5,   5,   5,   5,   5,   5,   5,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Knight
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // Bishop
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Rook
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         10,  10,  10,  10,  10,  10,  10,  10,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Queen
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+          0,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   5,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // King
+    [
+        -50, -40, -30, -20, -20, -30, -40, -50,
+        -30, -20, -10,   0,   0, -10, -20, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  30,  40,  40,  30, -10, -30,
+        -30, -10,  20,  30,  30,  20, -10, -30,
+        -30, -30,   0,   0,   0,   0, -30, -30,
+        -50, -30, -30, -30, -30, -30, -30, -50,
+    ],
+];
+
+// NOTE: Black's pieces use the tables of the square mirrored vertically
+static PIECE_SQUARE_SCORES: [[i32; 64]; 12] = generate_piece_square_scores();
+
+const fn generate_piece_square_scores() -> [[i32; 64]; 12] {
+    let mut table = [[0; 64]; 12];
+    let mut kind = 0;
+    while kind < 6 {
+        let material = PIECE_SCORES[kind];
+        let mut square = 0;
+        while square < 64 {
+            let mirrored = square ^ 56;
+            table[kind][square] = pack(material + MIDDLEGAME_TABLES[kind][square], material + ENDGAME_TABLES[kind][square]);
+            table[kind + 6][square] = -pack(material + MIDDLEGAME_TABLES[kind][mirrored], material + ENDGAME_TABLES[kind][mirrored]);
+            square += 1;
+        }
+        kind += 1;
+    }
+    table
+}
+
+const BISHOP_PAIR: i32 = pack(30, 50);
+const ROOK_ON_OPEN_FILE: i32 = pack(40, 20);
+const ROOK_ON_SEMI_OPEN_FILE: i32 = pack(20, 10);
+const KNIGHT_OUTPOST: i32 = pack(30, 20);
+const BISHOP_OUTPOST: i32 = pack(15, 10);
+
+const TEMPO: i16 = 10;
+
+// The eval is scaled by these out of NORMAL_SCALE in endgames that are hard to win
+const NORMAL_SCALE: i32 = 64;
+const OPPOSITE_BISHOPS_SCALE: i32 = 22;
+const ROOK_PAWN_VERSUS_ROOK_SCALE: i32 = 32;
+
+// How much of each kind of piece counts towards the middlegame phase
+static PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+// A middlegame and an endgame score packed into one i32 with the endgame score in the upper half
+const fn pack(middlegame: i16, endgame: i16) -> i32 {
+    ((endgame as i32) << 16) + middlegame as i32
+}
+
+const fn middlegame(packed: i32) -> i16 {
+    packed as i16
+}
+
+// NOTE: Adding half of the lower range makes up for the borrow of a negative middlegame score
+const fn endgame(packed: i32) -> i16 {
+    ((packed + 0x8000) >> 16) as i16
+}
+
+// Shelter bonus indexed by edge distance and the relative rank of the closest own pawn, 0 for none
+static SHELTER_STRENGTH: [[i16; 8]; 4] = [
+    [ -5, 35, 30, 15,  8,  3,   5, 0],
+    [-20, 30, 18, -8, -6, -3, -15, 0],
+    [ -5, 35, 12,  0,  8,  2, -10, 0],
+    [-18, -5, -12, -20, -20, -25, -40, 0],
+];
+
+static UNBLOCKED_STORM: [i16; 8] = [0, 0, 40, 25, 10, 5, 0, 0];
+static BLOCKED_STORM: [i16; 8] = [0, 0, 15, 5, 0, 0, 0, 0];
+
+// Combined shelter and storm score indexed by edge distance, own pawn rank and enemy pawn rank
+static SHELTER_STORM: [[[i16; 8]; 8]; 4] = generate_shelter_storm_table();
+
+const fn generate_shelter_storm_table() -> [[[i16; 8]; 8]; 4] {
+    let mut table = [[[0; 8]; 8]; 4];
+    let mut edge_distance = 0;
+    while edge_distance < 4 {
+        let mut own_rank = 0;
+        while own_rank < 8 {
+            let mut enemy_rank = 0;
+            while enemy_rank < 8 {
+                let is_blocked = own_rank != 0 && enemy_rank == own_rank + 1;
+                let storm = if is_blocked { BLOCKED_STORM[enemy_rank] } else { UNBLOCKED_STORM[enemy_rank] };
+                table[edge_distance][own_rank][enemy_rank] = SHELTER_STRENGTH[edge_distance][own_rank] - storm;
+                enemy_rank += 1;
+            }
+            own_rank += 1;
+        }
+        edge_distance += 1;
+    }
+    table
+}
+
+// How the network's score is combined with the handcrafted eval
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EvalBlend {
+    Off,
+    // The percentage of the network's score in a weighted sum
+    Weighted(u8),
+    // The handcrafted eval where the material is off by more than the centipawns
+    Imbalance(i16),
+}
+
 pub struct Eval { }
 
 impl Eval {
+    pub const FEATURE_COUNT: usize = 9;
+    pub const FEATURE_NAMES: [&'static str; Self::FEATURE_COUNT] = ["pawn", "knight", "bishop", "rook", "queen", "king_shelter", "piece_squares", "positional", "tempo"];
+    pub const FEATURE_WEIGHTS: [i16; Self::FEATURE_COUNT] = [100, 300, 301, 500, 900, 1, 1, 1, TEMPO];
+
     pub fn basic(position: &Position) -> ScoringMove {
         let side_modifier = match position.side {
             Color::White => 1,
             Color::Black => -1
         };
-        ScoringMove::blank(Square::ALL_SQUARES.iter().fold(0, |acc, &sq| acc + PIECE_SCORES[position.get_piece(sq) as usize] * side_modifier))
+        if Self::is_stalemate_trap(position) {
+            return ScoringMove::blank(0);
+        }
+        let score = Self::taper(Self::packed_terms(position) + pack(0, Self::bare_king_drive(position)), Self::phase(position));
+        let score = score + TEMPO * side_modifier;
+        ScoringMove::blank(Self::scale(position, score as i32) as i16 * side_modifier)
+    }
+
+    // NOTE: Stalemate traps and drawish endgames are still recognized, since those are rules rather than something to learn
+    pub fn nnue(position: &Position, accumulator: &Accumulator, network: &Network) -> ScoringMove {
+        if Self::is_stalemate_trap(position) {
+            return ScoringMove::blank(0);
+        }
+        let side_modifier = match position.side {
+            Color::White => 1,
+            Color::Black => -1
+        };
+        let score = network.evaluate(accumulator, position.side) as i32 * side_modifier;
+        ScoringMove::blank(Self::scale(position, score) as i16 * side_modifier as i16)
+    }
+
+    pub fn blended(position: &Position, accumulator: &Accumulator, network: &Network, blend: EvalBlend) -> ScoringMove {
+        match blend {
+            EvalBlend::Off => Self::nnue(position, accumulator, network),
+            EvalBlend::Weighted(percent) => {
+                let nnue = Self::nnue(position, accumulator, network).score as i32;
+                let basic = Self::basic(position).score as i32;
+                ScoringMove::blank(((nnue * percent as i32 + basic * (100 - percent as i32)) / 100) as i16)
+            },
+            EvalBlend::Imbalance(threshold) if Self::material_imbalance(position) > threshold as i32 => Self::basic(position),
+            EvalBlend::Imbalance(_) => Self::nnue(position, accumulator, network),
+        }
+    }
+
+    pub fn material_imbalance(position: &Position) -> i32 {
+        PieceType::ALL_PIECES
+            .iter()
+            .filter(|&&piece| piece != PieceType::WK && piece != PieceType::BK)
+            .map(|&piece| position.bbs[piece].count_bits() as i32 * PIECE_SCORES[piece as usize] as i32)
+            .sum::<i32>()
+            .abs()
+    }
+
+    fn scale(position: &Position, score: i32) -> i32 {
+        let strong_color = if score > 0 { Color::White } else { Color::Black };
+        score * Self::endgame_scale(position, strong_color) / NORMAL_SCALE
+    }
+
+    // Without pawns, the stronger side can't win with less than a rook
+    // NOTE: Two knights are left alone, since the other side can still blunder into a mate with pawns left
+    pub fn endgame_scale(position: &Position, strong_color: Color) -> i32 {
+        let (strong_pieces, weak_pieces) = match strong_color {
+            Color::White => (PieceType::WHITE_PIECES, PieceType::BLACK_PIECES),
+            Color::Black => (PieceType::BLACK_PIECES, PieceType::WHITE_PIECES),
+        };
+        let count = |piece: PieceType| position.bbs[piece].count_bits();
+        let [strong_pawn, strong_knight, strong_bishop, strong_rook, strong_queen, _] = strong_pieces;
+        let [weak_pawn, weak_knight, weak_bishop, weak_rook, weak_queen, _] = weak_pieces;
+
+        let strong_minor_pieces = count(strong_knight) + count(strong_bishop);
+        let weak_minor_pieces = count(weak_knight) + count(weak_bishop);
+        if count(strong_pawn) == 0 && count(strong_rook) + count(strong_queen) == 0 && strong_minor_pieces <= 1 {
+            return 0;
+        }
+
+        let no_heavy_pieces = count(strong_rook) + count(strong_queen) + count(weak_rook) + count(weak_queen) == 0;
+        let bishops = position.bbs[strong_bishop] | position.bbs[weak_bishop];
+        if no_heavy_pieces
+            && count(strong_knight) + count(weak_knight) == 0
+            && count(strong_bishop) == 1
+            && count(weak_bishop) == 1
+            && (bishops & Bitboard::WHITE_SQUARES).count_bits() == 1
+        {
+            return OPPOSITE_BISHOPS_SCALE;
+        }
+
+        if count(strong_rook) == 1
+            && count(weak_rook) == 1
+            && count(strong_queen) + count(weak_queen) + strong_minor_pieces + weak_minor_pieces == 0
+            && count(strong_pawn) == 1
+            && count(weak_pawn) == 0
+        {
+            return ROOK_PAWN_VERSUS_ROOK_SCALE;
+        }
+
+        NORMAL_SCALE
+    }
+
+    fn packed_terms(position: &Position) -> i32 {
+        let king_safety = Self::king_shelter(position, Color::White) - Self::king_shelter(position, Color::Black);
+        Self::piece_square_scores(position) + pack(king_safety, 0) + Self::positional_terms(position)
+    }
+
+    fn positional_terms(position: &Position) -> i32 {
+        Self::positional(position, Color::White) - Self::positional(position, Color::Black)
+    }
+
+    // The bishop pair, rooks on files without own pawns and minor pieces on outposts
+    fn positional(position: &Position, color: Color) -> i32 {
+        let (own_pieces, enemy_pawns) = match color {
+            Color::White => (PieceType::WHITE_PIECES, position.bbs[PieceType::BP]),
+            Color::Black => (PieceType::BLACK_PIECES, position.bbs[PieceType::WP]),
+        };
+        let [pawn, knight, bishop, rook, _, _] = own_pieces;
+        let own_pawns = position.bbs[pawn];
+
+        let mut score = 0;
+        if position.bbs[bishop].count_bits() >= 2 {
+            score += BISHOP_PAIR;
+        }
+
+        let mut rooks = position.bbs[rook];
+        while rooks.is_not_empty() {
+            let file_mask = Bitboard::FILES[rooks.pop_lsb().file_as_u8() as usize];
+            if (own_pawns & file_mask).is_empty() {
+                score += match (enemy_pawns & file_mask).is_empty() {
+                    true => ROOK_ON_OPEN_FILE,
+                    false => ROOK_ON_SEMI_OPEN_FILE,
+                };
+            }
+        }
+
+        for (minor_piece, bonus) in [(knight, KNIGHT_OUTPOST), (bishop, BISHOP_OUTPOST)] {
+            let mut pieces = position.bbs[minor_piece];
+            while pieces.is_not_empty() {
+                if Self::is_outpost(color, pieces.pop_lsb(), own_pawns, enemy_pawns) {
+                    score += bonus;
+                }
+            }
+        }
+        score
+    }
+
+    #[inline(always)]
+    fn is_outpost(color: Color, square: Square, own_pawns: Bitboard, enemy_pawns: Bitboard) -> bool {
+        let relative_rank = match color {
+            Color::White => 7 - square.rank_as_u8(),
+            Color::Black => square.rank_as_u8(),
+        };
+        (3..=5).contains(&relative_rank)
+            && (move_masks::get_pawn_capture_mask(color.opposite(), square) & own_pawns).is_not_empty()
+            && (move_masks::get_pawn_attack_span_mask(color, square) & enemy_pawns).is_empty()
+    }
+
+    fn piece_square_scores(position: &Position) -> i32 {
+        let mut score = 0;
+        for piece in PieceType::ALL_PIECES {
+            let mut pieces = position.bbs[piece];
+            while pieces.is_not_empty() {
+                score += PIECE_SQUARE_SCORES[piece as usize][pieces.pop_lsb() as usize];
+            }
+        }
+        score
+    }
+
+    pub fn phase(position: &Position) -> i32 {
+        PieceType::ALL_PIECES
+            .iter()
+            .map(|&piece| position.bbs[piece].count_bits() as i32 * PHASE_WEIGHTS[piece as usize % 6])
+            .sum::<i32>()
+            .min(MAX_PHASE)
+    }
+
+    pub fn taper(packed: i32, phase: i32) -> i16 {
+        ((middlegame(packed) as i32 * phase + endgame(packed) as i32 * (MAX_PHASE - phase)) / MAX_PHASE) as i16
+    }
+
+    // NOTE: With FEATURE_WEIGHTS, this is the same as basic
+    pub fn weighted(position: &Position, weights: &[i16; Self::FEATURE_COUNT]) -> ScoringMove {
+        let side_modifier = match position.side {
+            Color::White => 1,
+            Color::Black => -1
+        };
+        if Self::is_stalemate_trap(position) {
+            return ScoringMove::blank(0);
+        }
+        let score: i32 = Self::features(position)
+            .iter()
+            .zip(weights)
+            .map(|(&feature, &weight)| feature * weight as i32)
+            .sum::<i32>()
+            + Self::taper(pack(0, Self::bare_king_drive(position)), Self::phase(position)) as i32;
+        let score = Self::scale(position, score);
+        ScoringMove::blank(score.clamp(i16::MIN as i32 + 1, i16::MAX as i32) as i16 * side_modifier)
+    }
+
+    // The terms of the basic eval from white's perspective, in the order of FEATURE_NAMES
+    // NOTE: The piece-square feature takes up the rounding of the taper, so the features add up to the eval exactly
+    pub fn features(position: &Position) -> [i32; Self::FEATURE_COUNT] {
+        let mut features = [0; Self::FEATURE_COUNT];
+        for (feature, (&white_piece, &black_piece)) in features
+            .iter_mut()
+            .zip(PieceType::WHITE_PIECES.iter().zip(&PieceType::BLACK_PIECES))
+            .take(5)
+        {
+            *feature = position.bbs[white_piece].count_bits() as i32 - position.bbs[black_piece].count_bits() as i32;
+        }
+        let phase = Self::phase(position);
+        let king_safety = Self::king_shelter(position, Color::White) - Self::king_shelter(position, Color::Black);
+        let material: i32 = features.iter().zip(Self::FEATURE_WEIGHTS).take(5).map(|(&count, weight)| count * weight as i32).sum();
+        features[5] = Self::taper(pack(king_safety, 0), phase) as i32;
+        features[7] = Self::taper(Self::positional_terms(position), phase) as i32;
+        features[6] = Self::taper(Self::packed_terms(position), phase) as i32 - material - features[5] - features[7];
+        features[8] = match position.side {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        features
+    }
+
+    // Halfway between the middlegame and the endgame
+    // NOTE: The material of the king is left out, since both kings are always on the board
+    pub fn piece_square_value(piece: PieceType, square: Square) -> i16 {
+        let kind = piece as usize % 6;
+        let packed = PIECE_SQUARE_SCORES[kind][square as usize ^ if (piece as usize) < 6 { 0 } else { 56 }];
+        let value = Self::taper(packed, MAX_PHASE / 2);
+        if kind == PieceType::WK as usize { value - PIECE_SCORES[kind] } else { value }
+    }
+
+    pub fn piece_squares(position: &Position) -> i16 {
+        let material: i32 = PieceType::ALL_PIECES
+            .iter()
+            .map(|&piece| position.bbs[piece].count_bits() as i32 * PIECE_SCORES[piece as usize] as i32)
+            .sum();
+        Self::taper(Self::piece_square_scores(position) - pack(material as i16, material as i16), Self::phase(position))
+    }
+
+    // Takes the best shelter of where the king stands and where it could castle to
+    pub fn king_shelter(position: &Position, color: Color) -> i16 {
+        let (king, can_castle_king_side, can_castle_queen_side) = match color {
+            Color::White => (PieceType::WK, position.castling_rights.wk(), position.castling_rights.wq()),
+            Color::Black => (PieceType::BK, position.castling_rights.bk(), position.castling_rights.bq()),
+        };
+
+        let king_square = position.bbs[king].to_sq();
+        let mut shelter = Self::shelter_at(position, color, king_square);
+        if can_castle_king_side {
+            shelter = shelter.max(Self::shelter_at(position, color, position.castling_squares.king_side_king_target(color)));
+        }
+        if can_castle_queen_side {
+            shelter = shelter.max(Self::shelter_at(position, color, position.castling_squares.queen_side_king_target(color)));
+        }
+
+        shelter
+    }
+
+    // Drives a bare king to the edge, since the mate is often further away than the search looks
+    fn bare_king_drive(position: &Position) -> i16 {
+        let (strong_color, weak_king, strong_king) = if position.bo == position.bbs[PieceType::BK] {
+            (Color::White, position.bbs[PieceType::BK].to_sq(), position.bbs[PieceType::WK].to_sq())
+        } else if position.wo == position.bbs[PieceType::WK] {
+            (Color::Black, position.bbs[PieceType::WK].to_sq(), position.bbs[PieceType::BK].to_sq())
+        } else {
+            return 0;
+        };
+
+        let heavy_pieces = match strong_color {
+            Color::White => position.bbs[PieceType::WR] | position.bbs[PieceType::WQ],
+            Color::Black => position.bbs[PieceType::BR] | position.bbs[PieceType::BQ],
+        };
+        if heavy_pieces.is_empty() {
+            return 0;
+        }
+
+        let center_distance = |square: Square| {
+            let (file, rank) = (square.file_as_u8() as i16, square.rank_as_u8() as i16);
+            (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+        };
+        let king_distance = (strong_king.file_as_u8() as i16 - weak_king.file_as_u8() as i16).abs()
+            + (strong_king.rank_as_u8() as i16 - weak_king.rank_as_u8() as i16).abs();
+
+        let drive = 10 * center_distance(weak_king) + 4 * (14 - king_distance);
+        match strong_color {
+            Color::White => drive,
+            Color::Black => -drive,
+        }
+    }
+
+    // NOTE: The material would score a stalemate as a win, so the winning side would walk right into it
+    pub fn is_stalemate_trap(position: &Position) -> bool {
+        let (king, own_occupancies, own_pieces) = match position.side {
+            Color::White => (PieceType::WK, position.wo, PieceType::WHITE_PIECES),
+            Color::Black => (PieceType::BK, position.bo, PieceType::BLACK_PIECES),
+        };
+        if own_occupancies != position.bbs[own_pieces[0]] | position.bbs[king] {
+            return false;
+        }
+        if position.mobility_count(PieceType::WP, position.side) != 0 || position.in_check() {
+            return false;
+        }
+
+        // NOTE: The king is taken off the board, so sliders attack the squares behind it as well
+        let king_square = position.bbs[king].to_sq();
+        let mut without_king = position.ao;
+        without_king.pop_sq(king_square);
+
+        let mut king_moves = move_masks::get_king_mask(king_square) & !own_occupancies;
+        while king_moves.is_not_empty() {
+            if !position.is_square_attacked_through(king_moves.pop_lsb(), position.side, without_king) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn shelter_at(position: &Position, color: Color, king_square: Square) -> i16 {
+        let (own_pawns, enemy_pawns) = match color {
+            Color::White => (position.bbs[PieceType::WP], position.bbs[PieceType::BP]),
+            Color::Black => (position.bbs[PieceType::BP], position.bbs[PieceType::WP]),
+        };
+
+        // Pawns behind the king don't shelter it
+        let in_front = Self::ranks_in_front_mask(color, king_square);
+        let center_file = king_square.file_as_u8().clamp(1, 6);
+
+        let mut shelter = 0;
+        for file in center_file - 1..=center_file + 1 {
+            let file_mask = Bitboard::FILES[file as usize] & in_front;
+            let own_rank = Self::closest_pawn_relative_rank(own_pawns & file_mask, color);
+            let enemy_rank = Self::closest_pawn_relative_rank(enemy_pawns & file_mask, color);
+            let edge_distance = file.min(7 - file) as usize;
+            shelter += SHELTER_STORM[edge_distance][own_rank as usize][enemy_rank as usize];
+        }
+
+        shelter
+    }
+
+    #[inline(always)]
+    fn ranks_in_front_mask(color: Color, square: Square) -> Bitboard {
+        let rank_u8 = square.rank_as_u8() as u32;
+        match color {
+            Color::White => Bitboard(u64::MAX >> (56 - rank_u8 * 8)),
+            Color::Black => Bitboard(u64::MAX << (rank_u8 * 8)),
+        }
+    }
+
+    #[inline(always)]
+    fn closest_pawn_relative_rank(pawns: Bitboard, color: Color) -> u8 {
+        if pawns.is_empty() {
+            return 0;
+        }
+
+        match color {
+            Color::White => 7 - pawns.get_msb().rank_as_u8(),
+            Color::Black => pawns.get_lsb().rank_as_u8(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_masks};
+
+    use super::*;
+
+    #[test]
+    fn king_shelter_prefers_intact_pawn_cover() {
+        move_masks::init();
+        let intact = Fen::parse("r2q1rk1/ppp2ppp/8/8/8/8/PPP2PPP/R2Q1RK1 w - -").unwrap();
+        let weakened = Fen::parse("r2q1rk1/ppp2ppp/8/8/8/6P1/PPP2P1P/R2Q1RK1 w - -").unwrap();
+        let stormed = Fen::parse("r2q1rk1/ppp2p2/8/8/8/6pp/PPP2PPP/R2Q1RK1 w - -").unwrap();
+
+        assert_eq!(Eval::king_shelter(&intact, Color::White), Eval::king_shelter(&intact, Color::Black));
+        assert!(Eval::king_shelter(&weakened, Color::White) < Eval::king_shelter(&intact, Color::White));
+        assert!(Eval::king_shelter(&stormed, Color::White) < Eval::king_shelter(&intact, Color::White));
+    }
+
+    #[test]
+    fn king_shelter_considers_castling_squares() {
+        move_masks::init();
+        let uncastled = Fen::parse("r2qk3/8/8/8/8/8/5PPP/4K2R w K -").unwrap();
+        let no_rights = Fen::parse("r2qk3/8/8/8/8/8/5PPP/4K2R w - -").unwrap();
+
+        assert!(Eval::king_shelter(&uncastled, Color::White) > Eval::king_shelter(&no_rights, Color::White));
+    }
+
+    #[test]
+    fn blended_eval_follows_the_policy() {
+        move_masks::init();
+        let network = Network::from_piece_squares();
+        let balanced = Fen::parse("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -").unwrap();
+        let rook_up = Fen::parse("1nbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQk -").unwrap();
+
+        for position in [&balanced, &rook_up] {
+            let accumulator = Accumulator::new(&network, position);
+            let nnue = Eval::nnue(position, &accumulator, &network).score;
+            let basic = Eval::basic(position).score;
+            let blended = |blend| Eval::blended(position, &accumulator, &network, blend).score;
+
+            assert_eq!(blended(EvalBlend::Off), nnue);
+            assert_eq!(blended(EvalBlend::Weighted(100)), nnue);
+            assert_eq!(blended(EvalBlend::Weighted(0)), basic);
+            assert_eq!(blended(EvalBlend::Weighted(50)), ((nnue as i32 + basic as i32) / 2) as i16);
+        }
+
+        assert_eq!(Eval::material_imbalance(&rook_up), 500);
+        let balanced_accumulator = Accumulator::new(&network, &balanced);
+        let rook_up_accumulator = Accumulator::new(&network, &rook_up);
+        assert_eq!(Eval::blended(&balanced, &balanced_accumulator, &network, EvalBlend::Imbalance(300)), Eval::nnue(&balanced, &balanced_accumulator, &network));
+        assert_eq!(Eval::blended(&rook_up, &rook_up_accumulator, &network, EvalBlend::Imbalance(300)), Eval::basic(&rook_up));
+        assert_eq!(Eval::blended(&rook_up, &rook_up_accumulator, &network, EvalBlend::Imbalance(500)), Eval::nnue(&rook_up, &rook_up_accumulator, &network));
+    }
+
+    #[test]
+    fn features_add_up_to_basic_eval() {
+        move_masks::init();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+            "r2q1rk1/ppp2p2/8/8/8/6pp/PPP2PPP/R2Q1RK1 b - -",
+            "4k3/8/8/3n4/8/8/PPP5/1K1R4 w - -",
+        ] {
+            let position = Fen::parse(fen).unwrap();
+            let side_modifier = if position.side == Color::White { 1 } else { -1 };
+            let weighted_sum: i32 = Eval::features(&position)
+                .iter()
+                .zip(Eval::FEATURE_WEIGHTS)
+                .map(|(&feature, weight)| feature * weight as i32)
+                .sum();
+            assert_eq!(weighted_sum, Eval::basic(&position).score as i32 * side_modifier, "{fen}");
+            assert_eq!(Eval::weighted(&position, &Eval::FEATURE_WEIGHTS), Eval::basic(&position), "{fen}");
+        }
+    }
+
+    #[test]
+    fn bare_king_endgames_avoid_stalemate_and_drive_king_to_edge() {
+        move_masks::init();
+
+        // The black king on a8 has no squares left, which is only fine if it is in check
+        let stalemate = Fen::parse("k7/2Q5/1K6/8/8/8/8/8 b - -").unwrap();
+        let mate = Fen::parse("k1Q5/8/1K6/8/8/8/8/8 b - -").unwrap();
+        let blocked_pawn = Fen::parse("k7/p1Q5/P7/8/8/8/8/1K6 b - -").unwrap();
+        assert!(Eval::is_stalemate_trap(&stalemate));
+        assert!(Eval::is_stalemate_trap(&blocked_pawn));
+        assert!(!Eval::is_stalemate_trap(&mate));
+        assert_eq!(Eval::basic(&stalemate).score, 0);
+        assert_eq!(Eval::weighted(&stalemate, &Eval::FEATURE_WEIGHTS).score, 0);
+
+        let cornered = Fen::parse("7k/8/5K2/8/8/8/8/R7 w - -").unwrap();
+        let centered = Fen::parse("8/8/8/4k3/8/8/8/R3K3 w - -").unwrap();
+        assert!(Eval::basic(&cornered).score > Eval::basic(&centered).score);
+        let black_to_move = Fen::parse("8/8/8/4k3/8/8/8/R3K3 b - -").unwrap();
+        assert!(Eval::basic(&centered).score > 0);
+        assert_eq!(Eval::basic(&black_to_move).score, -Eval::basic(&centered).score + 2 * TEMPO);
+    }
+
+    #[test]
+    fn piece_square_tables_favor_the_center_and_mirror_for_black() {
+        move_masks::init();
+
+        let centralized = Fen::parse("4k3/8/8/8/4N3/8/8/4K3 w - -").unwrap();
+        let cornered = Fen::parse("4k3/8/8/8/8/8/8/N3K3 w - -").unwrap();
+        assert!(Eval::piece_squares(&centralized) > Eval::piece_squares(&cornered));
+
+        let position = Fen::parse("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -").unwrap();
+        let mirrored = Fen::parse("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq -").unwrap();
+        assert_eq!(Eval::piece_squares(&position), -Eval::piece_squares(&mirrored));
+        assert_eq!(Eval::basic(&position), Eval::basic(&mirrored));
+    }
+
+    #[test]
+    fn basic_eval_is_the_same_for_mirrored_positions() {
+        move_masks::init();
+
+        for fen in [Fen::STARTING_POSITION, Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, Fen::TRICKY_POSITION_2, "7k/8/5K2/8/8/8/8/R7 w - -"] {
+            let position = Fen::parse(fen).unwrap();
+            assert_eq!(Eval::basic(&position), Eval::basic(&position.mirror()), "{fen}");
+        }
+    }
+
+    #[test]
+    fn packed_scores_taper_by_game_phase() {
+        move_masks::init();
+
+        for (middlegame_score, endgame_score) in [(0, 0), (-5, 3), (250, -1), (-32000, -32000), (12, 32000)] {
+            let packed = pack(middlegame_score, endgame_score);
+            assert_eq!((middlegame(packed), endgame(packed)), (middlegame_score, endgame_score));
+        }
+        assert_eq!(endgame(pack(-5, 3) + pack(-7, -4)), -1);
+
+        let start = Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+        let pawns = Fen::parse("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - -").unwrap();
+        assert_eq!(Eval::phase(&start), MAX_PHASE);
+        assert_eq!(Eval::phase(&pawns), 0);
+        assert_eq!(Eval::taper(pack(100, -20), MAX_PHASE), 100);
+        assert_eq!(Eval::taper(pack(100, -20), 0), -20);
+        assert_eq!(Eval::taper(pack(100, -20), MAX_PHASE / 2), 40);
+    }
+
+    #[test]
+    fn positional_terms_reward_bishop_pair_open_files_and_outposts() {
+        move_masks::init();
+
+        let bishop_pair = Fen::parse("4k3/8/8/8/8/8/8/2B1KB2 w - -").unwrap();
+        let one_bishop = Fen::parse("4k3/8/8/8/8/8/8/4KB2 w - -").unwrap();
+        assert_eq!(Eval::positional(&bishop_pair, Color::White), BISHOP_PAIR);
+        assert_eq!(Eval::positional(&one_bishop, Color::White), 0);
+
+        let rook_files = Fen::parse("4k3/pp6/8/8/8/8/1P6/R1R1K3 w - -").unwrap();
+        assert_eq!(Eval::positional(&rook_files, Color::White), ROOK_ON_SEMI_OPEN_FILE + ROOK_ON_OPEN_FILE);
+
+        // The knight on d5 is defended by the e4 pawn, and only the pawn on e6 could chase it away
+        let outpost = Fen::parse("4k3/8/8/3N4/4P3/8/8/4K3 w - -").unwrap();
+        let contested = Fen::parse("4k3/8/4p3/3N4/4P3/8/8/4K3 w - -").unwrap();
+        let undefended = Fen::parse("4k3/8/8/3N4/8/4P3/8/4K3 w - -").unwrap();
+        assert_eq!(Eval::positional(&outpost, Color::White), KNIGHT_OUTPOST);
+        assert_eq!(Eval::positional(&contested, Color::White), 0);
+        assert_eq!(Eval::positional(&undefended, Color::White), 0);
+
+        let mirrored = Fen::parse("4k3/8/8/4p3/3n4/8/8/4K3 b - -").unwrap();
+        assert_eq!(Eval::positional(&mirrored, Color::Black), KNIGHT_OUTPOST);
+    }
+
+    #[test]
+    fn drawish_endgames_are_scaled_down() {
+        move_masks::init();
+
+        // A knight against a pawn can't win, while the pawn might still promote
+        let knight_against_pawn = Fen::parse("4k3/p7/8/8/8/8/8/3NK3 w - -").unwrap();
+        assert_eq!(Eval::endgame_scale(&knight_against_pawn, Color::White), 0);
+        assert_eq!(Eval::endgame_scale(&knight_against_pawn, Color::Black), NORMAL_SCALE);
+        assert_eq!(Eval::basic(&knight_against_pawn).score, 0);
+
+        let opposite_bishops = Fen::parse("4k3/5b2/8/3P4/2P5/8/8/2B1K3 w - -").unwrap();
+        let same_bishops = Fen::parse("4k3/4b3/8/3P4/2P5/8/8/2B1K3 w - -").unwrap();
+        assert_eq!(Eval::endgame_scale(&opposite_bishops, Color::White), OPPOSITE_BISHOPS_SCALE);
+        assert_eq!(Eval::endgame_scale(&same_bishops, Color::White), NORMAL_SCALE);
+        assert!(Eval::basic(&opposite_bishops).score < Eval::basic(&same_bishops).score);
+
+        let rook_pawn_against_rook = Fen::parse("4k3/8/8/8/8/4P3/8/R3K2r w - -").unwrap();
+        let two_pawns_against_rook = Fen::parse("4k3/8/8/8/8/3PP3/8/R3K2r w - -").unwrap();
+        assert_eq!(Eval::endgame_scale(&rook_pawn_against_rook, Color::White), ROOK_PAWN_VERSUS_ROOK_SCALE);
+        assert_eq!(Eval::endgame_scale(&two_pawns_against_rook, Color::White), NORMAL_SCALE);
     }
 }
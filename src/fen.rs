@@ -1,7 +1,11 @@
-use crate::{position::Position, castling_rights::CastlingRights, color::Color, piece::PieceType, square::{Square, SquareParseError}};
+use crate::{castling_rights::CastlingRights, color::Color, impl_error, impl_error_from, move_flag::MoveFlag, move_generation::MoveGeneration, piece::PieceType, position::Position, rank::Rank, square::{Square, SquareParseError}};
 
 #[derive(Debug)]
-pub struct FenParseError(pub &'static str);
+pub struct FenError(pub &'static str);
+
+impl_error!(FenError);
+impl_error_from!(SquareParseError, FenError);
+
 pub struct Fen { }
 
 impl Fen {
@@ -11,54 +15,117 @@ impl Fen {
     pub const TRICKY_POSITION: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ -";
     pub const TRICKY_POSITION_2: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
 
-    pub fn parse(fen_string: &str) -> Result<Position, FenParseError> {
+    pub fn parse(fen_string: &str) -> Result<Position, FenError> {
         let mut pos = Position::default();
         
         let mut fen_iter = fen_string.split_whitespace();
-        let pieces_str = fen_iter.next().ok_or(FenParseError("No pieces found!"))?;
-        let side_str = fen_iter.next().ok_or(FenParseError("No side found!"))?;
-        let castling_rights_str = fen_iter.next().ok_or(FenParseError("No castling rights found!"))?;
-        let en_passant_sq_str = fen_iter.next().ok_or(FenParseError("No en-passant found!"))?;
+        let pieces_str = fen_iter.next().ok_or(FenError("No pieces found!"))?;
+        let side_str = fen_iter.next().ok_or(FenError("No side found!"))?;
+        let castling_rights_str = fen_iter.next().ok_or(FenError("No castling rights found!"))?;
+        let en_passant_sq_str = fen_iter.next().ok_or(FenError("No en-passant found!"))?;
+        // NOTE: The clocks are optional, since a lot of test suites leave them out
+        let halfmove_clock_str = fen_iter.next().unwrap_or("0");
+        let fullmove_number_str = fen_iter.next().unwrap_or("1");
+
+        if fen_iter.next().is_some() {
+            return Err(FenError("Too many fields!"));
+        }
         
         Self::set_pieces(&mut pos, pieces_str)?;
         Self::set_side(&mut pos, side_str)?;
         Self::set_castling_rights(&mut pos, castling_rights_str)?;
         Self::set_en_passant_sq(&mut pos, en_passant_sq_str)?;
+        Self::set_clocks(&mut pos, halfmove_clock_str, fullmove_number_str)?;
+        pos.zobrist_key = pos.compute_zobrist_key();
+        pos.update_checks_and_pins();
         
         Ok(pos)
     }
     
-    fn set_pieces(position: &mut Position, pieces_str: &str) -> Result<(), FenParseError> {
-        let mut sq_index = 0_u8;
-        for pieces_char in pieces_str.chars() {
-            match pieces_char {
-                '1'..='8' => sq_index += pieces_char
-                .to_digit(10)
-                .ok_or(FenParseError("Could not convert char to digit!"))? as u8,
-                '/' => (),
-                'P' | 'N' | 'B' | 'R' | 'Q' | 'K' | 'p' | 'n' | 'b' | 'r' | 'q' | 'k' => {
-                    let piece_type = PieceType::from(pieces_char);
-                    position.set_piece(piece_type, Square::from(sq_index));
-                }
-                _ => return Err(FenParseError("Invalid pieces!"))
+    // Rewrites the FEN so that the same position always gets the same FEN, which lets books and
+    // databases find duplicates by comparing strings. Castling rights are dropped when the king or
+    // rook isn't on its square anymore, and the en-passant square is only kept when a legal
+    // en-passant capture exists, since neither changes what can happen in the position otherwise.
+    pub fn canonicalize(fen_string: &str) -> Result<String, FenError> {
+        let mut position = Self::parse(fen_string)?;
+
+        for (right, color, rook_squares) in [
+            (CastlingRights::WK, Color::White, position.castling_squares.king_side_rook),
+            (CastlingRights::WQ, Color::White, position.castling_squares.queen_side_rook),
+            (CastlingRights::BK, Color::Black, position.castling_squares.king_side_rook),
+            (CastlingRights::BQ, Color::Black, position.castling_squares.queen_side_rook),
+        ] {
+            let (king, rook) = match color {
+                Color::White => (PieceType::WK, PieceType::WR),
+                Color::Black => (PieceType::BK, PieceType::BR),
             };
-            if !pieces_char.is_ascii_digit() && pieces_char != '/' { sq_index += 1; }
+            let in_place = position.get_piece(position.castling_squares.king[color]) == king
+                && position.get_piece(rook_squares[color]) == rook;
+            if !in_place {
+                position.castling_rights.0 &= !right.0;
+            }
+        }
+        // NOTE: The moves below are made on the position, so its key has to follow the new rights
+        position.zobrist_key = position.compute_zobrist_key();
+
+        let can_capture_en_passant = MoveGeneration::generate_legal_moves(&position)
+            .iter()
+            .any(|bit_move| bit_move.flag() == MoveFlag::EnPassant);
+        if !can_capture_en_passant {
+            position.en_passant_sq = Square::None;
         }
+
+        position.zobrist_key = position.compute_zobrist_key();
+        Ok(position.to_fen())
+    }
+
+    fn set_pieces(position: &mut Position, pieces_str: &str) -> Result<(), FenError> {
+        let ranks: Vec<&str> = pieces_str.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError("Pieces don't have exactly 8 ranks!"));
+        }
+
+        for (rank_index, rank_str) in ranks.into_iter().enumerate() {
+            let mut file_index = 0_u8;
+            for pieces_char in rank_str.chars() {
+                match pieces_char {
+                    '1'..='8' => file_index += pieces_char as u8 - b'0',
+                    'P' | 'N' | 'B' | 'R' | 'Q' | 'K' | 'p' | 'n' | 'b' | 'r' | 'q' | 'k' => {
+                        if file_index >= 8 {
+                            return Err(FenError("Rank doesn't have exactly 8 squares!"));
+                        }
+                        let piece_type = PieceType::from(pieces_char);
+                        position.set_piece(piece_type, Square::from(rank_index as u8 * 8 + file_index));
+                        file_index += 1;
+                    }
+                    _ => return Err(FenError("Invalid pieces!"))
+                };
+            }
+
+            if file_index != 8 {
+                return Err(FenError("Rank doesn't have exactly 8 squares!"));
+            }
+        }
+
+        if position.bbs[PieceType::WK].count_bits() != 1 || position.bbs[PieceType::BK].count_bits() != 1 {
+            return Err(FenError("Each side needs exactly one king!"));
+        }
+
         position.populate_occupancies();
         Ok(())
     }
     
-    fn set_side(position: &mut Position, side_str: &str) -> Result<(), FenParseError> {
+    fn set_side(position: &mut Position, side_str: &str) -> Result<(), FenError> {
         match side_str {
             "w" => position.side = Color::White,
             "b" => position.side = Color::Black,
-            _ => return Err(FenParseError("Invalid side!")),
+            _ => return Err(FenError("Invalid side!")),
         }
         
         Ok(())
     }
     
-    fn set_castling_rights(position: &mut Position, castling_rights_str: &str) -> Result<(), FenParseError> {
+    fn set_castling_rights(position: &mut Position, castling_rights_str: &str) -> Result<(), FenError> {
         for char in castling_rights_str.chars() {
             match char {
                 'K' => position.castling_rights.0 |= CastlingRights::WK.0,
@@ -66,21 +133,101 @@ impl Fen {
                 'k' => position.castling_rights.0 |= CastlingRights::BK.0,
                 'q' => position.castling_rights.0 |= CastlingRights::BQ.0,
                 '-' => (),
-                _ => return Err(FenParseError("Invalid castling rights!")),
+                _ => return Err(FenError("Invalid castling rights!")),
             }
         }
         
         Ok(())
     }
     
-    fn set_en_passant_sq(position: &mut Position, en_passant_sq_str: &str) -> Result<(), FenParseError> {
+    fn set_en_passant_sq(position: &mut Position, en_passant_sq_str: &str) -> Result<(), FenError> {
         match en_passant_sq_str {
             "-" => Ok(()),
-        _ => {
-            position.en_passant_sq = Square::try_from(en_passant_sq_str)
-            .map_err(|SquareParseError(msg)| FenParseError(msg))?;
-            Ok(())
+            _ => {
+                let en_passant_sq = Square::try_from(en_passant_sq_str)?;
+                let expected_rank = match position.side {
+                    Color::White => Rank::R6,
+                    Color::Black => Rank::R3,
+                };
+                if en_passant_sq.rank() != expected_rank {
+                    return Err(FenError("En-passant square is on the wrong rank!"));
+                }
+                position.en_passant_sq = en_passant_sq;
+                Ok(())
             }
         }
     }
+
+    fn set_clocks(position: &mut Position, halfmove_clock_str: &str, fullmove_number_str: &str) -> Result<(), FenError> {
+        position.halfmove_clock = halfmove_clock_str.parse().map_err(|_| FenError("Invalid halfmove clock!"))?;
+        position.fullmove_number = fullmove_number_str.parse().map_err(|_| FenError("Invalid fullmove number!"))?;
+
+        if position.fullmove_number == 0 {
+            return Err(FenError("Fullmove number starts at 1!"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_malformed_fens() {
+        assert!(Fen::parse(Fen::STARTING_POSITION).is_ok());
+        assert!(Fen::parse("").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq -").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq -").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq -").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/9/8/8/PPPPPPPP/RNBQKBNR w KQkq -").is_err());
+        assert!(Fen::parse("rnbqkbnr/ppppppppp/7/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").is_err());
+        assert!(Fen::parse("rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ -").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0").is_err());
+        assert!(Fen::parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 extra").is_err());
+    }
+
+    #[test]
+    fn to_fen_round_trips() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ];
+        for fen in fens {
+            assert_eq!(Position::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn canonicalize_drops_unusable_castling_rights_and_en_passant() {
+        crate::move_masks::init();
+
+        // The h1 rook is gone, and no black pawn can take on e3
+        assert_eq!(
+            Fen::canonicalize("r3k2r/8/8/8/4P3/8/8/R3K3 b KQkq e3 0 1").unwrap(),
+            "r3k2r/8/8/8/4P3/8/8/R3K3 b Qkq - 0 1"
+        );
+        // The pawn on d4 can take en passant, so the square stays
+        assert_eq!(
+            Fen::canonicalize("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap(),
+            "4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"
+        );
+        assert_eq!(Fen::canonicalize("4k3/8/8/8/8/8/8/4K3 w qkQK - 3 9").unwrap(), "4k3/8/8/8/8/8/8/4K3 w - - 3 9");
+    }
+
+    #[test]
+    fn parse_reads_clocks() {
+        let position = Position::from_fen(Fen::TRICKY_POSITION_2).unwrap();
+        assert_eq!((position.halfmove_clock, position.fullmove_number), (1, 8));
+
+        let position = Position::from_fen(Fen::KIWIPETE_POSITION).unwrap();
+        assert_eq!((position.halfmove_clock, position.fullmove_number), (0, 1));
+    }
 }
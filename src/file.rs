@@ -1,6 +1,8 @@
 use core::fmt;
 use std::mem::transmute;
 
+use crate::impl_error;
+
 #[derive(Clone, Copy, PartialEq)]
 #[repr(u8)]
 pub enum File {
@@ -24,6 +26,8 @@ impl From<u8> for File {
 #[derive(Debug)]
 pub struct FileParseError(pub &'static str);
 
+impl_error!(FileParseError);
+
 impl TryFrom<char> for File {
     type Error = FileParseError;
 
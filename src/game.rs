@@ -0,0 +1,285 @@
+use std::{fs, io};
+
+use crate::{bit_move::BitMove, color::Color, game_result::GameResult, position::Position};
+
+// The tags every PGN game starts with, along with the value for when it isn't known
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+// Keeps every position of a game, so moves can be taken back and replayed without undo info
+pub struct Game {
+    positions: Vec<Position>,
+    moves: Vec<BitMove>,
+    undone_moves: Vec<BitMove>,
+    // PGN tags like ("Event", "Casual game"), in the order they were set
+    tags: Vec<(String, String)>,
+}
+
+impl Game {
+    pub fn new(start_position: Position) -> Game {
+        Game {
+            positions: vec![start_position],
+            moves: Vec::new(),
+            undone_moves: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag_name, _)| tag_name == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some((_, tag_value)) => *tag_value = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    #[inline(always)]
+    pub fn position(&self) -> &Position {
+        self.positions.last().unwrap()
+    }
+
+    #[inline(always)]
+    pub fn start_position(&self) -> &Position {
+        &self.positions[0]
+    }
+
+    #[inline(always)]
+    pub fn moves(&self) -> &[BitMove] {
+        &self.moves
+    }
+
+    // Plays a move and forgets the moves that were taken back. Returns false for illegal moves.
+    pub fn make_move(&mut self, bit_move: BitMove) -> bool {
+        if self.play(bit_move) {
+            self.undone_moves.clear();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<BitMove> {
+        let bit_move = self.moves.pop()?;
+        self.positions.pop();
+        self.undone_moves.push(bit_move);
+        Some(bit_move)
+    }
+
+    pub fn redo(&mut self) -> Option<BitMove> {
+        let bit_move = self.undone_moves.pop()?;
+        let is_legal = self.play(bit_move);
+        debug_assert!(is_legal, "Redone move must be legal!");
+        Some(bit_move)
+    }
+
+    fn play(&mut self, bit_move: BitMove) -> bool {
+        let mut position = self.position().clone();
+        if !position.make_move(bit_move) {
+            return false;
+        }
+        self.positions.push(position);
+        self.moves.push(bit_move);
+        true
+    }
+
+    pub fn game_result(&self) -> Option<GameResult> {
+        self.position().game_result(&self.history())
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.position().is_threefold_repetition(&self.history())
+    }
+
+    // The zobrist keys of the positions before the current one, oldest first
+    fn history(&self) -> Vec<u64> {
+        self.positions[..self.positions.len() - 1].iter().map(|position| position.zobrist_key).collect()
+    }
+
+    // Movetext with move numbers in standard algebraic notation, like "1. e4 e5 2. Nf3"
+    pub fn to_san_movetext(&self) -> String {
+        let start_position = self.start_position();
+        let mut movetext = Vec::new();
+
+        for (ply, (position, &bit_move)) in self.positions.iter().zip(&self.moves).enumerate() {
+            let move_number = start_position.fullmove_number as usize
+                + (ply + (start_position.side == Color::Black) as usize) / 2;
+            if position.side == Color::White {
+                movetext.push(format!("{}.", move_number));
+            } else if ply == 0 {
+                movetext.push(format!("{}...", move_number));
+            }
+            movetext.push(position.move_to_san(bit_move));
+        }
+
+        movetext.join(" ")
+    }
+
+    // The result of a finished game, otherwise the one given in the Result tag, like after a
+    // resignation, and "*" for games that are still going
+    pub fn result_token(&self) -> String {
+        match self.game_result() {
+            Some(result) => result.to_score_string().to_owned(),
+            None => self.tag("Result").unwrap_or("*").to_owned(),
+        }
+    }
+
+    // The Seven Tag Roster comes first, with unknown values as "?", followed by the other tags.
+    // Games that don't start from the usual position also get the SetUp and FEN tags.
+    pub fn to_pgn(&self) -> String {
+        let result = self.result_token();
+        let mut tags: Vec<(&str, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|&(name, unknown)| (name, self.tag(name).unwrap_or(unknown).to_owned()))
+            .collect();
+        tags[6].1 = result.clone();
+
+        let start_fen = self.start_position().to_fen();
+        let has_setup = start_fen != Position::starting_position().to_fen();
+        if has_setup {
+            tags.push(("SetUp", "1".to_owned()));
+            tags.push(("FEN", start_fen));
+        }
+        for (name, value) in &self.tags {
+            let is_written = SEVEN_TAG_ROSTER.iter().any(|&(roster_name, _)| roster_name == name)
+                || (name == "SetUp" || name == "FEN") && has_setup;
+            if !is_written {
+                tags.push((name, value.clone()));
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            let escaped_value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escaped_value));
+        }
+        pgn.push('\n');
+
+        // NOTE: Export format keeps lines of movetext below 80 characters
+        let movetext = self.to_san_movetext();
+        let mut line_length = 0;
+        for token in movetext.split_whitespace().chain([result.as_str()]) {
+            if line_length > 0 && line_length + 1 + token.len() >= 80 {
+                pgn.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                pgn.push(' ');
+                line_length += 1;
+            }
+            pgn.push_str(token);
+            line_length += token.len();
+        }
+        pgn.push('\n');
+        pgn
+    }
+
+    pub fn save_pgn(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_pgn())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_generation::MoveGeneration, move_masks};
+
+    use super::*;
+
+    fn find_move(position: &Position, uci_move: &str) -> BitMove {
+        *MoveGeneration::generate_legal_moves(position)
+            .iter()
+            .find(|m| m.to_uci_string() == uci_move)
+            .unwrap()
+    }
+
+    #[test]
+    fn game_undoes_and_redoes_moves() {
+        move_masks::init();
+        let mut game = Game::new(Position::starting_position());
+
+        for uci_move in ["e2e4", "e7e5", "g1f3"] {
+            let bit_move = find_move(game.position(), uci_move);
+            assert!(game.make_move(bit_move));
+        }
+        assert_eq!(game.to_san_movetext(), "1. e4 e5 2. Nf3");
+
+        assert!(game.undo().is_some());
+        assert!(game.undo().is_some());
+        assert_eq!(game.to_san_movetext(), "1. e4");
+        assert!(game.redo().is_some());
+        assert_eq!(game.to_san_movetext(), "1. e4 e5");
+
+        let bit_move = find_move(game.position(), "b1c3");
+        assert!(game.make_move(bit_move));
+        assert!(game.redo().is_none());
+        assert_eq!(game.to_san_movetext(), "1. e4 e5 2. Nc3");
+    }
+
+    #[test]
+    fn to_pgn_writes_the_tags_movetext_and_result() {
+        move_masks::init();
+        let mut game = Game::new(Position::starting_position());
+        game.set_tag("White", "Sisyphus32");
+        game.set_tag("Annotator", "A \"careful\" reader");
+        for uci_move in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let bit_move = find_move(game.position(), uci_move);
+            assert!(game.make_move(bit_move));
+        }
+
+        let pgn = game.to_pgn();
+        assert_eq!(pgn, concat!(
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n",
+            "[White \"Sisyphus32\"]\n[Black \"?\"]\n[Result \"0-1\"]\n",
+            "[Annotator \"A \\\"careful\\\" reader\"]\n\n",
+            "1. f3 e5 2. g4 Qh4# 0-1\n",
+        ));
+        let parsed = &crate::pgn::Pgn::parse(&pgn).unwrap()[0];
+        assert_eq!(parsed.tag("Annotator"), Some("A \"careful\" reader"));
+        assert_eq!(parsed.to_san_movetext(), game.to_san_movetext());
+
+        let mut game = Game::new(Fen::parse("4k3/8/8/8/8/8/8/R3K3 w Q - 0 40").unwrap());
+        game.set_tag("Result", "1-0");
+        for _ in 0..20 {
+            for uci_move in ["a1a2", "e8d8", "a2a1", "d8e8"] {
+                let bit_move = find_move(game.position(), uci_move);
+                assert!(game.make_move(bit_move));
+            }
+        }
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Result \"1/2-1/2\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 40\"]\n\n40. Ra2"));
+        assert!(pgn.lines().all(|line| line.len() < 80));
+        assert!(pgn.ends_with("Ke8 1/2-1/2\n") || pgn.ends_with("Ke8\n1/2-1/2\n"));
+    }
+
+    #[test]
+    fn game_result_finds_mates_and_repetitions() {
+        move_masks::init();
+        let mut game = Game::new(Position::starting_position());
+        for uci_move in ["f2f3", "e7e5", "g2g4"] {
+            let bit_move = find_move(game.position(), uci_move);
+            assert!(game.make_move(bit_move));
+            assert_eq!(game.game_result(), None);
+        }
+        let bit_move = find_move(game.position(), "d8h4");
+        assert!(game.make_move(bit_move));
+        assert_eq!(game.game_result(), Some(GameResult::Checkmate(Color::Black)));
+        assert_eq!(game.game_result().unwrap().to_score_string(), "0-1");
+
+        let mut game = Game::new(Position::starting_position());
+        for (index, uci_move) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].iter().enumerate() {
+            assert_eq!(game.game_result(), None);
+            let bit_move = find_move(game.position(), uci_move);
+            assert!(game.make_move(bit_move));
+            assert_eq!(game.is_threefold_repetition(), index == 7);
+        }
+        assert_eq!(game.game_result(), Some(GameResult::ThreefoldRepetition));
+    }
+}
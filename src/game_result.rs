@@ -0,0 +1,46 @@
+use core::fmt;
+
+use crate::color::Color;
+
+// How a game ended. Checkmate holds the side that delivered the mate, every other ending is a draw.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameResult {
+    Checkmate(Color),
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+    DeadPosition,
+}
+
+impl GameResult {
+    #[inline(always)]
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameResult::Checkmate(winner) => Some(winner),
+            _ => None,
+        }
+    }
+
+    // The score as it is written at the end of a PGN game
+    pub fn to_score_string(self) -> &'static str {
+        match self.winner() {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameResult::Checkmate(winner) => write!(f, "{} wins by checkmate", winner),
+            GameResult::Stalemate => f.pad("Draw by stalemate"),
+            GameResult::FiftyMoveRule => f.pad("Draw by the fifty-move rule"),
+            GameResult::ThreefoldRepetition => f.pad("Draw by threefold repetition"),
+            GameResult::InsufficientMaterial => f.pad("Draw by insufficient material"),
+            GameResult::DeadPosition => f.pad("Draw by dead position"),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{piece::PieceType, square::Square};
+
+// A move as the history tables see it, which is the piece that moved and the square it went to
+pub type PieceTarget = (PieceType, Square);
+
+// Indexed by the piece and target of the earlier move and then of the quiet move
+pub struct ContinuationHistory {
+    table: Vec<i16>,
+}
+
+impl ContinuationHistory {
+    // NOTE: The gravity in update keeps every entry within this bound
+    pub const MAX: i32 = 8192;
+
+    pub fn new() -> ContinuationHistory {
+        ContinuationHistory { table: vec![0; 12 * 64 * 12 * 64] }
+    }
+
+    #[inline(always)]
+    fn index((previous_piece, previous_target): PieceTarget, (piece, target): PieceTarget) -> usize {
+        ((previous_piece as usize * 64 + previous_target as usize) * 12 + piece as usize) * 64 + target as usize
+    }
+
+    #[inline(always)]
+    pub fn get(&self, previous: Option<PieceTarget>, current: PieceTarget) -> i16 {
+        previous.map_or(0, |previous| self.table[Self::index(previous, current)])
+    }
+
+    // Moves the entry towards the bonus, by less the closer it already is to the bound, so entries
+    // that keep getting the same bonus settle instead of overflowing
+    #[inline(always)]
+    pub fn update(&mut self, previous: Option<PieceTarget>, current: PieceTarget, bonus: i32) {
+        if let Some(previous) = previous {
+            let entry = &mut self.table[Self::index(previous, current)];
+            let bonus = bonus.clamp(-Self::MAX, Self::MAX);
+            *entry += (bonus - *entry as i32 * bonus.abs() / Self::MAX) as i16;
+        }
+    }
+}
+
+impl Default for ContinuationHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_saturates_within_bounds() {
+        let mut history = ContinuationHistory::new();
+        let previous = Some((PieceType::WP, Square::E4));
+        let current = (PieceType::BN, Square::F6);
+
+        for _ in 0..1000 {
+            history.update(previous, current, 2000);
+        }
+        let entry = history.get(previous, current) as i32;
+        assert!(entry > ContinuationHistory::MAX * 9 / 10 && entry <= ContinuationHistory::MAX);
+
+        for _ in 0..1000 {
+            history.update(previous, current, -2000);
+        }
+        assert!(history.get(previous, current) as i32 >= -ContinuationHistory::MAX);
+        assert!(history.get(previous, current) < 0);
+        assert_eq!(history.get(None, current), 0);
+        assert_eq!(history.get(Some((PieceType::WP, Square::E3)), current), 0);
+    }
+}
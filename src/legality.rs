@@ -0,0 +1,264 @@
+use core::fmt;
+
+use crate::{bit_move::BitMove, castling_rights::CastlingRights, color::Color, fen::{Fen, FenError}, impl_error, impl_error_from, move_flag::MoveFlag, move_generation::MoveGeneration, piece::PieceType, position::Position, square::Square};
+
+#[derive(Debug)]
+pub struct LegalityError(pub &'static str);
+
+impl_error!(LegalityError);
+impl_error_from!(FenError, LegalityError);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IllegalReason {
+    NoSuchMove,
+    AmbiguousMove,
+    Pinned,
+    LeavesKingInCheck,
+    NoCastlingRight,
+    CastlingPathBlocked,
+    CastlingOutOfCheck,
+    CastlingThroughCheck,
+}
+
+impl fmt::Display for IllegalReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            IllegalReason::NoSuchMove => "no piece can make this move",
+            IllegalReason::AmbiguousMove => "more than one piece can make this move",
+            IllegalReason::Pinned => "the piece is pinned to its king",
+            IllegalReason::LeavesKingInCheck => "the king would be left in check",
+            IllegalReason::NoCastlingRight => "the king or rook has already moved",
+            IllegalReason::CastlingPathBlocked => "there are pieces between the king and the rook",
+            IllegalReason::CastlingOutOfCheck => "the king can't castle out of check",
+            IllegalReason::CastlingThroughCheck => "the king can't castle through or into an attacked square",
+        };
+        f.pad(reason)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Legality {
+    Legal(BitMove),
+    Illegal(IllegalReason),
+}
+
+enum MoveRequest {
+    Castle { king_side: bool },
+    Candidates(Vec<BitMove>),
+}
+
+// Explains why a move given in UCI or SAN notation is illegal
+pub struct LegalityChecker { }
+
+impl LegalityChecker {
+    pub fn check_fen(fen: &str, move_string: &str) -> Result<Legality, LegalityError> {
+        Self::check(&Fen::parse(fen)?, move_string)
+    }
+
+    pub fn check(position: &Position, move_string: &str) -> Result<Legality, LegalityError> {
+        let request = match Self::is_uci_move(move_string) {
+            true => Self::parse_uci(position, move_string)?,
+            false => Self::parse_san(position, move_string)?,
+        };
+
+        Ok(match request {
+            MoveRequest::Castle { king_side } => Self::check_castling(position, king_side),
+            MoveRequest::Candidates(candidates) => Self::check_candidates(position, &candidates),
+        })
+    }
+
+    fn is_uci_move(move_string: &str) -> bool {
+        let bytes = move_string.as_bytes();
+        move_string.is_ascii() && (bytes.len() == 4 || bytes.len() == 5)
+            && Square::try_from(&move_string[0..2]).is_ok()
+            && Square::try_from(&move_string[2..4]).is_ok()
+            && bytes.get(4).is_none_or(|promotion| b"nbrq".contains(promotion))
+    }
+
+    fn parse_uci(position: &Position, move_string: &str) -> Result<MoveRequest, LegalityError> {
+        let source = Square::try_from(&move_string[0..2]).map_err(|_| LegalityError("Couldn't parse source square!"))?;
+        let target = Square::try_from(&move_string[2..4]).map_err(|_| LegalityError("Couldn't parse target square!"))?;
+        let promotion = move_string.chars().nth(4);
+
+        let castling_squares = position.castling_squares;
+        if source == castling_squares.king[position.side] && position.get_piece(source) == Self::own(position, PieceType::WK) {
+            if target == castling_squares.king_side_king_target(position.side) {
+                return Ok(MoveRequest::Castle { king_side: true });
+            }
+            if target == castling_squares.queen_side_king_target(position.side) {
+                return Ok(MoveRequest::Castle { king_side: false });
+            }
+        }
+
+        Ok(MoveRequest::Candidates(
+            MoveGeneration::generate_pseudo_legal_moves(position)
+                .into_iter()
+                .filter(|m| m.source() == source && m.target() == target && m.promotion().map(|p| p.to_char()) == promotion)
+                .collect()
+        ))
+    }
+
+    // NOTE: Check and annotation symbols are ignored, and so is the capture sign, since a move
+    // onto an enemy piece is a capture either way
+    fn parse_san(position: &Position, move_string: &str) -> Result<MoveRequest, LegalityError> {
+        let san = move_string.trim_end_matches(['+', '#', '!', '?']);
+        match san {
+            "O-O" | "0-0" => return Ok(MoveRequest::Castle { king_side: true }),
+            "O-O-O" | "0-0-0" => return Ok(MoveRequest::Castle { king_side: false }),
+            _ => {}
+        }
+
+        let (san, promotion) = match san.char_indices().last() {
+            Some((index, ch)) if "NBRQ".contains(ch) && index >= 2 => (san[..index].trim_end_matches('='), Some(ch.to_ascii_lowercase())),
+            _ => (san, None),
+        };
+
+        let (piece, san) = match san.chars().next() {
+            Some(ch) if "NBRQK".contains(ch) => (Self::own(position, PieceType::from(ch)), &san[1..]),
+            Some(_) => (Self::own(position, PieceType::WP), san),
+            None => return Err(LegalityError("Found empty move string!")),
+        };
+
+        let san = san.replace('x', "");
+        if san.len() < 2 || !san.is_char_boundary(san.len() - 2) {
+            return Err(LegalityError("Couldn't parse target square!"));
+        }
+        let (disambiguation, target) = san.split_at(san.len() - 2);
+        let target = Square::try_from(target).map_err(|_| LegalityError("Couldn't parse target square!"))?;
+        if disambiguation.len() > 2 || !disambiguation.chars().all(|ch| matches!(ch, 'a'..='h' | '1'..='8')) {
+            return Err(LegalityError("Couldn't parse move!"));
+        }
+
+        Ok(MoveRequest::Candidates(
+            MoveGeneration::generate_pseudo_legal_moves(position)
+                .into_iter()
+                .filter(|m| {
+                    let source = m.source().to_string();
+                    m.target() == target
+                        && m.flag() != MoveFlag::Castle
+                        && position.get_piece(m.source()) == piece
+                        && m.promotion().map(|p| p.to_char()) == promotion
+                        && disambiguation.chars().all(|ch| source.contains(ch))
+                })
+                .collect()
+        ))
+    }
+
+    // The piece of the side to move of the same kind as the given white piece
+    fn own(position: &Position, piece: PieceType) -> PieceType {
+        match position.side {
+            Color::White => PieceType::WHITE_PIECES[piece as usize % 6],
+            Color::Black => PieceType::BLACK_PIECES[piece as usize % 6],
+        }
+    }
+
+    fn check_candidates(position: &Position, candidates: &[BitMove]) -> Legality {
+        let legal_moves: Vec<BitMove> = candidates
+            .iter()
+            .copied()
+            .filter(|&m| position.clone().make_move(m))
+            .collect();
+
+        match (legal_moves.as_slice(), candidates.first()) {
+            ([legal_move], _) => Legality::Legal(*legal_move),
+            ([_, _, ..], _) => Legality::Illegal(IllegalReason::AmbiguousMove),
+            ([], None) => Legality::Illegal(IllegalReason::NoSuchMove),
+            ([], Some(&candidate)) => Legality::Illegal(Self::illegal_reason(position, candidate)),
+        }
+    }
+
+    // A move of a pinned piece off its line exposes the king. Everything else that makes a
+    // pseudo-legal move illegal leaves the king in check.
+    fn illegal_reason(position: &Position, bit_move: BitMove) -> IllegalReason {
+        if position.pinned(position.side).is_set_sq(bit_move.source()) {
+            IllegalReason::Pinned
+        } else {
+            IllegalReason::LeavesKingInCheck
+        }
+    }
+
+    fn right(side: Color, king_side: bool) -> CastlingRights {
+        match (side, king_side) {
+            (Color::White, true) => CastlingRights::WK,
+            (Color::White, false) => CastlingRights::WQ,
+            (Color::Black, true) => CastlingRights::BK,
+            (Color::Black, false) => CastlingRights::BQ,
+        }
+    }
+
+    // The squares the king passes through are left to the move generation, which only generates
+    // castling if none of them are attacked
+    fn check_castling(position: &Position, king_side: bool) -> Legality {
+        let side = position.side;
+        let castling_squares = position.castling_squares;
+        let (has_right, empty_mask, target) = match king_side {
+            true => (position.castling_rights.0 & Self::right(side, true).0 != 0, castling_squares.king_side_empty_mask(side), castling_squares.king_side_king_target(side)),
+            false => (position.castling_rights.0 & Self::right(side, false).0 != 0, castling_squares.queen_side_empty_mask(side), castling_squares.queen_side_king_target(side)),
+        };
+
+        if !has_right {
+            return Legality::Illegal(IllegalReason::NoCastlingRight);
+        }
+        if (position.ao & empty_mask).is_not_empty() {
+            return Legality::Illegal(IllegalReason::CastlingPathBlocked);
+        }
+        if position.in_check() {
+            return Legality::Illegal(IllegalReason::CastlingOutOfCheck);
+        }
+
+        MoveGeneration::generate_legal_moves(position)
+            .into_iter()
+            .find(|m| m.flag() == MoveFlag::Castle && m.target() == target)
+            .map_or(Legality::Illegal(IllegalReason::CastlingThroughCheck), Legality::Legal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    fn reason(fen: &str, move_string: &str) -> Option<IllegalReason> {
+        match LegalityChecker::check_fen(fen, move_string).unwrap() {
+            Legality::Legal(_) => None,
+            Legality::Illegal(reason) => Some(reason),
+        }
+    }
+
+    #[test]
+    fn explains_illegal_moves_in_uci_and_san() {
+        move_masks::init();
+
+        // The knight on d2 is pinned by the bishop on b4, and the king is attacked on f2 by the queen
+        let pinned = "4k3/8/8/8/1b3q2/8/3N4/4K2R w K - 0 1";
+        assert_eq!(reason(pinned, "d2f3"), Some(IllegalReason::Pinned));
+        assert_eq!(reason(pinned, "Nf3"), Some(IllegalReason::Pinned));
+        assert_eq!(reason(pinned, "e1f2"), Some(IllegalReason::LeavesKingInCheck));
+        assert_eq!(reason(pinned, "Ke2"), None);
+        assert_eq!(reason(pinned, "Nd2d4"), Some(IllegalReason::NoSuchMove));
+        assert_eq!(reason(pinned, "O-O"), Some(IllegalReason::CastlingThroughCheck));
+        assert_eq!(reason(pinned, "e1c1"), Some(IllegalReason::NoCastlingRight));
+
+        let starting_position = Fen::STARTING_POSITION;
+        assert_eq!(reason(starting_position, "e1g1"), Some(IllegalReason::CastlingPathBlocked));
+        assert_eq!(reason(starting_position, "e4"), None);
+        assert_eq!(reason(starting_position, "Nf3+"), None);
+        assert_eq!(reason(starting_position, "e2e5"), Some(IllegalReason::NoSuchMove));
+
+        // In check by the rook, so the other moves don't help
+        let check = "4r1k1/8/8/8/8/8/3P4/R3K1N1 w Q - 0 1";
+        assert_eq!(reason(check, "d2d4"), Some(IllegalReason::LeavesKingInCheck));
+        assert_eq!(reason(check, "O-O-O"), Some(IllegalReason::CastlingOutOfCheck));
+        assert_eq!(reason(check, "Kd1"), None);
+
+        // Both knights can reach d2 unless one of them is named
+        let ambiguous = "4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1";
+        assert_eq!(reason(ambiguous, "Nd2"), Some(IllegalReason::AmbiguousMove));
+        assert_eq!(reason(ambiguous, "Nbd2"), None);
+
+        assert_eq!(reason("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b8=Q+"), None);
+        assert_eq!(reason("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8n"), None);
+        assert!(LegalityChecker::check_fen(Fen::STARTING_POSITION, "hello").is_err());
+    }
+}
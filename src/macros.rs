@@ -10,3 +10,45 @@ macro_rules! pl {
         )*
     };
 }
+
+// Implements Display and Error for error types wrapping a static message
+#[macro_export]
+macro_rules! impl_error {
+    ($error:ident) => {
+        impl core::fmt::Display for $error {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.pad(self.0)
+            }
+        }
+
+        impl std::error::Error for $error { }
+    };
+}
+
+// Allows converting between error types with the ? operator by keeping the message
+#[macro_export]
+macro_rules! impl_error_from {
+    ($from:ident, $to:ident) => {
+        impl From<$from> for $to {
+            fn from($from(msg): $from) -> Self {
+                $to(msg)
+            }
+        }
+    };
+}
+
+// bb!(E4, D5), bb!(files: FA, FH) or bb!(ranks: R1, R8)
+#[macro_export]
+macro_rules! bb {
+    (files: $($file:ident),+ $(,)?) => {
+        $crate::bitboard::Bitboard($($crate::bitboard::Bitboard::file($crate::file::File::$file).0)|+)
+    };
+
+    (ranks: $($rank:ident),+ $(,)?) => {
+        $crate::bitboard::Bitboard($($crate::bitboard::Bitboard::rank($crate::rank::Rank::$rank).0)|+)
+    };
+
+    ($($square:ident),+ $(,)?) => {
+        $crate::bitboard::Bitboard::from_squares(&[$($crate::square::Square::$square),+])
+    };
+}
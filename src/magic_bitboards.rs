@@ -29,7 +29,7 @@ impl MagicBitboardGenerator {
     pub fn generate_magic_bitboard(&mut self, square: Square, num_relevant_bits: u8, is_bishop: bool) -> Bitboard {
         let mut occupancies = [Bitboard::EMPTY; 4096];
         let mut moves = [Bitboard::EMPTY; 4096];
-        let mask = unsafe { if is_bishop { move_masks::BISHOP_MASKS[square] } else { move_masks::ROOK_MASKS[square] } };
+        let mask = move_masks::get_relevant_occupancy_mask(square, is_bishop);
         let max_occupancy_index = 1 << num_relevant_bits;
 
         for i in 0..max_occupancy_index {
@@ -3,26 +3,62 @@
 mod bit_move;
 mod bitboard;
 mod position;
+mod position_diff;
 mod castling_rights;
+mod checkpoint;
 mod color;
+mod cpu_features;
+mod datagen;
+mod distributed_perft;
+mod engine_options;
+mod epd;
 mod uci;
 mod fen;
+mod game;
+mod game_result;
+mod history;
 mod file;
+mod legality;
 mod macros;
 mod magic_bitboards;
 mod move_masks;
 mod move_list;
+mod move_ordering;
+mod move_picker;
 mod piece;
 mod rank;
 mod square;
 mod timer;
+mod test_suite;
+mod tt;
+mod tune;
+mod wdl;
 mod perft;
+mod pgn;
 mod bit_twiddles;
 mod move_flag;
 mod search;
+mod search_shared;
+mod see;
 mod eval;
 mod move_generation;
+mod node_counter;
+mod nnue;
+mod play;
+mod playout;
+mod problem;
+mod profile;
+mod opponent_model;
+mod regression;
+mod repro;
+mod xboard;
+mod zobrist;
 
+use std::env;
+
+use cpu_features::CpuFeatures;
+use engine_options::EngineOptions;
+use profile::Profile;
 use uci::Uci;
 
 // NOTE: The following pairs of features are not allowed to be used together:
@@ -38,10 +74,48 @@ compile_error!("feature \"revert_with_clone\" and feature \"revert_with_undo_mov
 #[cfg(all(feature = "sliders_magic_bitboards", feature = "sliders_on_the_fly"))]
 compile_error!("feature \"sliders_magic_bitboards\" and feature \"sliders_on_the_fly\" cannot be enabled at the same time!");
 
+#[cfg(any(
+    all(feature = "search_minimax", feature = "search_alpha_beta"),
+    all(feature = "search_minimax", feature = "search_random"),
+    all(feature = "search_alpha_beta", feature = "search_random"),
+))]
+compile_error!("only one of the features \"search_minimax\", \"search_alpha_beta\" and \"search_random\" can be enabled at a time!");
+
 #[cfg(all(feature = "revert_with_undo_move", feature = "board_representation_array"))]
 compile_error!("feature \"revert_with_undo_move\" and feature \"board_representation_array\" cannot be enabled at the same time!");
 
+// The value following a flag on the command line, like the name in --preset club
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
+
 fn main() {
+    // NOTE: Backends can be forced for benchmarking with --force-backend <portable|magic|pext|avx2>,
+    // which takes a comma separated list like portable,pext,
+    // and a strength preset can be chosen with --preset <beginner|club|master|max>.
+    // The saved settings profile is loaded first, from --profile <path> if given, and is skipped
+    // entirely with --no-profile, so the other flags override it.
+    let args: Vec<String> = env::args().collect();
+
     move_masks::init();
-    Uci::default().init();
+    CpuFeatures::init(arg_value(&args, "--force-backend"));
+
+    let mut uci = Uci::default();
+    if let Some(path) = arg_value(&args, "--profile") {
+        uci.profile_path = path.into();
+    }
+    if !args.iter().any(|arg| arg == "--no-profile") {
+        if let Err(error) = Profile::load(&uci.profile_path, &mut uci.options) {
+            eprintln!("{error}");
+        }
+    }
+    if let Some(preset) = arg_value(&args, "--preset") {
+        if let Err(error) = uci.options.set(EngineOptions::PRESET, preset) {
+            eprintln!("{error}");
+        }
+    }
+    uci.init();
 }
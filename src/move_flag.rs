@@ -1,22 +1,16 @@
 use std::mem::transmute;
 use core::fmt;
 
+use crate::{color::Color, piece::PieceType};
+
+// NOTE: Double pawn pushes don't need a flag, since they are the only pawn moves over two ranks
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum MoveFlag {
     None,
-    WEnPassant,
-    BEnPassant,
-    WDoublePawn,
-    BDoublePawn,
-    WKCastle,
-    WQCastle,
-    BKCastle,
-    BQCastle,
-    PromoN,
-    PromoB,
-    PromoR,
-    PromoQ,
+    Promotion,
+    EnPassant,
+    Castle,
 }
 
 impl From<u8> for MoveFlag {
@@ -30,18 +24,65 @@ impl fmt::Display for MoveFlag {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let name = match self {
             MoveFlag::None => "None",
-            MoveFlag::WDoublePawn => "Double Pawn Push",
-            MoveFlag::BDoublePawn => "Double Pawn Push",
-            MoveFlag::WEnPassant => "En-passant",
-            MoveFlag::BEnPassant => "En-passant",
-            MoveFlag::WKCastle => "Kingside Castle",
-            MoveFlag::WQCastle => "Queenside Castle",
-            MoveFlag::BKCastle => "Kingside Castle",
-            MoveFlag::BQCastle => "Queenside Castle",
-            MoveFlag::PromoN => "Knight Promotion",
-            MoveFlag::PromoB => "Bishop Promotion",
-            MoveFlag::PromoR => "Rook Promotion",
-            MoveFlag::PromoQ => "Queen Promotion",
+            MoveFlag::Promotion => "Promotion",
+            MoveFlag::EnPassant => "En-passant",
+            MoveFlag::Castle => "Castle",
+        };
+        f.pad(name)
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Promotion {
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+}
+
+impl Promotion {
+    pub const ALL: [Promotion; 4] = [Promotion::Knight, Promotion::Bishop, Promotion::Rook, Promotion::Queen];
+
+    #[inline(always)]
+    pub fn piece(self, color: Color) -> PieceType {
+        match (self, color) {
+            (Promotion::Knight, Color::White) => PieceType::WN,
+            (Promotion::Bishop, Color::White) => PieceType::WB,
+            (Promotion::Rook, Color::White) => PieceType::WR,
+            (Promotion::Queen, Color::White) => PieceType::WQ,
+            (Promotion::Knight, Color::Black) => PieceType::BN,
+            (Promotion::Bishop, Color::Black) => PieceType::BB,
+            (Promotion::Rook, Color::Black) => PieceType::BR,
+            (Promotion::Queen, Color::Black) => PieceType::BQ,
+        }
+    }
+
+    // The lowercase letter used for the promotion in UCI move strings
+    pub fn to_char(self) -> char {
+        match self {
+            Promotion::Knight => 'n',
+            Promotion::Bishop => 'b',
+            Promotion::Rook => 'r',
+            Promotion::Queen => 'q',
+        }
+    }
+}
+
+impl From<u8> for Promotion {
+    #[inline(always)]
+    fn from(number: u8) -> Self {
+        unsafe { transmute::<u8, Self>(number) }
+    }
+}
+
+impl fmt::Display for Promotion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Promotion::Knight => "Knight",
+            Promotion::Bishop => "Bishop",
+            Promotion::Rook => "Rook",
+            Promotion::Queen => "Queen",
         };
         f.pad(name)
     }
@@ -1,55 +1,71 @@
 use std::collections::HashSet;
 
-use crate::{bit_move::{BitMove, Move, ScoringMove}, bitboard::Bitboard, color::Color, move_flag::MoveFlag, move_list::MoveList, move_masks, piece::PieceType, position::Position, rank::Rank, square::Square};
+use crate::{bit_move::{BitMove, Move, ScoringMove}, bitboard::Bitboard, color::Color, move_flag::{MoveFlag, Promotion}, move_list::MoveList, move_masks, piece::PieceType, position::Position, rank::Rank, square::Square};
+
+// NOTE: Captures also covers promotions and en passant
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GenKind {
+    All,
+    Captures,
+    Quiets,
+}
 
 pub struct MoveGeneration { }
 
 impl MoveGeneration {
-    // Based on side, relevant pieces and occupancies can be selected
     #[inline]
     pub fn generate_moves<T: Move>(position: &Position, add: fn(&Position, &mut MoveList<T>, BitMove)) -> MoveList<T> {
+        Self::generate_moves_of_kind(position, GenKind::All, add)
+    }
+
+    // Based on side, relevant pieces and occupancies can be selected
+    #[inline]
+    pub fn generate_moves_of_kind<T: Move>(position: &Position, kind: GenKind, add: fn(&Position, &mut MoveList<T>, BitMove)) -> MoveList<T> {
         let mut move_list = MoveList::new();
         
         let side = position.side;
         let en_passant_sq = position.en_passant_sq;
         let inv_all_occupancies = !position.ao;
         
-        let ([pawn, knight, bishop, rook, queen, king], enemy_pieces) = match side {
-            Color::White => (PieceType::WHITE_PIECES, PieceType::BLACK_PIECES),
-            Color::Black => (PieceType::BLACK_PIECES, PieceType::WHITE_PIECES)
+        let [pawn, knight, bishop, rook, queen, king] = match side {
+            Color::White => PieceType::WHITE_PIECES,
+            Color::Black => PieceType::BLACK_PIECES
+        };
+
+        #[cfg(feature = "board_representation_bitboard")]
+        let enemy_pieces = match side {
+            Color::White => PieceType::BLACK_PIECES,
+            Color::Black => PieceType::WHITE_PIECES
         };
 
         let (inv_own_occupancies, enemy_occupancies) = match side {
             Color::White => (!position.wo, position.bo),
             Color::Black => (!position.bo, position.wo)
         };
+
+        let (captures_only, quiets_only) = (kind == GenKind::Captures, kind == GenKind::Quiets);
+        let target_mask = match kind {
+            GenKind::All => inv_own_occupancies,
+            GenKind::Captures => enemy_occupancies,
+            GenKind::Quiets => inv_all_occupancies,
+        };
         
         let (pawn_promotion_rank, pawn_starting_rank, en_passant_rank, pawn_double_push_rank) = match side {
             Color::White => (Rank::R7, Rank::R2, Rank::R5, Rank::R4),
             Color::Black => (Rank::R2, Rank::R7, Rank::R4, Rank::R5)
         };
         
-        let (double_pawn_flag, en_passant_flag, king_side_castling_flag, queen_side_castling_flag) = match side {
-            Color::White => (MoveFlag::WDoublePawn, MoveFlag::WEnPassant, MoveFlag::WKCastle, MoveFlag::WQCastle),
-            Color::Black => (MoveFlag::BDoublePawn, MoveFlag::BEnPassant, MoveFlag::BKCastle, MoveFlag::BQCastle)
-        };
-
-        let (king_side_castling_mask, queen_side_castling_mask) = match side {
-            Color::White => (Bitboard::W_KING_SIDE_MASK, Bitboard::W_QUEEN_SIDE_MASK),
-            Color::Black => (Bitboard::B_KING_SIDE_MASK, Bitboard::B_QUEEN_SIDE_MASK)
-        };
+        let castling_squares = position.castling_squares;
 
         let (king_side_castling_right, queen_side_castling_right) = match side {
             Color::White => (position.castling_rights.wk(), position.castling_rights.wq()),
             Color::Black => (position.castling_rights.bk(), position.castling_rights.bq())
         };
 
-        let (castling_square_c, castling_square_d, castling_square_e, castling_square_f, castling_square_g) = match side {
-            Color::White => (Square::C1, Square::D1, Square::E1, Square::F1, Square::G1),
-            Color::Black => (Square::C8, Square::D8, Square::E8, Square::F8, Square::G8)
-        };
+        // NOTE: In double check, only the king can move
+        let double_check = position.checkers_count() > 1;
 
-        {
+        if !double_check {
             /*------------------------------*\ 
                         Pawn moves
             \*------------------------------*/
@@ -59,7 +75,10 @@ impl MoveGeneration {
                 let source_rank = source.rank();
 
                 // Captures
-                let mut capture_mask = move_masks::get_pawn_capture_mask(side, source) & enemy_occupancies;
+                let mut capture_mask = match quiets_only {
+                    true => Bitboard::EMPTY,
+                    false => move_masks::get_pawn_capture_mask(side, source) & enemy_occupancies,
+                };
                 while capture_mask.is_not_empty() {
                     let target = capture_mask.pop_lsb();
 
@@ -69,28 +88,28 @@ impl MoveGeneration {
                     if source_rank == pawn_promotion_rank {
                         
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, target_piece, MoveFlag::PromoN));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, target_piece, Promotion::Knight));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoN));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Knight));
                         
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, target_piece, MoveFlag::PromoB));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, target_piece, Promotion::Bishop));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoB));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Bishop));
                         
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, target_piece, MoveFlag::PromoR));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, target_piece, Promotion::Rook));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoR));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Rook));
                         
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, target_piece, MoveFlag::PromoQ));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, target_piece, Promotion::Queen));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoQ));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Queen));
                     } else {
                         
                         #[cfg(feature = "board_representation_bitboard")]
@@ -102,7 +121,11 @@ impl MoveGeneration {
                 }
 
                 // Quiet moves
-                let mut quiet_mask = move_masks::get_pawn_quiet_mask(side, source) & inv_all_occupancies;
+                let is_promotion = source_rank == pawn_promotion_rank;
+                let mut quiet_mask = match captures_only && !is_promotion || quiets_only && is_promotion {
+                    true => Bitboard::EMPTY,
+                    false => move_masks::get_pawn_quiet_mask(side, source) & inv_all_occupancies,
+                };
                 while quiet_mask.is_not_empty() {
                     let target = quiet_mask.pop_lsb();
                     
@@ -111,35 +134,35 @@ impl MoveGeneration {
                         if (move_masks::get_pawn_quiet_mask(side, source) & position.ao).is_empty() {
                             
                             #[cfg(feature = "board_representation_bitboard")]
-                            add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, double_pawn_flag));
+                            add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, MoveFlag::None));
 
                             #[cfg(feature = "board_representation_array")]
-                                add(position, &mut move_list, BitMove::encode(source, target, double_pawn_flag));
+                                add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::None));
                         } 
                     } else if source_rank == pawn_promotion_rank {
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, MoveFlag::PromoN));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, PieceType::None, Promotion::Knight));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoN));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Knight));
                         
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, MoveFlag::PromoB));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, PieceType::None, Promotion::Bishop));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoB));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Bishop));
                         
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, MoveFlag::PromoR));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, PieceType::None, Promotion::Rook));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoR));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Rook));
                         
                         #[cfg(feature = "board_representation_bitboard")]
-                        add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, MoveFlag::PromoQ));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, pawn, PieceType::None, Promotion::Queen));
 
                         #[cfg(feature = "board_representation_array")]
-                        add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::PromoQ));
+                        add(position, &mut move_list, BitMove::encode_promotion(source, target, Promotion::Queen));
                     } else {
                         #[cfg(feature = "board_representation_bitboard")]
                         add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, MoveFlag::None));
@@ -150,23 +173,23 @@ impl MoveGeneration {
                 }
                 
                 // En-passant (could maybe be combined with captures?)
-                if en_passant_sq != Square::None && source_rank == en_passant_rank {
+                if !quiets_only && en_passant_sq != Square::None && source_rank == en_passant_rank {
                     let mut en_passant_mask = move_masks::get_pawn_capture_mask(side, source);
                     while en_passant_mask.is_not_empty() {
                         let target = en_passant_mask.pop_lsb();
                         if target == en_passant_sq {
                             #[cfg(feature = "board_representation_bitboard")]
-                            add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, en_passant_flag));
+                            add(position, &mut move_list, BitMove::encode(source, target, pawn, PieceType::None, MoveFlag::EnPassant));
 
                             #[cfg(feature = "board_representation_array")]
-                            add(position, &mut move_list, BitMove::encode(source, target, en_passant_flag));
+                            add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::EnPassant));
                         }
                     }
                 }
             }
         }
 
-        {
+        if !double_check {
             /*------------------------------*\ 
                     Knight moves
             \*------------------------------*/
@@ -174,7 +197,7 @@ impl MoveGeneration {
             while knight_bb.is_not_empty() {
                 let source = knight_bb.pop_lsb();
                 
-                let mut move_mask = move_masks::get_knight_mask(source) & inv_own_occupancies;
+                let mut move_mask = move_masks::get_knight_mask(source) & target_mask;
                 while move_mask.is_not_empty() {
                     let target = move_mask.pop_lsb();
 
@@ -196,7 +219,7 @@ impl MoveGeneration {
             \*------------------------------*/
             let mut king_bb = position.bbs[king];
             let source = king_bb.pop_lsb();
-            let mut move_mask = move_masks::get_king_mask(source) & inv_own_occupancies;
+            let mut move_mask = move_masks::get_king_mask(source) & target_mask;
             while move_mask.is_not_empty() {
                 let target = move_mask.pop_lsb();
 
@@ -212,45 +235,41 @@ impl MoveGeneration {
 
             // Kingside Castling
             #[allow(clippy::collapsible_if)]
-            if king_side_castling_right && (position.ao & king_side_castling_mask).is_empty() {
-                if !position.is_square_attacked(castling_square_e, position.side, &enemy_pieces) &&
-                !position.is_square_attacked(castling_square_f, position.side, &enemy_pieces) &&
-                !position.is_square_attacked(castling_square_g, position.side, &enemy_pieces)
-                {
+            if king_side_castling_right && !captures_only && !double_check && (position.ao & castling_squares.king_side_empty_mask(side)).is_empty() {
+                if !Self::is_any_square_attacked(position, castling_squares.king_side_safe_mask(side), ) {
+                    let target = castling_squares.king_side_king_target(side);
                     
                     #[cfg(feature = "board_representation_bitboard")]
-                    add(position, &mut move_list, BitMove::encode(source, castling_square_g, king, PieceType::None, king_side_castling_flag));
+                    add(position, &mut move_list, BitMove::encode(source, target, king, PieceType::None, MoveFlag::Castle));
 
                     #[cfg(feature = "board_representation_array")]
-                    add(position, &mut move_list, BitMove::encode(source, castling_square_g, king_side_castling_flag));
+                    add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::Castle));
                 }
             }
 
             // Queenside Castling
             #[allow(clippy::collapsible_if)]
-            if queen_side_castling_right && (position.ao & queen_side_castling_mask).is_empty() {
-                if !position.is_square_attacked(castling_square_e, position.side, &enemy_pieces) &&
-                !position.is_square_attacked(castling_square_d, position.side, &enemy_pieces) &&
-                !position.is_square_attacked(castling_square_c, position.side, &enemy_pieces)
-                {
+            if queen_side_castling_right && !captures_only && !double_check && (position.ao & castling_squares.queen_side_empty_mask(side)).is_empty() {
+                if !Self::is_any_square_attacked(position, castling_squares.queen_side_safe_mask(side), ) {
+                    let target = castling_squares.queen_side_king_target(side);
                     
                     #[cfg(feature = "board_representation_bitboard")]
-                    add(position, &mut move_list, BitMove::encode(source, castling_square_c, king, PieceType::None, queen_side_castling_flag));
+                    add(position, &mut move_list, BitMove::encode(source, target, king, PieceType::None, MoveFlag::Castle));
 
                     #[cfg(feature = "board_representation_array")]
-                    add(position, &mut move_list, BitMove::encode(source, castling_square_c, queen_side_castling_flag));
+                    add(position, &mut move_list, BitMove::encode(source, target, MoveFlag::Castle));
                 }
             }
         }
 
-        {
+        if !double_check {
             /*------------------------------*\ 
                     Bishop moves
             \*------------------------------*/
             let mut bishop_bb = position.bbs[bishop];
             while bishop_bb.is_not_empty() {
                 let source = bishop_bb.pop_lsb();
-                let mut move_mask = move_masks::get_bishop_mask(source, position.ao) & inv_own_occupancies;
+                let mut move_mask = move_masks::get_bishop_mask(source, position.ao) & target_mask;
                 while move_mask.is_not_empty() {
                     let target = move_mask.pop_lsb();
 
@@ -266,14 +285,14 @@ impl MoveGeneration {
             }
         }
 
-        {
+        if !double_check {
             /*------------------------------*\ 
                         Rook moves
             \*------------------------------*/
             let mut rook_bb = position.bbs[rook];
             while rook_bb.is_not_empty() {
                 let source = rook_bb.pop_lsb();
-                let mut move_mask = move_masks::get_rook_mask(source, position.ao) & inv_own_occupancies;
+                let mut move_mask = move_masks::get_rook_mask(source, position.ao) & target_mask;
                 while move_mask.is_not_empty() {
                     let target = move_mask.pop_lsb();
 
@@ -289,14 +308,14 @@ impl MoveGeneration {
             }
         }
 
-        {
+        if !double_check {
             /*------------------------------*\ 
                     Queen moves
             \*------------------------------*/
             let mut queen_bb = position.bbs[queen];
             while queen_bb.is_not_empty() {
                 let source = queen_bb.pop_lsb();
-                let mut move_mask = move_masks::get_queen_mask(source, position.ao) & inv_own_occupancies;
+                let mut move_mask = move_masks::get_queen_mask(source, position.ao) & target_mask;
                 while move_mask.is_not_empty() {
                     let target = move_mask.pop_lsb();
 
@@ -321,6 +340,16 @@ impl MoveGeneration {
         move_list
     }
 
+    #[inline(always)]
+    fn is_any_square_attacked(position: &Position, mut squares: Bitboard) -> bool {
+        while squares.is_not_empty() {
+            if position.is_square_attacked(squares.pop_lsb(), position.side) {
+                return true;
+            }
+        }
+        false
+    }
+
     #[inline]
     pub fn generate_pseudo_legal_moves(position: &Position) -> MoveList<BitMove> {
         Self::generate_moves::<BitMove>(position, |_position, move_list, bit_move| {
@@ -340,6 +369,41 @@ impl MoveGeneration {
         })
     }
 
+    // Legal quiet moves that give check, for the quiescence search and mate searches
+    #[inline]
+    pub fn generate_quiet_checks(position: &Position) -> MoveList<BitMove> {
+        Self::generate_moves::<BitMove>(position, |position, move_list, bit_move| {
+            if Self::is_quiet_check(position, bit_move) {
+                move_list.add(bit_move);
+            }
+        })
+    }
+
+    fn is_quiet_check(position: &Position, bit_move: BitMove) -> bool {
+        let (source, target, flag) = (bit_move.source(), bit_move.target(), bit_move.flag());
+        let is_quiet = !position.ao.is_set_sq(target) && matches!(flag, MoveFlag::None | MoveFlag::Castle);
+        if !is_quiet {
+            return false;
+        }
+
+        let enemy_king_square = match position.side {
+            Color::White => position.bbs[PieceType::BK],
+            Color::Black => position.bbs[PieceType::WK],
+        }.to_sq();
+        let is_candidate = flag == MoveFlag::Castle
+            || move_masks::get_check_candidate_mask(position.get_piece(source), enemy_king_square).is_set_sq(target)
+            || move_masks::get_check_candidate_mask(PieceType::WQ, enemy_king_square).is_set_sq(source);
+        if !is_candidate {
+            return false;
+        }
+
+        if !position.gives_check(bit_move) {
+            return false;
+        }
+        let mut position_copy = position.clone();
+        position_copy.make_move(bit_move)
+    }
+
     #[inline]
     pub fn generate_pseudo_legal_scoring_moves(position: &Position) -> MoveList<ScoringMove> {
         Self::generate_moves::<ScoringMove>(position, |_position, move_list, bit_move| {
@@ -347,6 +411,13 @@ impl MoveGeneration {
         })
     }
 
+    #[inline]
+    pub fn generate_pseudo_legal_scoring_captures(position: &Position) -> MoveList<ScoringMove> {
+        Self::generate_moves_of_kind::<ScoringMove>(position, GenKind::Captures, |_position, move_list, bit_move| {
+            move_list.add(ScoringMove::from(bit_move));
+        })
+    }
+
     #[inline]
     pub fn generate_legal_scoring_moves(position: &Position) -> MoveList<ScoringMove> {
         Self::generate_moves::<ScoringMove>(position, |position, move_list, bit_move| {
@@ -362,6 +433,8 @@ impl MoveGeneration {
 mod tests {
     use std::collections::HashSet;
 
+    use crate::move_ordering::MoveOrdering;
+
     use super::*;
 
     #[test]
@@ -370,4 +443,72 @@ mod tests {
         let mut seen = HashSet::new();
         assert!(move_list.iter().all(|&m| seen.insert(m)));
     }
+
+    #[test]
+    fn double_check_only_generates_king_moves() {
+        move_masks::init();
+        let position = Position::from_fen("4k3/8/5N2/8/8/8/3Q4/4R1K1 b - - 0 1").unwrap();
+        let king_square = position.bbs[PieceType::BK].to_sq();
+
+        let move_list = MoveGeneration::generate_pseudo_legal_moves(&position);
+        assert!(move_list.iter().all(|m| m.source() == king_square));
+        assert_eq!(MoveGeneration::generate_legal_moves(&position).len(), 2);
+    }
+
+    #[test]
+    fn generate_quiet_checks_matches_filtered_legal_moves() {
+        move_masks::init();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "4k3/8/8/8/1b6/8/3N4/4K2Q b - - 0 1",
+            "4k3/8/4N3/8/8/8/8/R3K3 w Q - 0 1",
+            "4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1",
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            let mut expected: Vec<String> = MoveGeneration::generate_legal_moves(&position)
+                .iter()
+                .filter(|&&m| !position.ao.is_set_sq(m.target()) && matches!(m.flag(), MoveFlag::None | MoveFlag::Castle))
+                .filter(|&&m| {
+                    let mut position_copy = position.clone();
+                    position_copy.make_move(m);
+                    position_copy.in_check()
+                })
+                .map(|m| m.to_uci_string())
+                .collect();
+            let mut quiet_checks: Vec<String> = MoveGeneration::generate_quiet_checks(&position).iter().map(|m| m.to_uci_string()).collect();
+            expected.sort();
+            quiet_checks.sort();
+            assert_eq!(quiet_checks, expected, "{fen}");
+        }
+        let castling_check = Position::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(MoveGeneration::generate_quiet_checks(&castling_check).iter().any(|m| m.to_uci_string() == "e1g1"));
+    }
+
+    #[test]
+    fn captures_and_quiets_split_the_moves() {
+        move_masks::init();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "4k3/1P6/8/8/8/8/6p1/4K2R b K - 0 1",
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            let expected: Vec<BitMove> = MoveGeneration::generate_pseudo_legal_moves(&position)
+                .iter()
+                .copied()
+                .filter(|&m| !MoveOrdering::is_quiet(&position, m))
+                .collect();
+            let captures: Vec<BitMove> = MoveGeneration::generate_pseudo_legal_scoring_captures(&position).iter().map(|m| m.bit_move).collect();
+            assert_eq!(captures, expected, "{fen}");
+
+            let all_moves = MoveGeneration::generate_pseudo_legal_moves(&position);
+            let quiets = MoveGeneration::generate_moves_of_kind::<BitMove>(&position, GenKind::Quiets, |_position, move_list, bit_move| {
+                move_list.add(bit_move);
+            });
+            assert_eq!(quiets.len() + captures.len(), all_moves.len(), "{fen}");
+            assert!(quiets.iter().all(|&m| MoveOrdering::is_quiet(&position, m)), "{fen}");
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::bit_move::{BitMove, Move};
+use crate::bit_move::{BitMove, Move, ScoringMove};
 use core::fmt;
 use std::ops::{Index, IndexMut};
 
@@ -40,6 +40,23 @@ impl<T: Move> MoveList<T> {
     }
 }
 
+impl MoveList<ScoringMove> {
+    // Moves the best scored of the remaining moves to the index and returns it. Searching the moves
+    // in this order is a selection sort, which pays off since a cutoff often leaves most of the
+    // moves unsorted.
+    #[inline(always)]
+    pub fn pick_best(&mut self, index: usize) -> ScoringMove {
+        let mut best_index = index;
+        for other_index in index + 1..self.size {
+            if self.array[other_index].score > self.array[best_index].score {
+                best_index = other_index;
+            }
+        }
+        self.array.swap(index, best_index);
+        self.array[index]
+    }
+}
+
 pub struct MoveListIntoIter<T> {
     move_list: MoveList<T>,
     idx: usize,
@@ -120,9 +137,6 @@ impl fmt::Display for MoveList<BitMove> {
 
 #[cfg(test)]
 mod tests {
-
-    use crate::bit_move::ScoringMove;
-
     use super::*;
 
     #[test]
@@ -138,4 +152,15 @@ mod tests {
         assert_eq!(move_list.iter().max().unwrap().score, 2);
         assert_eq!(move_list.iter().min().unwrap().score, -2);
     }
+
+    #[test]
+    fn pick_best_sorts_remaining_moves() {
+        let mut move_list = MoveList::<ScoringMove>::new();
+        for score in [3, -1, 7, 0, 7, 2] {
+            move_list.add(ScoringMove::blank(score));
+        }
+
+        let picked: Vec<i16> = (0..move_list.len()).map(|index| move_list.pick_best(index).score).collect();
+        assert_eq!(picked, [7, 7, 3, 2, 0, -1]);
+    }
 }
@@ -1,13 +1,35 @@
-use crate::{bitboard::Bitboard, color::Color, rank::Rank, square::Square, file::File};
-
-pub static mut PAWN_QUIET_MASKS: [[Bitboard; 64]; 2] = [[Bitboard::EMPTY; 64]; 2];
-pub static mut PAWN_CAPTURE_MASKS: [[Bitboard; 64]; 2] = [[Bitboard::EMPTY; 64]; 2];
-pub static mut KNIGHT_MASKS: [Bitboard; 64] = [Bitboard::EMPTY; 64];
-pub static mut KING_MASKS: [Bitboard; 64] = [Bitboard::EMPTY; 64];
-pub static mut BISHOP_MASKS: [Bitboard; 64] = [Bitboard::EMPTY; 64];
-pub static mut ROOK_MASKS: [Bitboard; 64] = [Bitboard::EMPTY; 64];
-pub static mut ROOK_MOVE_CONFIGURATIONS: [[Bitboard; 4096]; 64] = [[Bitboard::EMPTY; 4096]; 64];
-pub static mut BISHOP_MOVE_CONFIGURATIONS: [[Bitboard; 512]; 64] = [[Bitboard::EMPTY; 512]; 64];
+use std::sync::{atomic::{AtomicBool, Ordering}, OnceLock};
+
+use crate::{bitboard::Bitboard, color::Color, piece::PieceType, square::Square};
+
+static PAWN_QUIET_MASKS: [[Bitboard; 64]; 2] = [generate_pawn_quiet_masks(-1, 6), generate_pawn_quiet_masks(1, 1)];
+static PAWN_CAPTURE_MASKS: [[Bitboard; 64]; 2] = [generate_leaper_masks(&[(-1, -1), (-1, 1)]), generate_leaper_masks(&[(1, -1), (1, 1)])];
+static PAWN_ATTACK_SPAN_MASKS: [[Bitboard; 64]; 2] = [generate_pawn_attack_span_masks(-1), generate_pawn_attack_span_masks(1)];
+static KNIGHT_MASKS: [Bitboard; 64] = generate_leaper_masks(&[(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)]);
+static KING_MASKS: [Bitboard; 64] = generate_leaper_masks(&[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]);
+static BISHOP_MASKS: [Bitboard; 64] = generate_relevant_occupancy_masks(&BISHOP_DIRECTIONS);
+static ROOK_MASKS: [Bitboard; 64] = generate_relevant_occupancy_masks(&ROOK_DIRECTIONS);
+static CHECK_CANDIDATE_MASKS: [[Bitboard; 64]; 12] = generate_check_candidate_masks();
+static BETWEEN_MASKS: [[Bitboard; 64]; 64] = generate_between_masks();
+static LINE_MASKS: [[Bitboard; 64]; 64] = generate_line_masks();
+
+// NOTE: The slider tables take too long to compute at compile time, so they are computed on first use
+static SLIDER_MOVES: OnceLock<SliderMoves> = OnceLock::new();
+
+// NOTE: Only computed when pext is chosen, and the flag is only set once bmi2 is detected
+static PEXT_SLIDER_MOVES: OnceLock<SliderMoves> = OnceLock::new();
+static PEXT_SLIDERS: AtomicBool = AtomicBool::new(false);
+
+// Rows count down from the eighth rank like the squares do
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const QUEEN_DIRECTIONS: [(i32, i32); 8] = [(-1, -1), (-1, 1), (1, -1), (1, 1), (-1, 0), (1, 0), (0, -1), (0, 1)];
+
+struct SliderMoves {
+    // NOTE: The tables are too large for the stack
+    bishop: Box<[[Bitboard; 512]; 64]>,
+    rook: Box<[[Bitboard; 4096]; 64]>,
+}
 
 pub static BISHOP_RELEVANT_BITS: [u8; 64] = [
     6, 5, 5, 5, 5, 5, 5, 6,
@@ -166,228 +188,236 @@ pub static ROOK_MAGIC_BITBOARDS: [Bitboard; 64] = [
 ];
 
 pub fn init() {
-    unsafe {
-        init_masks();
-        init_slider_configurations();
-    }
+    slider_moves();
 }
 
-unsafe fn init_masks() {
-    for square in Square::ALL_SQUARES {
-        PAWN_QUIET_MASKS[Color::White][square] = generate_pawn_quiet_mask(Color::White, square);
-        PAWN_CAPTURE_MASKS[Color::White][square] = generate_pawn_capture_mask(Color::White, square);
-        PAWN_QUIET_MASKS[Color::Black][square] = generate_pawn_quiet_mask(Color::Black, square);
-        PAWN_CAPTURE_MASKS[Color::Black][square] = generate_pawn_capture_mask(Color::Black, square);
-        KNIGHT_MASKS[square] = generate_knight_mask(square);
-        KING_MASKS[square] = generate_king_mask(square);
-        BISHOP_MASKS[square] = generate_bishop_mask(square);
-        ROOK_MASKS[square] = generate_rook_mask(square);
-
-        debug_assert_eq!(BISHOP_MASKS[square].count_bits(), BISHOP_RELEVANT_BITS[square]);
-        debug_assert_eq!(ROOK_MASKS[square].count_bits(), ROOK_RELEVANT_BITS[square]);
+pub fn set_pext_sliders(enabled: bool) {
+    if enabled {
+        PEXT_SLIDER_MOVES.get_or_init(|| SliderMoves::new(SliderIndexing::Pext));
     }
+    PEXT_SLIDERS.store(enabled, Ordering::Relaxed);
 }
 
-unsafe fn init_slider_configurations() {
-    for square in Square::ALL_SQUARES {
-        let bishop_mask = BISHOP_MASKS[square];
-        let rook_mask = ROOK_MASKS[square];
-
-        let num_bishop_relevant_bits = BISHOP_RELEVANT_BITS[square];
-        let num_rook_relevant_bits = ROOK_RELEVANT_BITS[square];
-
-        let max_bishop_occupancy_index = 1 << num_bishop_relevant_bits;
-        let max_rook_occupancy_index = 1 << num_rook_relevant_bits;
-
-        for occupancy_index in 0..max_bishop_occupancy_index {
-            let occupancy = generate_occupancy_permutation(occupancy_index, num_bishop_relevant_bits, bishop_mask);
-            let magic_index = occupancy.0.wrapping_mul(BISHOP_MAGIC_BITBOARDS[square].0) >> (64 - num_bishop_relevant_bits);
-            BISHOP_MOVE_CONFIGURATIONS[square][magic_index as usize] = generate_bishop_moves_on_the_fly(square, occupancy);
-        }
-
-        for occupancy_index in 0..max_rook_occupancy_index {
-            let occupancy = generate_occupancy_permutation(occupancy_index, num_rook_relevant_bits, rook_mask);
-            let magic_index = occupancy.0.wrapping_mul(ROOK_MAGIC_BITBOARDS[square].0) >> (64 - num_rook_relevant_bits);
-            ROOK_MOVE_CONFIGURATIONS[square][magic_index as usize] = generate_rook_moves_on_the_fly(square, occupancy);
-        }
+// NOTE: Kept small enough to inline, since the lookups are in the innermost loops
+#[inline(always)]
+fn slider_moves() -> &'static SliderMoves {
+    match SLIDER_MOVES.get() {
+        Some(slider_moves) => slider_moves,
+        None => compute_slider_moves(),
     }
 }
 
-fn generate_pawn_quiet_mask(color: Color, square: Square) -> Bitboard {
-    let mut bb_mask = Bitboard::EMPTY;
-    let square_bb = square.to_bb();
-    let square_rank = square.rank();
-
-    match color {
-        Color::White => {
-            bb_mask |= square_bb.shift_upwards(8);
-
-            if square_rank == Rank::R2 {
-                bb_mask |= square_bb.shift_upwards(16);
-            }
-        }
-        Color::Black => {
-            bb_mask |= square_bb.shift_downwards(8);
-
-            if square_rank == Rank::R7 {
-                bb_mask |= square_bb.shift_downwards(16);
-            }
-        }
-    };
-
-    bb_mask
+#[cold]
+#[inline(never)]
+fn compute_slider_moves() -> &'static SliderMoves {
+    SLIDER_MOVES.get_or_init(|| SliderMoves::new(SliderIndexing::Magic))
 }
 
-fn generate_pawn_capture_mask(color: Color, square: Square) -> Bitboard {
-    let mut bb_mask = Bitboard::EMPTY;
-    let square_bb = square.to_bb();
-    let square_file = square.file();
+#[derive(Clone, Copy, PartialEq)]
+enum SliderIndexing {
+    Magic,
+    Pext,
+}
 
-    match color {
-        Color::White => {
-            if square_file != File::FA {
-                bb_mask |= square_bb.shift_upwards(9);
+impl SliderMoves {
+    fn new(indexing: SliderIndexing) -> SliderMoves {
+        let mut slider_moves = SliderMoves {
+            bishop: Self::empty_table(),
+            rook: Self::empty_table(),
+        };
+
+        for square in Square::ALL_SQUARES {
+            let num_bishop_relevant_bits = BISHOP_RELEVANT_BITS[square];
+            let num_rook_relevant_bits = ROOK_RELEVANT_BITS[square];
+
+            for occupancy_index in 0..1 << num_bishop_relevant_bits {
+                let occupancy = generate_occupancy_permutation(occupancy_index, num_bishop_relevant_bits, BISHOP_MASKS[square]);
+                let index = match indexing {
+                    SliderIndexing::Magic => occupancy.0.wrapping_mul(BISHOP_MAGIC_BITBOARDS[square].0) >> (64 - num_bishop_relevant_bits),
+                    // NOTE: Pext gathers the mask bits in order, which undoes the permutation
+                    SliderIndexing::Pext => occupancy_index as u64,
+                };
+                slider_moves.bishop[square][index as usize] = generate_bishop_moves_on_the_fly(square, occupancy);
             }
 
-            if square_file != File::FH {
-                bb_mask |= square_bb.shift_upwards(7);
+            for occupancy_index in 0..1 << num_rook_relevant_bits {
+                let occupancy = generate_occupancy_permutation(occupancy_index, num_rook_relevant_bits, ROOK_MASKS[square]);
+                let index = match indexing {
+                    SliderIndexing::Magic => occupancy.0.wrapping_mul(ROOK_MAGIC_BITBOARDS[square].0) >> (64 - num_rook_relevant_bits),
+                    SliderIndexing::Pext => occupancy_index as u64,
+                };
+                slider_moves.rook[square][index as usize] = generate_rook_moves_on_the_fly(square, occupancy);
             }
         }
-        Color::Black => {
-            if square_file != File::FA {
-                bb_mask |= square_bb.shift_downwards(7);
-            }
 
-            if square_file != File::FH {
-                bb_mask |= square_bb.shift_downwards(9);
-            }
-        }
-    };
+        slider_moves
+    }
 
-    bb_mask
+    fn empty_table<const N: usize>() -> Box<[[Bitboard; N]; 64]> {
+        vec![[Bitboard::EMPTY; N]; 64]
+            .into_boxed_slice()
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("the table has a row per square"))
+    }
 }
 
-fn generate_knight_mask(square: Square) -> Bitboard {
-    let mut bb_mask = Bitboard::EMPTY;
-    let square_bb = square.to_bb();
-    let square_file = square.file();
-
-    if square_file != File::FA {
-        bb_mask |= square_bb.shift_upwards(17);
-        bb_mask |= square_bb.shift_downwards(15);
-
-        if square_file != File::FB {
-            bb_mask |= square_bb.shift_upwards(10);
-            bb_mask |= square_bb.shift_downwards(6);
-        }
+const fn step(square: usize, (rows, files): (i32, i32)) -> u64 {
+    let row = (square / 8) as i32 + rows;
+    let file = (square % 8) as i32 + files;
+    if row < 0 || row > 7 || file < 0 || file > 7 {
+        0
+    } else {
+        1 << (row * 8 + file)
     }
+}
 
-    if square_file != File::FH {
-        bb_mask |= square_bb.shift_upwards(15);
-        bb_mask |= square_bb.shift_downwards(17);
-
-        if square_file != File::FG {
-            bb_mask |= square_bb.shift_upwards(6);
-            bb_mask |= square_bb.shift_downwards(10);
+// NOTE: Const functions can't use for loops yet
+const fn generate_leaper_masks(steps: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut masks = [Bitboard::EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut index = 0;
+        while index < steps.len() {
+            masks[square].0 |= step(square, steps[index]);
+            index += 1;
         }
+        square += 1;
     }
-
-    bb_mask
+    masks
 }
 
-fn generate_king_mask(square: Square) -> Bitboard {
-    let mut bb_mask = Bitboard::EMPTY;
-    let square_bb = square.to_bb();
-    let square_file = square.file();
-
-    bb_mask |= square_bb.shift_upwards(8);
-    bb_mask |= square_bb.shift_downwards(8);
-
-    if square_file != File::FA {
-        bb_mask |= square_bb.shift_upwards(1);
-        bb_mask |= square_bb.shift_upwards(9);
-        bb_mask |= square_bb.shift_downwards(7);
-    }
-
-    if square_file != File::FH {
-        bb_mask |= square_bb.shift_upwards(7);
-        bb_mask |= square_bb.shift_downwards(1);
-        bb_mask |= square_bb.shift_downwards(9);
+const fn generate_pawn_quiet_masks(forward: i32, starting_row: usize) -> [Bitboard; 64] {
+    let mut masks = generate_leaper_masks(&[(forward, 0)]);
+    let mut square = 0;
+    while square < 64 {
+        if square / 8 == starting_row {
+            masks[square].0 |= step(square, (2 * forward, 0));
+        }
+        square += 1;
     }
-
-    bb_mask
+    masks
 }
 
-fn generate_bishop_mask(square: Square) -> Bitboard {
-    use std::cmp::min;
-
-    let mut bb_mask = Bitboard::EMPTY;
-    let square_bb = square.to_bb();
-    let rank_u8 = square.rank_as_u8();
-    let file_u8 = square.file_as_u8();
-
-    // Bottom right
-    for i in 1..=min(6_u8.saturating_sub(rank_u8), 6_u8.saturating_sub(file_u8)) as usize {
-        let ray = square_bb.shift_downwards(i * 9);
-        bb_mask |= ray;
-    }
-    
-    // Top right
-    for i in 1..=min(rank_u8.saturating_sub(1), 6_u8.saturating_sub(file_u8)) as usize {
-        let ray = square_bb.shift_upwards(i * 7);
-        bb_mask |= ray;
+// The squares the color's pawns could attack the square from
+const fn generate_pawn_attack_span_masks(forward: i32) -> [Bitboard; 64] {
+    let mut masks = [Bitboard::EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut rows = forward;
+        while (square / 8) as i32 + rows >= 0 && (square / 8) as i32 + rows <= 7 {
+            masks[square].0 |= step(square, (rows, -1)) | step(square, (rows, 1));
+            rows += forward;
+        }
+        square += 1;
     }
+    masks
+}
 
-    // Bottom left
-    for i in 1..=min(6_u8.saturating_sub(rank_u8), file_u8.saturating_sub(1)) as usize {
-        let ray = square_bb.shift_downwards(i * 7);
-        bb_mask |= ray;
+const fn generate_rays(square: usize, directions: &[(i32, i32); 4], only_relevant: bool) -> u64 {
+    let mut rays = 0;
+    let mut direction = 0;
+    while direction < 4 {
+        let (rows, files) = directions[direction];
+        let mut distance = 1;
+        while step(square, (rows * distance, files * distance)) != 0
+            && !(only_relevant && step(square, (rows * (distance + 1), files * (distance + 1))) == 0)
+        {
+            rays |= step(square, (rows * distance, files * distance));
+            distance += 1;
+        }
+        direction += 1;
     }
+    rays
+}
 
-    // Top left
-    for i in 1..=min(rank_u8.saturating_sub(1), file_u8.saturating_sub(1)) as usize {
-        let ray = square_bb.shift_upwards(i * 9);
-        bb_mask |= ray;
+const fn generate_relevant_occupancy_masks(directions: &[(i32, i32); 4]) -> [Bitboard; 64] {
+    let mut masks = [Bitboard::EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        masks[square].0 = generate_rays(square, directions, true);
+        square += 1;
     }
-
-    bb_mask
+    masks
 }
 
-
-fn generate_rook_mask(square: Square) -> Bitboard {
-    let mut bb_mask = Bitboard::EMPTY;
-    let square_bb = square.to_bb();
-    let rank_u8 = square.rank_as_u8();
-    let file_u8 = square.file_as_u8();
-
-    // Down
-    for i in 1..=(6_u8.saturating_sub(rank_u8)) as usize {
-        let ray = square_bb.shift_downwards(i * 8);
-        bb_mask |= ray;
-    }
-    
-    // Up
-    for i in 1..=(rank_u8.saturating_sub(1)) as usize {
-        let ray = square_bb.shift_upwards(i * 8);
-        bb_mask |= ray;
+const fn generate_check_candidate_masks() -> [[Bitboard; 64]; 12] {
+    let mut masks = [[Bitboard::EMPTY; 64]; 12];
+    let mut king_square = 0;
+    while king_square < 64 {
+        let bishop_rays = generate_rays(king_square, &BISHOP_DIRECTIONS, false);
+        let rook_rays = generate_rays(king_square, &ROOK_DIRECTIONS, false);
+        let candidates = [
+            PAWN_CAPTURE_MASKS[Color::Black as usize][king_square].0,
+            KNIGHT_MASKS[king_square].0,
+            bishop_rays,
+            rook_rays,
+            bishop_rays | rook_rays,
+            0,
+        ];
+        let mut piece = 0;
+        while piece < 6 {
+            masks[PieceType::WP as usize + piece][king_square].0 = candidates[piece];
+            masks[PieceType::BP as usize + piece][king_square].0 = candidates[piece];
+            piece += 1;
+        }
+        masks[PieceType::BP as usize][king_square].0 = PAWN_CAPTURE_MASKS[Color::White as usize][king_square].0;
+        king_square += 1;
     }
+    masks
+}
 
-    // Right
-    for i in 1..=(6_u8.saturating_sub(file_u8)) as usize {
-        let ray = square_bb.shift_downwards(i);
-        bb_mask |= ray;
+const fn generate_between_masks() -> [[Bitboard; 64]; 64] {
+    let mut masks = [[Bitboard::EMPTY; 64]; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut direction = 0;
+        while direction < 8 {
+            let (rows, files) = QUEEN_DIRECTIONS[direction];
+            let mut between = 0;
+            let mut distance = 1;
+            while step(square, (rows * distance, files * distance)) != 0 {
+                let other = step(square, (rows * distance, files * distance));
+                masks[square][other.trailing_zeros() as usize].0 = between;
+                between |= other;
+                distance += 1;
+            }
+            direction += 1;
+        }
+        square += 1;
     }
+    masks
+}
 
-    // Left
-    for i in 1..=(file_u8.saturating_sub(1)) as usize {
-        let ray = square_bb.shift_upwards(i);
-        bb_mask |= ray;
+const fn generate_line_masks() -> [[Bitboard; 64]; 64] {
+    let mut masks = [[Bitboard::EMPTY; 64]; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut direction = 0;
+        while direction < 8 {
+            let (rows, files) = QUEEN_DIRECTIONS[direction];
+            let mut line = 1 << square;
+            let mut distance = 1;
+            while step(square, (rows * distance, files * distance)) != 0 {
+                line |= step(square, (rows * distance, files * distance));
+                distance += 1;
+            }
+            distance = 1;
+            while step(square, (-rows * distance, -files * distance)) != 0 {
+                line |= step(square, (-rows * distance, -files * distance));
+                distance += 1;
+            }
+            distance = 1;
+            while step(square, (rows * distance, files * distance)) != 0 {
+                masks[square][step(square, (rows * distance, files * distance)).trailing_zeros() as usize].0 = line;
+                distance += 1;
+            }
+            direction += 1;
+        }
+        square += 1;
     }
-
-    bb_mask
+    masks
 }
 
-
 pub fn generate_bishop_moves_on_the_fly(square: Square, occupancy: Bitboard) -> Bitboard {
     use std::cmp::min;
 
@@ -478,24 +508,50 @@ pub fn generate_occupancy_permutation(occupancy_index: u32, num_bits: u8, mut ma
     occupancy
 }
 
+
 #[inline(always)]
 pub fn get_pawn_quiet_mask(color: Color, square: Square) -> Bitboard {
-    unsafe { PAWN_QUIET_MASKS[color][square] }
+    PAWN_QUIET_MASKS[color][square]
 }
 
 #[inline(always)]
 pub fn get_pawn_capture_mask(color: Color, square: Square) -> Bitboard {
-    unsafe { PAWN_CAPTURE_MASKS[color][square] }
+    PAWN_CAPTURE_MASKS[color][square]
+}
+
+#[inline(always)]
+pub fn get_pawn_attack_span_mask(color: Color, square: Square) -> Bitboard {
+    PAWN_ATTACK_SPAN_MASKS[color][square]
+}
+
+#[inline(always)]
+pub fn get_check_candidate_mask(piece: PieceType, king_square: Square) -> Bitboard {
+    CHECK_CANDIDATE_MASKS[piece as usize][king_square]
+}
+
+#[inline(always)]
+pub fn get_between_mask(square: Square, other: Square) -> Bitboard {
+    BETWEEN_MASKS[square][other]
+}
+
+#[inline(always)]
+pub fn get_line_mask(square: Square, other: Square) -> Bitboard {
+    LINE_MASKS[square][other]
+}
+
+#[inline(always)]
+pub fn get_relevant_occupancy_mask(square: Square, is_bishop: bool) -> Bitboard {
+    if is_bishop { BISHOP_MASKS[square] } else { ROOK_MASKS[square] }
 }
 
 #[inline(always)]
 pub fn get_knight_mask(square: Square) -> Bitboard {
-    unsafe { KNIGHT_MASKS[square] }
+    KNIGHT_MASKS[square]
 }
 
 #[inline(always)]
 pub fn get_king_mask(square: Square) -> Bitboard {
-    unsafe { KING_MASKS[square] }
+    KING_MASKS[square]
 }
 
 #[inline(always)]
@@ -507,13 +563,17 @@ pub fn get_bishop_mask(square: Square, occupancy: Bitboard) -> Bitboard {
 #[inline(always)]
 #[cfg(feature = "sliders_magic_bitboards")]
 pub fn get_bishop_mask(square: Square, occupancy: Bitboard) -> Bitboard {
-    unsafe {
-        let mut index = occupancy.0 & BISHOP_MASKS[square].0;
-        index = 
-            index.wrapping_mul(BISHOP_MAGIC_BITBOARDS[square].0) >> 
-            (64 - BISHOP_RELEVANT_BITS[square]);
-        BISHOP_MOVE_CONFIGURATIONS[square][index as usize]
+    #[cfg(target_arch = "x86_64")]
+    if PEXT_SLIDERS.load(Ordering::Relaxed) {
+        // SAFETY: PEXT_SLIDERS is only enabled after the bmi2 instruction set has been detected
+        return unsafe { get_bishop_mask_pext(square, occupancy) };
     }
+
+    let mut index = occupancy.0 & BISHOP_MASKS[square].0;
+    index = 
+        index.wrapping_mul(BISHOP_MAGIC_BITBOARDS[square].0) >> 
+        (64 - BISHOP_RELEVANT_BITS[square]);
+    slider_moves().bishop[square][index as usize]
 }
 
 #[inline(always)]
@@ -525,13 +585,36 @@ pub fn get_rook_mask(square: Square, occupancy: Bitboard) -> Bitboard {
 #[inline(always)]
 #[cfg(feature = "sliders_magic_bitboards")]
 pub fn get_rook_mask(square: Square, occupancy: Bitboard) -> Bitboard {
-    unsafe {
-        let mut index = occupancy.0 & ROOK_MASKS[square].0;
-        index = 
-            index.wrapping_mul(ROOK_MAGIC_BITBOARDS[square].0) >> 
-            (64 - ROOK_RELEVANT_BITS[square]);
-        ROOK_MOVE_CONFIGURATIONS[square][index as usize]
+    #[cfg(target_arch = "x86_64")]
+    if PEXT_SLIDERS.load(Ordering::Relaxed) {
+        // SAFETY: PEXT_SLIDERS is only enabled after the bmi2 instruction set has been detected
+        return unsafe { get_rook_mask_pext(square, occupancy) };
     }
+
+    let mut index = occupancy.0 & ROOK_MASKS[square].0;
+    index = 
+        index.wrapping_mul(ROOK_MAGIC_BITBOARDS[square].0) >> 
+        (64 - ROOK_RELEVANT_BITS[square]);
+    slider_moves().rook[square][index as usize]
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "bmi2")]
+unsafe fn get_bishop_mask_pext(square: Square, occupancy: Bitboard) -> Bitboard {
+    let index = std::arch::x86_64::_pext_u64(occupancy.0, BISHOP_MASKS[square].0);
+    pext_slider_moves().bishop[square][index as usize]
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "bmi2")]
+unsafe fn get_rook_mask_pext(square: Square, occupancy: Bitboard) -> Bitboard {
+    let index = std::arch::x86_64::_pext_u64(occupancy.0, ROOK_MASKS[square].0);
+    pext_slider_moves().rook[square][index as usize]
+}
+
+#[inline(always)]
+fn pext_slider_moves() -> &'static SliderMoves {
+    PEXT_SLIDER_MOVES.get_or_init(|| SliderMoves::new(SliderIndexing::Pext))
 }
 
 #[inline(always)]
@@ -545,3 +628,74 @@ pub fn get_queen_mask(square: Square, occupancy: Bitboard) -> Bitboard {
 pub fn get_queen_mask(square: Square, occupancy: Bitboard) -> Bitboard {
     get_bishop_mask(square, occupancy) | get_rook_mask(square, occupancy)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::bb;
+
+    use super::*;
+
+    #[test]
+    fn relevant_occupancy_masks_match_the_relevant_bits() {
+        for square in Square::ALL_SQUARES {
+            assert_eq!(BISHOP_MASKS[square].count_bits(), BISHOP_RELEVANT_BITS[square]);
+            assert_eq!(ROOK_MASKS[square].count_bits(), ROOK_RELEVANT_BITS[square]);
+        }
+    }
+
+    #[test]
+    fn between_and_line_masks_follow_the_diagonals_and_lines() {
+        assert!(get_between_mask(Square::A1, Square::H8) == bb!(B2, C3, D4, E5, F6, G7));
+        assert!(get_between_mask(Square::E1, Square::E4) == bb!(E2, E3));
+        assert!(get_between_mask(Square::E4, Square::E1) == bb!(E2, E3));
+        assert!(get_between_mask(Square::E4, Square::F6).is_empty());
+        assert!(get_between_mask(Square::E4, Square::F5).is_empty());
+
+        assert!(get_line_mask(Square::C3, Square::E5) == bb!(A1, B2, C3, D4, E5, F6, G7, H8));
+        assert!(get_line_mask(Square::B1, Square::B7) == get_line_mask(Square::B8, Square::B2));
+        assert_eq!(get_line_mask(Square::B1, Square::B7).count_bits(), 8);
+        assert!(get_line_mask(Square::E4, Square::F6).is_empty());
+
+        for square in Square::ALL_SQUARES {
+            for other in Square::ALL_SQUARES {
+                let between = get_between_mask(square, other);
+                assert!(between == get_between_mask(other, square));
+                assert!((between & get_line_mask(square, other)) == between);
+                assert!(between.is_empty() || get_queen_mask(square, Bitboard::EMPTY).is_set_sq(other));
+            }
+        }
+    }
+
+    #[test]
+    fn slider_moves_are_computed_once_for_all_threads() {
+        let threads: Vec<_> = (0..4).map(|_| thread::spawn(|| {
+            Square::ALL_SQUARES.map(|square| get_queen_mask(square, Bitboard::EMPTY).count_bits())
+        })).collect();
+        for thread in threads {
+            let counts = thread.join().unwrap();
+            assert_eq!(counts[Square::A8 as usize], 21);
+            assert_eq!(counts[Square::D4 as usize], 27);
+        }
+    }
+
+    #[cfg(all(feature = "sliders_magic_bitboards", target_arch = "x86_64"))]
+    #[test]
+    fn pext_slider_moves_match_the_magic_slider_moves() {
+        if !is_x86_feature_detected!("bmi2") {
+            return;
+        }
+
+        let occupancies = [Bitboard::EMPTY, bb!(B2, D4, E5, G7, C6, F3), bb!(A1, D1, D7, H4, E4, B4), Bitboard(0x00ff_00ff_00ff_00ff)];
+        for square in Square::ALL_SQUARES {
+            for occupancy in occupancies {
+                // SAFETY: The test returns early on CPUs without bmi2
+                unsafe {
+                    assert!(get_bishop_mask_pext(square, occupancy) == get_bishop_mask(square, occupancy));
+                    assert!(get_rook_mask_pext(square, occupancy) == get_rook_mask(square, occupancy));
+                }
+            }
+        }
+    }
+}
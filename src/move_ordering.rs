@@ -0,0 +1,133 @@
+use crate::{bit_move::{BitMove, ScoringMove}, move_flag::MoveFlag, move_list::MoveList, piece::PieceType, position::Position, see::See};
+
+// Most valuable victim, least valuable attacker, indexed by the victim and the attacker
+static MVV_LVA: [[i16; 6]; 6] = generate_mvv_lva_table();
+
+const fn generate_mvv_lva_table() -> [[i16; 6]; 6] {
+    let mut table = [[0; 6]; 6];
+    let mut victim = 0;
+    while victim < 6 {
+        let mut attacker = 0;
+        while attacker < 6 {
+            table[victim][attacker] = (victim as i16 + 1) * 10 - attacker as i16;
+            attacker += 1;
+        }
+        victim += 1;
+    }
+    table
+}
+
+pub struct MoveOrdering { }
+
+impl MoveOrdering {
+    pub const HASH_MOVE_SCORE: i16 = 30000;
+    pub const CAPTURE_SCORE: i16 = 20000;
+    pub const PROMOTION_SCORE: i16 = 10000;
+    pub const LOSING_CAPTURE_SCORE: i16 = -20000;
+
+    // Scores every move in the list, so the search can pick them from best to worst. The hash move
+    // goes first, then captures that don't lose material by MVV-LVA, then promotions, and quiet
+    // moves are scored by the given function, which has to stay below the promotions. Captures
+    // that lose material in the exchange come last.
+    pub fn score_moves(position: &Position, move_list: &mut MoveList<ScoringMove>, hash_move: BitMove, quiet_score: impl Fn(BitMove) -> i16) {
+        for index in 0..move_list.len() {
+            let scoring_move = &mut move_list[index];
+            scoring_move.score = match Self::score_move(position, scoring_move.bit_move, hash_move) {
+                0 => quiet_score(scoring_move.bit_move),
+                score => score,
+            };
+        }
+    }
+
+    // Neither a capture nor a promotion
+    #[inline(always)]
+    pub fn is_quiet(position: &Position, bit_move: BitMove) -> bool {
+        bit_move.promotion().is_none()
+            && bit_move.flag() != MoveFlag::EnPassant
+            && position.get_piece(bit_move.target()) == PieceType::None
+    }
+
+    #[inline(always)]
+    pub fn score_move(position: &Position, bit_move: BitMove, hash_move: BitMove) -> i16 {
+        if bit_move == hash_move {
+            return Self::HASH_MOVE_SCORE;
+        }
+
+        // NOTE: Promotions are ranked by their piece, which stays below the gap between two attackers
+        let promotion_bonus = bit_move.promotion().map_or(0, |promotion| promotion as i16 + 1);
+
+        let victim = match bit_move.flag() {
+            MoveFlag::EnPassant => PieceType::WP,
+            _ => position.get_piece(bit_move.target()),
+        };
+        if victim == PieceType::None {
+            return match promotion_bonus {
+                0 => 0,
+                _ => Self::PROMOTION_SCORE + promotion_bonus,
+            };
+        }
+
+        let attacker = position.get_piece(bit_move.source());
+        let capture_score = if See::see(position, bit_move) < 0 { Self::LOSING_CAPTURE_SCORE } else { Self::CAPTURE_SCORE };
+        capture_score + MVV_LVA[victim as usize % 6][attacker as usize % 6] + promotion_bonus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_flag::Promotion, move_generation::MoveGeneration, move_masks, square::Square};
+
+    use super::*;
+
+    #[test]
+    fn captures_are_ordered_by_victim_then_attacker() {
+        move_masks::init();
+
+        // The queen on d4 can be taken by the pawn on e3 and the rook on d1, while the pawn on e3
+        // can also take the knight on f4. The quiet king move to f2 stands in for the hash move.
+        let position = Fen::parse("4k3/8/8/8/3q1n2/4P3/8/3RK3 w - - 0 1").unwrap();
+        let mut move_list = MoveGeneration::generate_pseudo_legal_scoring_moves(&position);
+        let hash_move = move_list.iter().find(|m| m.bit_move.source() == Square::E1 && m.bit_move.target() == Square::F2).unwrap().bit_move;
+        MoveOrdering::score_moves(&position, &mut move_list, hash_move, |_| 0);
+
+        let ordered: Vec<(Square, Square)> = (0..4)
+            .map(|index| move_list.pick_best(index).bit_move)
+            .map(|bit_move| (bit_move.source(), bit_move.target()))
+            .collect();
+        assert_eq!(ordered, [
+            (Square::E1, Square::F2),
+            (Square::E3, Square::D4),
+            (Square::D1, Square::D4),
+            (Square::E3, Square::F4),
+        ]);
+    }
+
+    #[test]
+    fn promotions_come_before_quiet_moves() {
+        move_masks::init();
+
+        let position = Fen::parse("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut move_list = MoveGeneration::generate_pseudo_legal_scoring_moves(&position);
+        MoveOrdering::score_moves(&position, &mut move_list, BitMove::EMPTY, |_| 0);
+
+        let first = move_list.pick_best(0).bit_move;
+        assert_eq!(first.promotion(), Some(Promotion::Queen));
+        for index in 1..4 {
+            assert!(move_list.pick_best(index).bit_move.promotion().is_some());
+        }
+        assert!(move_list.pick_best(4).bit_move.promotion().is_none());
+    }
+
+    #[test]
+    fn losing_captures_come_after_quiet_moves() {
+        move_masks::init();
+
+        // The pawn on d6 is defended by the pawn on c7, so taking it loses the queen
+        let position = Fen::parse("4k3/2p5/3p4/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let mut move_list = MoveGeneration::generate_pseudo_legal_scoring_moves(&position);
+        MoveOrdering::score_moves(&position, &mut move_list, BitMove::EMPTY, |_| 0);
+
+        let last = (0..move_list.len()).map(|index| move_list.pick_best(index).bit_move).last().unwrap();
+        assert_eq!((last.source(), last.target()), (Square::D1, Square::D6));
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{bit_move::{BitMove, ScoringMove}, move_generation::{GenKind, MoveGeneration}, move_list::MoveList, move_ordering::MoveOrdering, position::Position};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stage {
+    HashMove,
+    GoodCaptures,
+    Killers,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Generates the moves of a node in stages, since most cutoffs come before the quiet moves
+// NOTE: The hash move and the killers are only handed out if they are pseudo-legal here
+pub struct MovePicker<'a> {
+    position: &'a Position,
+    stage: Stage,
+    hash_move: BitMove,
+    killers: [BitMove; 2],
+    captures: MoveList<ScoringMove>,
+    capture_index: usize,
+    quiets: MoveList<ScoringMove>,
+    quiets_generated: bool,
+    quiet_index: usize,
+    killer_index: usize,
+}
+
+impl<'a> MovePicker<'a> {
+    pub fn new(position: &'a Position, hash_move: BitMove, killers: [BitMove; 2]) -> MovePicker<'a> {
+        let mut captures = MoveGeneration::generate_pseudo_legal_scoring_captures(position);
+        MoveOrdering::score_moves(position, &mut captures, BitMove::EMPTY, |_| 0);
+        MovePicker {
+            position,
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            captures,
+            capture_index: 0,
+            quiets: MoveList::new(),
+            quiets_generated: false,
+            quiet_index: 0,
+            killer_index: 0,
+        }
+    }
+
+    // The next move to search, where quiet moves are ordered by the given score, which is only
+    // asked for once the quiet moves are generated
+    pub fn next(&mut self, quiet_score: impl Fn(BitMove) -> i16) -> Option<BitMove> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GoodCaptures;
+                    if self.position.is_pseudo_legal(self.hash_move) {
+                        return Some(self.hash_move);
+                    }
+                    self.hash_move = BitMove::EMPTY;
+                }
+                Stage::GoodCaptures => match self.pick_capture(true) {
+                    Some(bit_move) => return Some(bit_move),
+                    None => self.stage = Stage::Killers,
+                },
+                Stage::Killers => {
+                    let Some(&killer) = self.killers.get(self.killer_index) else {
+                        self.stage = Stage::Quiets;
+                        continue;
+                    };
+                    self.killer_index += 1;
+                    let is_playable = killer != self.hash_move
+                        && self.position.is_pseudo_legal(killer)
+                        && MoveOrdering::is_quiet(self.position, killer);
+                    if is_playable {
+                        return Some(killer);
+                    }
+                }
+                Stage::Quiets => {
+                    self.generate_quiets(&quiet_score);
+                    if self.quiet_index == self.quiets.len() {
+                        self.stage = Stage::BadCaptures;
+                        continue;
+                    }
+                    let bit_move = self.quiets.pick_best(self.quiet_index).bit_move;
+                    self.quiet_index += 1;
+                    if bit_move != self.hash_move && !self.killers.contains(&bit_move) {
+                        return Some(bit_move);
+                    }
+                }
+                Stage::BadCaptures => match self.pick_capture(false) {
+                    Some(bit_move) => return Some(bit_move),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    // The best of the remaining captures, if it's a good one or bad ones are asked for. Captures
+    // that lose material are scored below zero, and promotions without a capture above it.
+    fn pick_capture(&mut self, good: bool) -> Option<BitMove> {
+        while self.capture_index < self.captures.len() {
+            let capture = self.captures.pick_best(self.capture_index);
+            if good && capture.score < 0 {
+                return None;
+            }
+            self.capture_index += 1;
+            if capture.bit_move != self.hash_move {
+                return Some(capture.bit_move);
+            }
+        }
+        None
+    }
+
+    fn generate_quiets(&mut self, quiet_score: impl Fn(BitMove) -> i16) {
+        if self.quiets_generated {
+            return;
+        }
+        self.quiets = MoveGeneration::generate_moves_of_kind::<ScoringMove>(self.position, GenKind::Quiets, |_position, move_list, bit_move| {
+            move_list.add(ScoringMove::from(bit_move));
+        });
+        for index in 0..self.quiets.len() {
+            self.quiets[index].score = quiet_score(self.quiets[index].bit_move);
+        }
+        self.quiets_generated = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_masks};
+
+    use super::*;
+
+    #[test]
+    fn moves_come_in_stages_and_each_only_once() {
+        move_masks::init();
+        let position = Fen::parse(Fen::KIWIPETE_POSITION).unwrap();
+        let all_moves = MoveGeneration::generate_pseudo_legal_moves(&position);
+        let find = |uci: &str| *all_moves.iter().find(|m| m.to_uci_string() == uci).unwrap();
+        let (hash_move, killer) = (find("e2a6"), find("a2a3"));
+        // A killer from a sibling position, which can't be played here
+        let foreign_killer = *MoveGeneration::generate_pseudo_legal_moves(&Position::starting_position())
+            .iter()
+            .find(|m| m.to_uci_string() == "g1f3")
+            .unwrap();
+
+        let mut picker = MovePicker::new(&position, hash_move, [foreign_killer, killer]);
+        let mut picked = Vec::new();
+        while let Some(bit_move) = picker.next(|_| 0) {
+            picked.push(bit_move);
+        }
+
+        assert_eq!(picked.len(), all_moves.len());
+        assert!(all_moves.iter().all(|m| picked.contains(m)));
+        assert!(!picked.contains(&foreign_killer));
+        assert_eq!(picked[0], hash_move);
+        let killer_index = picked.iter().position(|&m| m == killer).unwrap();
+        assert!(picked[1..killer_index].iter().all(|&m| !MoveOrdering::is_quiet(&position, m)));
+        assert!(picked[killer_index + 1..].iter().any(|&m| !MoveOrdering::is_quiet(&position, m)), "losing captures come last");
+    }
+}
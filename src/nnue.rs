@@ -0,0 +1,422 @@
+use std::{fs, io, path::Path, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::{color::Color, eval::Eval, impl_error, piece::PieceType, position::Position, square::Square};
+
+#[derive(Debug)]
+pub struct NnueError(pub &'static str);
+
+impl_error!(NnueError);
+
+impl From<io::Error> for NnueError {
+    fn from(_: io::Error) -> Self {
+        NnueError("Couldn't read network file!")
+    }
+}
+
+// One input per piece and square for each perspective, with its own pieces first and the board flipped for black (HalfKA with one king bucket)
+pub const INPUT_SIZE: usize = 768;
+pub const HIDDEN_SIZE: usize = 128;
+
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+// Keeps the scores of a badly trained network out of the range of mate scores
+const MAX_SCORE: i32 = 5000;
+
+// Magic and layer sizes as little-endian u32, then the weights as little-endian i16
+const MAGIC: &[u8; 4] = b"S32N";
+const HEADER_SIZE: usize = 12;
+const WEIGHT_COUNT: usize = INPUT_SIZE * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1;
+
+// NOTE: A neuron only holds up to QA, so a kind of piece that can add up to more needs several
+const PIECE_SQUARE_NEURONS: [usize; 6] = [8, 4, 4, 6, 8, 1];
+
+#[cfg(feature = "embedded_network")]
+pub fn embedded_network() -> Option<Network> {
+    match Network::parse(include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/nets/default.nnue"))) {
+        Ok(network) => Some(network),
+        Err(error) => {
+            crate::pl!(format!("info string Embedded network is invalid: {}", error));
+            None
+        },
+    }
+}
+
+#[cfg(not(feature = "embedded_network"))]
+pub fn embedded_network() -> Option<Network> {
+    None
+}
+
+pub struct Network {
+    input_weights: Vec<i16>,
+    hidden_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+impl Network {
+    pub fn load(path: &Path) -> Result<Network, NnueError> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Network, NnueError> {
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+            return Err(NnueError("File isn't a Sisyphus32 network!"));
+        }
+        let size = |index: usize| u32::from_le_bytes(bytes[index..index + 4].try_into().unwrap()) as usize;
+        if size(4) != INPUT_SIZE || size(8) != HIDDEN_SIZE {
+            return Err(NnueError("Network has different layer sizes!"));
+        }
+        if bytes.len() != HEADER_SIZE + 2 * WEIGHT_COUNT {
+            return Err(NnueError("Network file has the wrong size!"));
+        }
+
+        let mut weights = bytes[HEADER_SIZE..]
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut take = |count: usize| weights.by_ref().take(count).collect::<Vec<i16>>();
+        Ok(Network {
+            input_weights: take(INPUT_SIZE * HIDDEN_SIZE),
+            hidden_biases: take(HIDDEN_SIZE),
+            output_weights: take(2 * HIDDEN_SIZE),
+            output_bias: take(1)[0],
+        })
+    }
+
+    // Adds up the material and piece-square bonuses of the handcrafted eval, halfway between the middlegame and the endgame
+    // NOTE: The king's neuron starts halfway up, since its bonus can be negative
+    pub fn from_piece_squares() -> Network {
+        const OUTPUT_WEIGHT: i16 = 41;
+        const KING_BIAS: i16 = 128;
+        const TEMPO: i32 = 10;
+
+        let mut input_weights = vec![0; INPUT_SIZE * HIDDEN_SIZE];
+        let mut hidden_biases = vec![0; HIDDEN_SIZE];
+        let mut output_weights = vec![0; 2 * HIDDEN_SIZE];
+        let neurons_per_side: usize = PIECE_SQUARE_NEURONS.iter().sum();
+
+        for (relative_piece, &piece) in PieceType::ALL_PIECES.iter().enumerate() {
+            let kind = relative_piece % 6;
+            let first_neuron = neurons_per_side * (relative_piece / 6) + PIECE_SQUARE_NEURONS[..kind].iter().sum::<usize>();
+            let neurons = PIECE_SQUARE_NEURONS[kind];
+            for neuron in first_neuron..first_neuron + neurons {
+                output_weights[neuron] = if relative_piece < 6 { OUTPUT_WEIGHT } else { -OUTPUT_WEIGHT };
+                if piece == PieceType::WK || piece == PieceType::BK {
+                    hidden_biases[neuron] = KING_BIAS;
+                }
+            }
+            for &square in &Square::ALL_SQUARES {
+                let value = Eval::piece_square_value(piece, square) as i32;
+                let index = Self::input_index(Color::White, piece, square);
+                for offset in 0..neurons {
+                    let share = (value + offset as i32).div_euclid(neurons as i32);
+                    input_weights[index * HIDDEN_SIZE + first_neuron + offset] = share as i16;
+                }
+            }
+        }
+
+        Network {
+            input_weights,
+            hidden_biases,
+            output_weights,
+            output_bias: (TEMPO * QA * QB / SCALE) as i16,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((INPUT_SIZE as u32).to_le_bytes());
+        bytes.extend((HIDDEN_SIZE as u32).to_le_bytes());
+        for weight in self.input_weights.iter().chain(&self.hidden_biases).chain(&self.output_weights).chain([&self.output_bias]) {
+            bytes.extend(weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[inline(always)]
+    fn input_index(perspective: Color, piece: PieceType, square: Square) -> usize {
+        let (relative_piece, relative_square) = match perspective {
+            Color::White => (piece as usize, square as usize ^ 56),
+            Color::Black => ((piece as usize + 6) % 12, square as usize),
+        };
+        relative_piece * 64 + relative_square
+    }
+
+    #[inline(always)]
+    fn input_weights(&self, perspective: Color, piece: PieceType, square: Square) -> &[i16] {
+        let index = Self::input_index(perspective, piece, square);
+        &self.input_weights[index * HIDDEN_SIZE..(index + 1) * HIDDEN_SIZE]
+    }
+
+    pub fn evaluate(&self, accumulator: &Accumulator, side: Color) -> i16 {
+        let mut output = self.output_bias as i32;
+        for (perspective, output_weights) in [side, side.opposite()].into_iter().zip(self.output_weights.chunks_exact(HIDDEN_SIZE)) {
+            output += activated_dot(&accumulator.values[perspective], output_weights);
+        }
+        (output * SCALE / (QA * QB)).clamp(-MAX_SCORE, MAX_SCORE) as i16
+    }
+}
+
+// The pieces the last move put on and took off the board
+// NOTE: Other edits of the board can make more changes than fit, which are dropped
+#[derive(Clone, Copy)]
+pub struct PieceChanges {
+    added: [(PieceType, Square); 4],
+    removed: [(PieceType, Square); 4],
+    added_count: u8,
+    removed_count: u8,
+}
+
+impl Default for PieceChanges {
+    fn default() -> Self {
+        PieceChanges {
+            added: [(PieceType::None, Square::None); 4],
+            removed: [(PieceType::None, Square::None); 4],
+            added_count: 0,
+            removed_count: 0,
+        }
+    }
+}
+
+impl PieceChanges {
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.added_count = 0;
+        self.removed_count = 0;
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, piece: PieceType, square: Square) {
+        if let Some(change) = self.added.get_mut(self.added_count as usize) {
+            *change = (piece, square);
+            self.added_count += 1;
+        }
+    }
+
+    #[inline(always)]
+    pub fn remove(&mut self, piece: PieceType, square: Square) {
+        if let Some(change) = self.removed.get_mut(self.removed_count as usize) {
+            *change = (piece, square);
+            self.removed_count += 1;
+        }
+    }
+}
+
+// The hidden layer before activation for both perspectives, updated incrementally by the search
+#[derive(Clone, Copy)]
+pub struct Accumulator {
+    values: [[i16; HIDDEN_SIZE]; 2],
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Accumulator { values: [[0; HIDDEN_SIZE]; 2] }
+    }
+}
+
+impl Accumulator {
+    pub fn new(network: &Network, position: &Position) -> Accumulator {
+        let mut accumulator = Accumulator::default();
+        for perspective in [Color::White, Color::Black] {
+            accumulator.values[perspective].copy_from_slice(&network.hidden_biases);
+        }
+        for piece in PieceType::ALL_PIECES {
+            let mut bb = position.bbs[piece];
+            while bb.is_not_empty() {
+                accumulator.add(network, piece, bb.pop_lsb());
+            }
+        }
+        accumulator
+    }
+
+    #[inline(always)]
+    pub fn apply(&mut self, network: &Network, changes: &PieceChanges) {
+        for &(piece, square) in &changes.removed[..changes.removed_count as usize] {
+            self.remove(network, piece, square);
+        }
+        for &(piece, square) in &changes.added[..changes.added_count as usize] {
+            self.add(network, piece, square);
+        }
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, network: &Network, piece: PieceType, square: Square) {
+        for perspective in [Color::White, Color::Black] {
+            add_weights(&mut self.values[perspective], network.input_weights(perspective, piece, square));
+        }
+    }
+
+    #[inline(always)]
+    pub fn remove(&mut self, network: &Network, piece: PieceType, square: Square) {
+        for perspective in [Color::White, Color::Black] {
+            subtract_weights(&mut self.values[perspective], network.input_weights(perspective, piece, square));
+        }
+    }
+}
+
+// NOTE: Only set once the CPU is known to support AVX2
+static AVX2_KERNELS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_avx2_kernels(enabled: bool) {
+    AVX2_KERNELS.store(enabled, Ordering::Relaxed);
+}
+
+// NOTE: The loops are written so the compiler vectorizes them, once for SSE2 and once for AVX2
+macro_rules! hidden_layer_kernel {
+    ($name:ident, $portable:ident, $avx2:ident, ($($arg:ident: $ty:ty),*) $(-> $ret:ty)? $body:block) => {
+        #[inline(always)]
+        fn $name($($arg: $ty),*) $(-> $ret)? {
+            #[cfg(target_arch = "x86_64")]
+            if AVX2_KERNELS.load(Ordering::Relaxed) {
+                // SAFETY: AVX2_KERNELS is only enabled after AVX2 has been detected
+                return unsafe { $avx2($($arg),*) };
+            }
+            $portable($($arg),*)
+        }
+
+        #[inline(always)]
+        fn $portable($($arg: $ty),*) $(-> $ret)? $body
+
+        #[cfg(target_arch = "x86_64")]
+        #[target_feature(enable = "avx2")]
+        unsafe fn $avx2($($arg: $ty),*) $(-> $ret)? {
+            $portable($($arg),*)
+        }
+    };
+}
+
+hidden_layer_kernel!(add_weights, add_weights_portable, add_weights_avx2, (values: &mut [i16; HIDDEN_SIZE], weights: &[i16]) {
+    for (value, &weight) in values.iter_mut().zip(weights) {
+        *value += weight;
+    }
+});
+
+hidden_layer_kernel!(subtract_weights, subtract_weights_portable, subtract_weights_avx2, (values: &mut [i16; HIDDEN_SIZE], weights: &[i16]) {
+    for (value, &weight) in values.iter_mut().zip(weights) {
+        *value -= weight;
+    }
+});
+
+hidden_layer_kernel!(activated_dot, activated_dot_portable, activated_dot_avx2, (values: &[i16; HIDDEN_SIZE], weights: &[i16]) -> i32 {
+    values
+        .iter()
+        .zip(weights)
+        .map(|(&value, &weight)| (value as i32).clamp(0, QA) * weight as i32)
+        .sum::<i32>()
+});
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_generation::MoveGeneration, move_masks};
+
+    use super::*;
+
+    fn random_network() -> Network {
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut next = |range: i16| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % (2 * range as u64 + 1)) as i16 - range
+        };
+        Network {
+            input_weights: (0..INPUT_SIZE * HIDDEN_SIZE).map(|_| next(40)).collect(),
+            hidden_biases: (0..HIDDEN_SIZE).map(|_| next(100) + 100).collect(),
+            output_weights: (0..2 * HIDDEN_SIZE).map(|_| next(60)).collect(),
+            output_bias: next(500),
+        }
+    }
+
+    #[test]
+    fn accumulator_updates_match_refresh_and_colors_mirror() {
+        move_masks::init();
+        let network = random_network();
+
+        for fen in [Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, "4k3/1P6/8/3pP3/8/8/8/4K2R w K d6 0 1"] {
+            let position = Fen::parse(fen).unwrap();
+            let accumulator = Accumulator::new(&network, &position);
+            for &bit_move in MoveGeneration::generate_legal_moves(&position).iter() {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                let mut updated = accumulator;
+                updated.apply(&network, &position_copy.piece_changes);
+                assert_eq!(updated.values, Accumulator::new(&network, &position_copy).values, "{fen} {}", bit_move.to_uci_string());
+            }
+        }
+
+        let white = Fen::parse("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -").unwrap();
+        let black = Fen::parse("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq -").unwrap();
+        let (white_accumulator, black_accumulator) = (Accumulator::new(&network, &white), Accumulator::new(&network, &black));
+        assert_eq!(white_accumulator.values[0], black_accumulator.values[1]);
+        assert_eq!(network.evaluate(&white_accumulator, white.side), network.evaluate(&black_accumulator, black.side));
+    }
+
+    #[test]
+    fn piece_square_network_adds_up_the_piece_square_values() {
+        move_masks::init();
+        let network = Network::from_piece_squares();
+
+        for fen in [Fen::STARTING_POSITION, Fen::KIWIPETE_POSITION, "4k3/8/8/8/8/8/8/3QK3 b - - 0 1", "6k1/1P3ppp/8/8/8/8/5PPP/r5K1 w - - 0 1"] {
+            let position = Fen::parse(fen).unwrap();
+            let mut expected = 10;
+            for piece in PieceType::ALL_PIECES {
+                let mut bb = position.bbs[piece];
+                while bb.is_not_empty() {
+                    let value = Eval::piece_square_value(piece, bb.pop_lsb()) as i32;
+                    expected += if ((piece as usize) < 6) == (position.side == Color::White) { value } else { -value };
+                }
+            }
+            let score = network.evaluate(&Accumulator::new(&network, &position), position.side) as i32;
+            assert!((score - expected).abs() <= expected.abs() / 100 + 1, "{fen}: {score} instead of {expected}");
+        }
+    }
+
+    #[cfg(feature = "embedded_network")]
+    #[test]
+    fn embedded_network_is_the_piece_square_network() {
+        assert_eq!(embedded_network().unwrap().to_bytes(), Network::from_piece_squares().to_bytes());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn avx2_kernels_match_the_portable_kernels() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+
+        let network = random_network();
+        let position = Fen::parse(Fen::KIWIPETE_POSITION).unwrap();
+        let accumulator = Accumulator::new(&network, &position);
+        let weights = network.input_weights(Color::White, PieceType::WQ, Square::D4);
+        for perspective in [Color::White, Color::Black] {
+            let (mut portable, mut avx2) = (accumulator.values[perspective], accumulator.values[perspective]);
+            add_weights_portable(&mut portable, weights);
+            subtract_weights_portable(&mut portable, &network.output_weights[..HIDDEN_SIZE]);
+            // SAFETY: The test returns early on CPUs without AVX2
+            unsafe {
+                add_weights_avx2(&mut avx2, weights);
+                subtract_weights_avx2(&mut avx2, &network.output_weights[..HIDDEN_SIZE]);
+                assert_eq!(activated_dot_avx2(&avx2, weights), activated_dot_portable(&portable, weights));
+            }
+            assert_eq!(avx2, portable);
+        }
+    }
+
+    #[test]
+    fn network_round_trips_and_rejects_bad_files() {
+        let network = random_network();
+        let bytes = network.to_bytes();
+        assert_eq!(Network::parse(&bytes).unwrap().to_bytes(), bytes);
+
+        assert!(Network::parse(&bytes[..bytes.len() - 2]).is_err());
+        assert!(Network::parse(b"S32N").is_err());
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(Network::parse(&wrong_magic).is_err());
+        let mut wrong_sizes = bytes.clone();
+        wrong_sizes[8] = 0;
+        assert!(Network::parse(&wrong_sizes).is_err());
+        assert!(Network::load(Path::new("missing-network.nnue")).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::search_shared::SearchShared;
+
+// NOTE: Nodes are added to the shared count in batches to keep the threads off the cache line
+#[derive(Default)]
+pub struct NodeCounter {
+    shared: Arc<SearchShared>,
+    pending: u64,
+}
+
+impl NodeCounter {
+    pub fn with_shared(shared: Arc<SearchShared>) -> NodeCounter {
+        NodeCounter { shared, pending: 0 }
+    }
+
+    #[inline(always)]
+    pub fn increment(&mut self) {
+        self.pending += 1;
+    }
+
+    pub fn flush(&mut self) {
+        self.shared.add_nodes(self.pending);
+        self.pending = 0;
+    }
+
+    // Exact as far as this thread is concerned, other threads may still have nodes pending
+    pub fn total(&self) -> u64 {
+        self.shared.nodes() + self.pending
+    }
+}
+
+// Measures the nodes per second over roughly the last second
+#[derive(Default)]
+pub struct NpsMeter {
+    samples: VecDeque<(u128, u64)>,
+}
+
+impl NpsMeter {
+    const WINDOW_MILLIS: u128 = 1000;
+    const SAMPLE_INTERVAL_MILLIS: u128 = 50;
+
+    pub fn record(&mut self, time: u128, nodes: u64) {
+        if self.samples.back().is_some_and(|&(last_time, _)| time < last_time + Self::SAMPLE_INTERVAL_MILLIS) {
+            return;
+        }
+        self.samples.push_back((time, nodes));
+
+        // The newest sample that is at least a window old is kept as the start of the window
+        while self.samples.len() > 2 && self.samples[1].0 + Self::WINDOW_MILLIS <= time {
+            self.samples.pop_front();
+        }
+    }
+
+    // NOTE: Until there is a sample old enough to measure against, the speed since the start is used
+    pub fn nps(&self, time: u128, nodes: u64) -> u64 {
+        let (start_time, start_nodes) = match self.samples.front() {
+            Some(&(sample_time, sample_nodes)) if sample_time + Self::SAMPLE_INTERVAL_MILLIS <= time => (sample_time, sample_nodes),
+            _ => (0, 0),
+        };
+        let elapsed = time.saturating_sub(start_time).max(1);
+        (nodes.saturating_sub(start_nodes) as u128 * 1000 / elapsed) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_add_up_and_nps_uses_last_second() {
+        let shared = Arc::new(SearchShared::default());
+        let mut first = NodeCounter::with_shared(shared.clone());
+        let mut second = NodeCounter::with_shared(shared.clone());
+        for _ in 0..10 {
+            first.increment();
+            second.increment();
+        }
+        first.flush();
+        assert_eq!(first.total(), 10);
+        assert_eq!(second.total(), 20);
+        second.flush();
+        assert_eq!(shared.nodes(), 20);
+
+        // Slow for the first 5 seconds, then 10 times faster
+        let mut meter = NpsMeter::default();
+        for time in (0..=6000).step_by(100) {
+            let nodes = if time <= 5000 { time as u64 * 100 } else { 500_000 + (time as u64 - 5000) * 1000 };
+            meter.record(time, nodes);
+        }
+        assert_eq!(meter.nps(6000, 1_500_000), 1_000_000);
+    }
+}
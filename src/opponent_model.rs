@@ -0,0 +1,80 @@
+// Learns how long the opponent thinks, since pondering buys more time against a slow opponent
+#[derive(Default)]
+pub struct OpponentModel {
+    last_remaining: Option<u128>,
+    average_move_time: Option<u128>,
+}
+
+impl OpponentModel {
+    // NOTE: Each new observation has a weight of 1 / MOVE_TIME_SMOOTHING in the average
+    const MOVE_TIME_SMOOTHING: u128 = 4;
+    const MAX_PONDER_CREDIT_PERCENT: u128 = 50;
+
+    // Records the opponent's clock before the own move. The time spent on the previous move is
+    // the difference to the previous observation, including the increment gained since.
+    pub fn observe(&mut self, remaining: u128, increment: u128) {
+        if let Some(last_remaining) = self.last_remaining {
+            let move_time = (last_remaining + increment).saturating_sub(remaining);
+            self.average_move_time = Some(match self.average_move_time {
+                Some(average) => (average * (Self::MOVE_TIME_SMOOTHING - 1) + move_time) / Self::MOVE_TIME_SMOOTHING,
+                None => move_time,
+            });
+        }
+        self.last_remaining = Some(remaining);
+    }
+
+    pub fn average_move_time(&self) -> Option<u128> {
+        self.average_move_time
+    }
+
+    // Scales the planned time between 3/4 and 5/4, depending on how the opponent's average move
+    // time compares to it. An opponent using the same time leaves the budget unchanged.
+    pub fn adjust_stop_time(&self, stop_time: u128) -> u128 {
+        match self.average_move_time {
+            Some(average) if stop_time > 0 => {
+                let ratio_percent = (average * 100 / stop_time).min(200);
+                stop_time * (500 - ratio_percent) / 400
+            }
+            _ => stop_time,
+        }
+    }
+
+    // How much of the time spent pondering counts towards the own budget after a ponderhit, in
+    // percent. The longer the opponent usually thinks, the further the ponder search got, so the
+    // more of it is credited.
+    pub fn ponder_credit_percent(&self, stop_time: u128) -> u128 {
+        match self.average_move_time {
+            Some(average) if stop_time > 0 => {
+                (average * Self::MAX_PONDER_CREDIT_PERCENT / stop_time).min(Self::MAX_PONDER_CREDIT_PERCENT)
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opponent_model_tracks_move_times() {
+        let mut model = OpponentModel::default();
+        assert_eq!(model.adjust_stop_time(1000), 1000);
+
+        model.observe(60_000, 1000);
+        model.observe(57_000, 1000);
+        assert_eq!(model.average_move_time(), Some(4000));
+        model.observe(57_000, 1000);
+        assert_eq!(model.average_move_time(), Some(3250));
+
+        assert_eq!(model.adjust_stop_time(3250), 3250);
+        assert_eq!(model.adjust_stop_time(1000), 750);
+        assert_eq!(model.ponder_credit_percent(1000), 50);
+
+        let mut fast_opponent = OpponentModel::default();
+        fast_opponent.observe(60_000, 0);
+        fast_opponent.observe(60_000, 0);
+        assert_eq!(fast_opponent.adjust_stop_time(1000), 1250);
+        assert_eq!(fast_opponent.ponder_credit_percent(1000), 0);
+    }
+}
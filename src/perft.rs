@@ -4,9 +4,9 @@ use crate::{fen::Fen, pl, position::Position, timer::Timer, move_generation::Mov
 use {std::sync::Arc, rayon::iter::{IntoParallelRefIterator, ParallelIterator}};
 
 pub struct PerftResult {
-    depth: u8,
-    nodes: u64,
-    time: u128,
+    pub depth: u8,
+    pub nodes: u64,
+    pub time: u128,
 }
 
 struct PerftPosition {
@@ -127,13 +127,12 @@ impl Perft {
         if print_result { pl!("\n  Performance Test\n"); }
 
         let mut position_copy = position.clone();
-        let old_castling_rights = position.castling_rights;
         
         for mv in MoveGeneration::generate_pseudo_legal_moves(position).iter() {
             if position_copy.make_move(*mv) {
-                current_nodes += Self::perft_driver(&position_copy, depth - 1);
+                current_nodes += Self::perft_driver(&mut position_copy, depth - 1);
             }
-            position_copy.undo_move(*mv, old_castling_rights);
+            position_copy.undo_move(*mv);
 
             if print_result {
                 pl!(format!("  Move: {:<5} Nodes: {}", mv.to_uci_string(), current_nodes));
@@ -258,19 +257,16 @@ impl Perft {
 
     #[cfg(all(feature = "perft_single_thread", feature = "revert_with_undo_move"))]
     #[inline(always)]
-    fn perft_driver(position: &Position, depth: u8) -> u64 {
+    fn perft_driver(position: &mut Position, depth: u8) -> u64 {
         if depth == 0 {
             1
         } else {
             let mut nodes = 0;
-            let mut position_copy = position.clone();
-            let old_castling_rights = position.castling_rights;
-            
             for mv in MoveGeneration::generate_pseudo_legal_moves(position).iter() {
-                if position_copy.make_move(*mv) {
-                    nodes += Self::perft_driver(&position_copy, depth - 1);
+                if position.make_move(*mv) {
+                    nodes += Self::perft_driver(position, depth - 1);
                 }
-                position_copy.undo_move(*mv, old_castling_rights);
+                position.undo_move(*mv);
             }
             nodes
         }
@@ -0,0 +1,243 @@
+use std::{fs, io, iter::Peekable, path::Path, str::Chars};
+
+use crate::{fen::{Fen, FenError}, game::Game, impl_error, impl_error_from, legality::{Legality, LegalityChecker, LegalityError}, position::Position};
+
+#[derive(Debug)]
+pub struct PgnError(pub &'static str);
+
+impl_error!(PgnError);
+impl_error_from!(FenError, PgnError);
+impl_error_from!(LegalityError, PgnError);
+
+impl From<io::Error> for PgnError {
+    fn from(_: io::Error) -> Self {
+        PgnError("Couldn't read PGN file!")
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum PgnToken {
+    Tag(String, String),
+    Move(String),
+    Result(String),
+}
+
+// Only the tags and the main line of each game are kept
+pub struct Pgn { }
+
+impl Pgn {
+    pub fn load(path: &Path) -> Result<Vec<Game>, PgnError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(pgn: &str) -> Result<Vec<Game>, PgnError> {
+        let mut games = Vec::new();
+        let mut tags: Vec<(String, String)> = Vec::new();
+        let mut game: Option<Game> = None;
+
+        for token in Self::tokenize(pgn)? {
+            match token {
+                PgnToken::Tag(name, value) => {
+                    // NOTE: Tags after the movetext belong to the next game, even if the result is missing
+                    if let Some(game) = game.take() {
+                        games.push(game);
+                    }
+                    tags.push((name, value));
+                }
+                PgnToken::Move(san) => {
+                    let game = match &mut game {
+                        Some(game) => game,
+                        None => game.insert(Self::start_game(std::mem::take(&mut tags))?),
+                    };
+                    match LegalityChecker::check(game.position(), &san)? {
+                        Legality::Legal(bit_move) => {
+                            game.make_move(bit_move);
+                        }
+                        Legality::Illegal(_) => return Err(PgnError("Found illegal move in movetext!")),
+                    }
+                }
+                PgnToken::Result(_) => {
+                    let game = match game.take() {
+                        Some(game) => game,
+                        None => Self::start_game(std::mem::take(&mut tags))?,
+                    };
+                    games.push(game);
+                }
+            }
+        }
+
+        if let Some(game) = game {
+            games.push(game);
+        } else if !tags.is_empty() {
+            games.push(Self::start_game(tags)?);
+        }
+
+        Ok(games)
+    }
+
+    // Games that don't start from the usual position give it in the FEN tag
+    fn start_game(tags: Vec<(String, String)>) -> Result<Game, PgnError> {
+        let position = match tags.iter().find(|(name, _)| name == "FEN") {
+            Some((_, fen)) => Fen::parse(fen)?,
+            None => Position::starting_position(),
+        };
+
+        let mut game = Game::new(position);
+        for (name, value) in tags {
+            game.set_tag(&name, &value);
+        }
+        Ok(game)
+    }
+
+    fn tokenize(pgn: &str) -> Result<Vec<PgnToken>, PgnError> {
+        let mut tokens = Vec::new();
+        let mut chars = pgn.chars().peekable();
+
+        while let Some(&ch) = chars.peek() {
+            match ch {
+                '[' => {
+                    chars.next();
+                    tokens.push(Self::read_tag(&mut chars)?);
+                }
+                '{' => {
+                    chars.find(|&ch| ch == '}').ok_or(PgnError("Comment isn't closed!"))?;
+                }
+                ';' | '%' => {
+                    chars.find(|&ch| ch == '\n');
+                }
+                '(' => Self::skip_variation(&mut chars)?,
+                ')' => return Err(PgnError("Found end of variation that wasn't started!")),
+                _ if ch.is_whitespace() => {
+                    chars.next();
+                }
+                _ => {
+                    let mut word = String::new();
+                    while let Some(&ch) = chars.peek() {
+                        if ch.is_whitespace() || "[]{}();".contains(ch) {
+                            break;
+                        }
+                        word.push(ch);
+                        chars.next();
+                    }
+
+                    match word.as_str() {
+                        "1-0" | "0-1" | "1/2-1/2" | "*" => tokens.push(PgnToken::Result(word)),
+                        // Annotation glyphs like $1 don't change the game
+                        _ if word.starts_with('$') => (),
+                        _ => {
+                            // NOTE: Move numbers can be written right in front of the move, like 1.e4 or 12...Nf6
+                            let after_number = word.trim_start_matches(|ch: char| ch.is_ascii_digit());
+                            let san = match after_number.strip_prefix('.') {
+                                Some(san) => san.trim_start_matches('.'),
+                                None => &word,
+                            };
+                            if !san.is_empty() {
+                                tokens.push(PgnToken::Move(san.to_owned()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    // Reads a tag like [Event "Casual game"], where quotes and backslashes in the value are escaped
+    fn read_tag(chars: &mut Peekable<Chars>) -> Result<PgnToken, PgnError> {
+        let mut name = String::new();
+        while let Some(ch) = chars.next_if(|&ch| ch != '"' && ch != ']') {
+            name.push(ch);
+        }
+        if chars.next() != Some('"') {
+            return Err(PgnError("Tag has no value!"));
+        }
+
+        let mut value = String::new();
+        loop {
+            match chars.next().ok_or(PgnError("Tag value isn't closed!"))? {
+                '"' => break,
+                '\\' => value.push(chars.next().ok_or(PgnError("Tag value isn't closed!"))?),
+                ch => value.push(ch),
+            }
+        }
+
+        chars.find(|&ch| ch == ']').ok_or(PgnError("Tag isn't closed!"))?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(PgnError("Tag has no name!"));
+        }
+        Ok(PgnToken::Tag(name.to_owned(), value))
+    }
+
+    // Skips a variation along with the variations and comments inside of it
+    fn skip_variation(chars: &mut Peekable<Chars>) -> Result<(), PgnError> {
+        let mut depth = 0;
+        while let Some(ch) = chars.next() {
+            match ch {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                '{' => {
+                    chars.find(|&ch| ch == '}').ok_or(PgnError("Comment isn't closed!"))?;
+                }
+                _ => (),
+            }
+        }
+        Err(PgnError("Variation isn't closed!"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{color::Color, game_result::GameResult, move_masks};
+
+    use super::*;
+
+    #[test]
+    fn parse_reads_tags_and_the_main_line() {
+        move_masks::init();
+        let pgn = r#"[Event "Casual \"blitz\" game"]
+[White "Alice"]
+[Black "Bob"]
+[Result "0-1"]
+
+1. f3 {A bad start} e5 $2 (1... d5 2. g4 (2. e4) e5) 2.g4?? Qh4# 0-1
+
+[Event "Second game"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 40"]
+
+40. 0-0-0 Kf7 ; the king runs
+41. Rd7+ *
+"#;
+        let games = Pgn::parse(pgn).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("Event"), Some("Casual \"blitz\" game"));
+        assert_eq!(games[0].tag("Black"), Some("Bob"));
+        assert_eq!(games[0].to_san_movetext(), "1. f3 e5 2. g4 Qh4#");
+        assert_eq!(games[0].game_result(), Some(GameResult::Checkmate(Color::Black)));
+
+        assert_eq!(games[1].tag("Event"), Some("Second game"));
+        assert_eq!(games[1].to_san_movetext(), "40. O-O-O Kf7 41. Rd7+");
+    }
+
+    #[test]
+    fn parse_rejects_broken_games() {
+        move_masks::init();
+        for pgn in [
+            "1. e4 e5 2. Ke3 *",
+            "1. e4 {Unfinished comment",
+            "1. e4 (1. d4 *",
+            "[Event \"Unfinished tag\n1. e4 *",
+            "[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n1. e4 *",
+        ] {
+            assert!(Pgn::parse(pgn).is_err(), "{pgn}");
+        }
+    }
+}
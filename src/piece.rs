@@ -58,12 +58,11 @@ impl PieceType {
 
     #[inline(always)]
     pub fn color(self) -> Color {
-        if PieceType::WHITE_PIECES.contains(&self) {
+        debug_assert_ne!(self, PieceType::None, "Illegal color found!");
+        if (self as u8) < PieceType::BP as u8 {
             Color::White
-        } else if PieceType::BLACK_PIECES.contains(&self) {
-            Color::Black
         } else {
-            panic!("Illegal color found!")
+            Color::Black
         }
     }
 }
@@ -107,7 +106,10 @@ impl From<char> for PieceType {
             'r' => PieceType::BR,
             'q' => PieceType::BQ,
             'k' => PieceType::BK,
-            _ => panic!("Illegal piece char found!"),
+            _ => {
+                debug_assert!(false, "Illegal piece char found!");
+                PieceType::None
+            }
         }
     }
 }
@@ -127,7 +129,10 @@ impl From<PieceType> for char {
             PieceType::BR => 'r',
             PieceType::BQ => 'q',
             PieceType::BK => 'k',
-            PieceType::None => panic!("Can't convert none piece type to char!"),
+            PieceType::None => {
+                debug_assert!(false, "Can't convert none piece type to char!");
+                '.'
+            }
         }
     }
 }
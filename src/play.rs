@@ -0,0 +1,246 @@
+use std::io;
+
+use crate::{bit_move::BitMove, color::Color, game::Game, move_generation::MoveGeneration, pl, position::Position, search::Search, timer::{Clock, MonotonicClock, TimeLimits, Timer}};
+
+// Keeps the remaining time of both sides, only the clock of the side to move is running
+pub struct ChessClock<C: Clock = MonotonicClock> {
+    timer: Timer<C>,
+    remaining: [u128; 2],
+    increment: u128,
+}
+
+impl ChessClock {
+    pub fn new(base_time: u128, increment: u128) -> Self {
+        ChessClock::with_clock(MonotonicClock::default(), base_time, increment)
+    }
+}
+
+impl<C: Clock> ChessClock<C> {
+    pub fn with_clock(clock: C, base_time: u128, increment: u128) -> Self {
+        ChessClock {
+            timer: Timer::with_clock(clock),
+            remaining: [base_time; 2],
+            increment,
+        }
+    }
+
+    pub fn start_turn(&mut self) {
+        self.timer.reset();
+    }
+
+    // Remaining time of the given side, including the time spent on the current turn
+    pub fn remaining(&self, color: Color, to_move: Color) -> u128 {
+        if color == to_move {
+            self.remaining[color].saturating_sub(self.timer.get_time_passed_millis())
+        } else {
+            self.remaining[color]
+        }
+    }
+
+    pub fn increment(&self) -> u128 {
+        self.increment
+    }
+
+    // Stops the clock of the side that just moved and adds the increment.
+    // Returns false if the side ran out of time before completing the move.
+    pub fn end_turn(&mut self, color: Color) -> bool {
+        let time_spent = self.timer.get_time_passed_millis();
+        if time_spent >= self.remaining[color] {
+            self.remaining[color] = 0;
+            return false;
+        }
+
+        self.remaining[color] = self.remaining[color] - time_spent + self.increment;
+        true
+    }
+}
+
+fn format_time(millis: u128) -> String {
+    format!("{}:{:02}.{}", millis / 60_000, millis / 1000 % 60, millis / 100 % 10)
+}
+
+enum HumanCommand {
+    Move(BitMove),
+    Undo,
+    Redo,
+    Resign,
+}
+
+pub struct Play {
+    game: Game,
+    human_side: Color,
+    clock: ChessClock,
+    temperature: u16,
+    seed: Option<u64>,
+}
+
+impl Play {
+    pub fn new(position: Position, human_side: Color, base_time: u128, increment: u128) -> Play {
+        let mut game = Game::new(position);
+        let (white, black) = match human_side {
+            Color::White => ("Human", "Sisyphus32"),
+            Color::Black => ("Sisyphus32", "Human"),
+        };
+        game.set_tag("Event", "Casual game");
+        game.set_tag("White", white);
+        game.set_tag("Black", black);
+
+        Play {
+            game,
+            human_side,
+            clock: ChessClock::new(base_time, increment),
+            temperature: 0,
+            seed: None,
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: u16, seed: Option<u64>) -> Play {
+        self.temperature = temperature;
+        self.seed = seed;
+        self
+    }
+
+    pub fn run(&mut self) {
+        pl!("Enter moves like e2e4 or e7e8q, or one of undo, redo, moves, fen, save <file.pgn> and resign");
+
+        loop {
+            self.clock.start_turn();
+            self.print_state();
+
+            if let Some(result) = self.game.game_result() {
+                pl!(result);
+                return;
+            }
+
+            let side = self.game.position().side;
+
+            let bit_move = if side == self.human_side {
+                match self.read_human_command() {
+                    HumanCommand::Move(bit_move) => bit_move,
+                    // NOTE: Taking back and replaying moves doesn't change the clocks
+                    HumanCommand::Undo => {
+                        self.step_back_to_human_turn(Game::undo);
+                        continue;
+                    }
+                    HumanCommand::Redo => {
+                        self.step_back_to_human_turn(Game::redo);
+                        continue;
+                    }
+                    HumanCommand::Resign => {
+                        pl!(format!("{} resigns", side));
+                        return;
+                    }
+                }
+            } else {
+                self.engine_move()
+            };
+
+            if !self.clock.end_turn(side) {
+                pl!(format!("{} lost on time", side));
+                return;
+            }
+
+            pl!(format!("{} plays {}", side, self.game.position().move_to_san(bit_move)));
+            self.game.make_move(bit_move);
+        }
+    }
+
+    // Undoes or redoes a full move, so it's the human's turn again afterwards if possible
+    fn step_back_to_human_turn(&mut self, step: fn(&mut Game) -> Option<BitMove>) {
+        if step(&mut self.game).is_none() {
+            eprintln!("No moves left!");
+            return;
+        }
+        if self.game.position().side != self.human_side {
+            step(&mut self.game);
+        }
+    }
+
+    fn print_state(&self) {
+        let side = self.game.position().side;
+        pl!(self.game.position());
+        pl!(format!(
+            "  White: {}   Black: {}",
+            format_time(self.clock.remaining(Color::White, side)),
+            format_time(self.clock.remaining(Color::Black, side)),
+        ));
+    }
+
+    // Keeps asking until a legal move or a command that changes the game is entered.
+    // Commands that only show information are handled right away.
+    fn read_human_command(&self) -> HumanCommand {
+        let position = self.game.position();
+        loop {
+            let mut line = String::new();
+            if let Ok(0) | Err(_) = io::stdin().read_line(&mut line) {
+                return HumanCommand::Resign;
+            }
+
+            let mut words = line.split_whitespace();
+            match words.next().unwrap_or("") {
+                "" => (),
+                "resign" | "quit" | "exit" => return HumanCommand::Resign,
+                "undo" => return HumanCommand::Undo,
+                "redo" => return HumanCommand::Redo,
+                "fen" => pl!(position.to_fen()),
+                "moves" => {
+                    let legal_moves = MoveGeneration::generate_legal_moves(position);
+                    let san_moves: Vec<String> = legal_moves.iter().map(|&m| position.move_to_san(m)).collect();
+                    pl!(san_moves.join(" "));
+                }
+                "save" => match words.next() {
+                    Some(path) => match self.game.save_pgn(path) {
+                        Ok(()) => pl!(format!("Saved game to {}", path)),
+                        Err(error) => eprintln!("Couldn't save game: {error}"),
+                    },
+                    None => eprintln!("Didn't find file path!"),
+                },
+                move_string => match BitMove::from_uci(position, move_string) {
+                    Ok(bit_move) => return HumanCommand::Move(bit_move),
+                    Err(error) => eprintln!("{error}"),
+                },
+            }
+        }
+    }
+
+    fn engine_move(&self) -> BitMove {
+        let position = self.game.position();
+        let side = position.side;
+        let remaining = self.clock.remaining(side, side);
+
+        // NOTE: Never plan to use more than half of the remaining time, so a large increment
+        // can't make the engine flag
+        let overhead = TimeLimits::DEFAULT_MOVE_OVERHEAD;
+        let stop_time = TimeLimits::from_clock(remaining, self.clock.increment(), None, overhead).soft.min(remaining / 2);
+        let time_limits = TimeLimits::with_soft_limit(remaining, stop_time, overhead);
+        Search::new(time_limits.soft)
+            .with_hard_time_limit(time_limits.hard)
+            .with_temperature(self.temperature, self.seed)
+            .go_isolated(position, Search::MAX_DEPTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timer::VirtualClock;
+
+    use super::*;
+
+    #[test]
+    fn chess_clock_applies_increment_and_flags() {
+        let virtual_clock = VirtualClock::default();
+        let mut clock = ChessClock::with_clock(virtual_clock.clone(), 1000, 200);
+
+        clock.start_turn();
+        virtual_clock.advance_millis(300);
+        assert_eq!(clock.remaining(Color::White, Color::White), 700);
+        assert_eq!(clock.remaining(Color::Black, Color::White), 1000);
+        assert!(clock.end_turn(Color::White));
+        assert_eq!(clock.remaining(Color::White, Color::Black), 900);
+
+        clock.start_turn();
+        virtual_clock.advance_millis(1000);
+        assert!(!clock.end_turn(Color::Black));
+        assert_eq!(clock.remaining(Color::Black, Color::White), 0);
+    }
+}
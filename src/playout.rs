@@ -0,0 +1,190 @@
+extern crate rand;
+
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::{bit_move::BitMove, color::Color, move_flag::{MoveFlag, Promotion}, move_generation::MoveGeneration, pl, piece::PieceType, position::Position, timer::Timer};
+
+// NOTE: Games that go on for this long are almost certainly shuffling pieces around
+const MAX_PLAYOUT_PLIES: u32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlayoutPolicy {
+    Random,
+    Weighted,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlayoutOutcome {
+    WhiteWins,
+    BlackWins,
+    Stalemate,
+    FiftyMoveRule,
+    Repetition,
+    DeadPosition,
+    MaxLength,
+}
+
+#[derive(Default)]
+pub struct PlayoutStatistics {
+    pub games: u32,
+    pub white_wins: u32,
+    pub black_wins: u32,
+    pub stalemates: u32,
+    pub fifty_move_draws: u32,
+    pub repetition_draws: u32,
+    pub dead_position_draws: u32,
+    pub max_length_draws: u32,
+    pub total_plies: u64,
+}
+
+impl PlayoutStatistics {
+    fn add(&mut self, outcome: PlayoutOutcome, plies: u32) {
+        self.games += 1;
+        self.total_plies += plies as u64;
+        match outcome {
+            PlayoutOutcome::WhiteWins => self.white_wins += 1,
+            PlayoutOutcome::BlackWins => self.black_wins += 1,
+            PlayoutOutcome::Stalemate => self.stalemates += 1,
+            PlayoutOutcome::FiftyMoveRule => self.fifty_move_draws += 1,
+            PlayoutOutcome::Repetition => self.repetition_draws += 1,
+            PlayoutOutcome::DeadPosition => self.dead_position_draws += 1,
+            PlayoutOutcome::MaxLength => self.max_length_draws += 1,
+        }
+    }
+}
+
+pub struct Playout { }
+
+impl Playout {
+    pub fn playout_test(position: &Position, games: u32, policy: PlayoutPolicy) -> PlayoutStatistics {
+        let timer = Timer::new();
+        let mut statistics = PlayoutStatistics::default();
+
+        for _ in 0..games {
+            let (outcome, plies) = Self::play_game(position, policy);
+            statistics.add(outcome, plies);
+        }
+
+        let average_plies = statistics.total_plies as f64 / statistics.games.max(1) as f64;
+
+        pl!(format!("
+    Playout results for {} games ({:?} policy):
+  |------------------------------------------|
+  | {:<28} | {:<9} |
+  |------------------------------------------|
+  | {:<28} | {:<9} |
+  | {:<28} | {:<9} |
+  | {:<28} | {:<9} |
+  | {:<28} | {:<9} |
+  | {:<28} | {:<9} |
+  | {:<28} | {:<9} |
+  | {:<28} | {:<9} |
+  |------------------------------------------|
+  | {:<28} | {:<9.1} |
+  | {:<28} | {:<9} |
+  |------------------------------------------|",
+            statistics.games, policy,
+            "Outcome", "Games",
+            "White wins", statistics.white_wins,
+            "Black wins", statistics.black_wins,
+            "Stalemate", statistics.stalemates,
+            "Fifty-move rule", statistics.fifty_move_draws,
+            "Threefold repetition", statistics.repetition_draws,
+            "Dead position", statistics.dead_position_draws,
+            "Maximum length reached", statistics.max_length_draws,
+            "Average plies", average_plies,
+            "Time (milliseconds)", timer.get_time_passed_millis(),
+        ));
+
+        statistics
+    }
+
+    pub fn play_game(position: &Position, policy: PlayoutPolicy) -> (PlayoutOutcome, u32) {
+        let mut rng = rand::rng();
+        let mut position = position.clone();
+        let mut repetitions: HashMap<u64, u8> = HashMap::new();
+
+        for plies in 0..MAX_PLAYOUT_PLIES {
+            let moves = MoveGeneration::generate_legal_moves(&position);
+
+            if moves.len() == 0 {
+                let outcome = if !position.in_check() {
+                    PlayoutOutcome::Stalemate
+                } else if position.side == Color::White {
+                    PlayoutOutcome::BlackWins
+                } else {
+                    PlayoutOutcome::WhiteWins
+                };
+                return (outcome, plies);
+            }
+
+            if position.halfmove_clock >= 100 {
+                return (PlayoutOutcome::FiftyMoveRule, plies);
+            }
+
+            if position.is_dead_position() {
+                return (PlayoutOutcome::DeadPosition, plies);
+            }
+
+            let repetition_count = repetitions.entry(position.zobrist_key).or_insert(0);
+            *repetition_count += 1;
+            if *repetition_count >= 3 {
+                return (PlayoutOutcome::Repetition, plies);
+            }
+
+            let bit_move = match policy {
+                PlayoutPolicy::Random => moves[rng.random_range(0..moves.len())],
+                PlayoutPolicy::Weighted => {
+                    let weights: Vec<u32> = moves.iter().map(|&m| Self::move_weight(&position, m)).collect();
+                    let mut pick = rng.random_range(0..weights.iter().sum::<u32>());
+                    let mut index = 0;
+                    while pick >= weights[index] {
+                        pick -= weights[index];
+                        index += 1;
+                    }
+                    moves[index]
+                }
+            };
+
+            position.make_move(bit_move);
+            if position.halfmove_clock == 0 {
+                repetitions.clear();
+            }
+        }
+
+        (PlayoutOutcome::MaxLength, MAX_PLAYOUT_PLIES)
+    }
+
+    // Captures and promotions are preferred, which makes games resemble actual play more closely
+    fn move_weight(position: &Position, bit_move: BitMove) -> u32 {
+        match bit_move.flag() {
+            MoveFlag::Promotion if bit_move.promotion() == Some(Promotion::Queen) => 8,
+            MoveFlag::EnPassant => 4,
+            _ if position.get_piece(bit_move.target()) != PieceType::None => 4,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_masks};
+
+    use super::*;
+
+    #[test]
+    fn playout_detects_immediate_outcomes() {
+        move_masks::init();
+
+        let checkmate = Fen::parse("R5k1/5ppp/8/8/8/8/8/6K1 b - -").unwrap();
+        assert_eq!(Playout::play_game(&checkmate, PlayoutPolicy::Random), (PlayoutOutcome::WhiteWins, 0));
+
+        let stalemate = Fen::parse("7k/5Q2/6K1/8/8/8/8/8 b - -").unwrap();
+        assert_eq!(Playout::play_game(&stalemate, PlayoutPolicy::Random), (PlayoutOutcome::Stalemate, 0));
+
+        let bare_kings = Fen::parse("8/8/4k3/8/8/3K4/8/8 w - -").unwrap();
+        assert_eq!(Playout::play_game(&bare_kings, PlayoutPolicy::Weighted), (PlayoutOutcome::DeadPosition, 0));
+    }
+}
@@ -1,5 +1,11 @@
 use core::fmt;
-use crate::{bit_move::BitMove, bitboard::Bitboard, castling_rights::CastlingRights, color::Color, move_flag::MoveFlag, move_masks, piece::PieceType, square::Square};
+use std::hash::{Hash, Hasher};
+use crate::{bit_move::BitMove, bitboard::Bitboard, castling_rights::{CastlingRights, CastlingSquares}, color::Color, fen::{Fen, FenError}, game_result::GameResult, impl_error, move_flag::MoveFlag, move_generation::MoveGeneration, move_masks, nnue::PieceChanges, piece::PieceType, rank::Rank, square::Square, zobrist};
+
+#[derive(Debug)]
+pub struct PositionError(pub &'static str);
+
+impl_error!(PositionError);
 
 #[derive(Clone)]
 pub struct Position {
@@ -13,6 +19,29 @@ pub struct Position {
     pub side: Color,
     pub en_passant_sq: Square,
     pub castling_rights: CastlingRights,
+    pub castling_squares: CastlingSquares,
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+    pub zobrist_key: u64,
+    pub piece_changes: PieceChanges,
+    // NOTE: Worked out once per position by update_checks_and_pins
+    checkers: Bitboard,
+    pinned: Bitboard,
+    #[cfg(feature = "revert_with_undo_move")]
+    undo_states: Vec<UndoState>,
+}
+
+// The state a move overwrites that can't be worked out from the move itself
+#[cfg(feature = "revert_with_undo_move")]
+#[derive(Clone, Copy)]
+struct UndoState {
+    capture: PieceType,
+    en_passant_sq: Square,
+    castling_rights: CastlingRights,
+    halfmove_clock: u16,
+    zobrist_key: u64,
+    checkers: Bitboard,
+    pinned: Bitboard,
 }
 
 impl Position {
@@ -40,7 +69,7 @@ impl Position {
     }
 
     pub fn starting_position() -> Position {
-        Position {
+        let mut position = Position {
             #[cfg(feature = "board_representation_array")]
             pps: [
                 PieceType::BR, PieceType::BN, PieceType::BB, PieceType::BQ, PieceType::BK, PieceType::BB, PieceType::BN, PieceType::BR,
@@ -73,12 +102,29 @@ impl Position {
             side: Color::White,
             en_passant_sq: Square::None,
             castling_rights: CastlingRights::DEFAULT,
-        }
+            castling_squares: CastlingSquares::STANDARD,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            zobrist_key: 0,
+            piece_changes: PieceChanges::default(),
+            checkers: Bitboard::EMPTY,
+            pinned: Bitboard::EMPTY,
+            #[cfg(feature = "revert_with_undo_move")]
+            undo_states: Vec::new(),
+        };
+        position.zobrist_key = position.compute_zobrist_key();
+        position
+    }
+
+    pub fn from_fen(fen_string: &str) -> Result<Position, FenError> {
+        Fen::parse(fen_string)
     }
 
     #[inline(always)]
     pub fn set_piece(&mut self, piece: PieceType, sq: Square) {
         self.bbs[piece].set_sq(sq);
+        self.zobrist_key ^= zobrist::piece_key(piece, sq);
+        self.piece_changes.add(piece, sq);
 
         #[cfg(feature = "board_representation_array")]
         { self.pps[sq] = piece; }
@@ -87,11 +133,32 @@ impl Position {
     #[inline(always)]
     pub fn remove_piece(&mut self, piece: PieceType, sq: Square) {
         self.bbs[piece].pop_sq(sq);
+        self.zobrist_key ^= zobrist::piece_key(piece, sq);
+        self.piece_changes.remove(piece, sq);
 
         #[cfg(feature = "board_representation_array")]
         { self.pps[sq] = PieceType::None; }
     }
 
+    pub fn compute_zobrist_key(&self) -> u64 {
+        let mut key = self.state_zobrist_key();
+        for piece in PieceType::ALL_PIECES {
+            let mut bb = self.bbs[piece];
+            while bb.is_not_empty() {
+                key ^= zobrist::piece_key(piece, bb.pop_lsb());
+            }
+        }
+        key
+    }
+
+    // The part of the key that doesn't depend on the pieces
+    #[inline(always)]
+    fn state_zobrist_key(&self) -> u64 {
+        zobrist::side_key(self.side)
+            ^ zobrist::castling_key(self.castling_rights)
+            ^ zobrist::en_passant_key(self.en_passant_sq)
+    }
+
     #[inline]
     pub fn make_move(&mut self, bit_move: BitMove) -> bool {
         #[cfg(feature = "board_representation_bitboard")]
@@ -103,8 +170,9 @@ impl Position {
         #[cfg(feature = "board_representation_array")]
         let piece = self.pps[source];
 
+        // NOTE: With non-standard castling squares the king can land on its own rook
         #[cfg(feature = "board_representation_array")]
-        let capture = self.pps[target];
+        let capture = if flag == MoveFlag::Castle { PieceType::None } else { self.pps[target] };
 
 
         debug_assert_eq!(piece.color(), self.side);
@@ -112,8 +180,46 @@ impl Position {
         debug_assert!(self.bbs[piece].is_set_sq(source));
         debug_assert!(capture == PieceType::None || self.bbs[capture].is_set_sq(target));
 
-        // Moves piece
+        // NOTE: Out of check, only king moves, pinned pieces leaving their line and en passant can expose the own king
+        let own_king_square = match self.side {
+            Color::White => self.bbs[PieceType::WK],
+            Color::Black => self.bbs[PieceType::BK],
+        }.to_sq();
+        let can_expose_king = self.checkers.is_not_empty()
+            || self.pinned.is_set_sq(source) && !move_masks::get_line_mask(own_king_square, source).is_set_sq(target)
+            || piece == PieceType::WK
+            || piece == PieceType::BK
+            || flag == MoveFlag::EnPassant;
+
+        #[cfg(feature = "revert_with_undo_move")]
+        self.undo_states.push(UndoState {
+            capture,
+            en_passant_sq: self.en_passant_sq,
+            castling_rights: self.castling_rights,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_key: self.zobrist_key,
+            checkers: self.checkers,
+            pinned: self.pinned,
+        });
+
+        self.zobrist_key ^= self.state_zobrist_key();
+        self.piece_changes.clear();
+
+        let rook = match self.side {
+            Color::White => PieceType::WR,
+            Color::Black => PieceType::BR,
+        };
+
+        // Moves piece, where a castling rook is taken off first, since the king can land on its square
         self.remove_piece(piece, source);
+        let rook_target = match flag {
+            MoveFlag::Castle => {
+                let (rook_source, rook_target) = self.castling_squares.rook_move(self.side, target);
+                self.remove_piece(rook, rook_source);
+                rook_target
+            }
+            _ => Square::None,
+        };
         self.set_piece(piece, target);
 
         // Removes captured piece
@@ -122,219 +228,584 @@ impl Position {
             self.remove_piece(capture, target);
             
             #[cfg(feature = "board_representation_array")]
-            self.bbs[capture].pop_sq(target);
+            {
+                self.bbs[capture].pop_sq(target);
+                self.zobrist_key ^= zobrist::piece_key(capture, target);
+                self.piece_changes.remove(capture, target);
+            }
         }
 
         // Resets en-passant square
         self.en_passant_sq = Square::None;
 
         match flag {
-            MoveFlag::None => (),
-            MoveFlag::WDoublePawn => self.en_passant_sq = target.below(),
-            MoveFlag::BDoublePawn => self.en_passant_sq = target.above(),
-            MoveFlag::WEnPassant => self.remove_piece(PieceType::BP, target.below()),
-            MoveFlag::BEnPassant => self.remove_piece(PieceType::WP, target.above()),
-            MoveFlag::WKCastle => {
-                self.remove_piece(PieceType::WR, Square::H1);
-                self.set_piece(PieceType::WR, Square::F1);
-            }
-            MoveFlag::WQCastle => {
-                self.remove_piece(PieceType::WR, Square::A1);
-                self.set_piece(PieceType::WR, Square::D1);
-            }
-            MoveFlag::BKCastle => {
-                self.remove_piece(PieceType::BR, Square::H8);
-                self.set_piece(PieceType::BR, Square::F8);
-            }
-            MoveFlag::BQCastle => {
-                self.remove_piece(PieceType::BR, Square::A8);
-                self.set_piece(PieceType::BR, Square::D8);
-            }
-            MoveFlag::PromoQ => {
-                self.remove_piece(piece, target);
-                self.set_piece(
-                    match self.side {
-                        Color::White => PieceType::WQ,
-                        Color::Black => PieceType::BQ,
-                    },
-                    target,
-                );
-            }
-            MoveFlag::PromoR => {
-                self.remove_piece(piece, target);
-                self.set_piece(
-                    match self.side {
-                        Color::White => PieceType::WR,
-                        Color::Black => PieceType::BR,
-                    },
-                    target,
-                );
-            }
-            MoveFlag::PromoN => {
-                self.remove_piece(piece, target);
-                self.set_piece(
-                    match self.side {
-                        Color::White => PieceType::WN,
-                        Color::Black => PieceType::BN,
-                    },
-                    target,
-                );
-            }
-            MoveFlag::PromoB => {
+            MoveFlag::None => {
+                // NOTE: A pawn moving over two ranks is always a double pawn push
+                if (piece == PieceType::WP || piece == PieceType::BP) && (source as u8).abs_diff(target as u8) == 16 {
+                    self.en_passant_sq = Square::from((source as u8 + target as u8) / 2);
+                }
+            }
+            MoveFlag::EnPassant => match self.side {
+                Color::White => self.remove_piece(PieceType::BP, target.below()),
+                Color::Black => self.remove_piece(PieceType::WP, target.above()),
+            },
+            MoveFlag::Castle => self.set_piece(rook, rook_target),
+            MoveFlag::Promotion => {
                 self.remove_piece(piece, target);
-                self.set_piece(
-                    match self.side {
-                        Color::White => PieceType::WB,
-                        Color::Black => PieceType::BB,
-                    },
-                    target,
-                );
+                self.set_piece(bit_move.promotion_field().piece(self.side), target);
             }
         };
 
-        self.castling_rights.update(source, target);
+        if capture != PieceType::None || piece == PieceType::WP || piece == PieceType::BP {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if self.side == Color::Black {
+            self.fullmove_number = self.fullmove_number.saturating_add(1);
+        }
+
+        self.castling_rights.update(&self.castling_squares, source, target);
         self.side.switch();
+        self.zobrist_key ^= self.state_zobrist_key();
         self.populate_occupancies();
 
-        if self.is_square_attacked(
-            if self.side == Color::White {
-                self.bbs[PieceType::BK].to_sq()
-            } else {
-                self.bbs[PieceType::WK].to_sq()
-            },
-            self.side.opposite(),
-            if self.side == Color::White {
-                &PieceType::WHITE_PIECES
-            } else {
-                &PieceType::BLACK_PIECES
-            },
-        ) {
-            return false;
-        }
+        let moved_king = match self.side {
+            Color::White => PieceType::BK,
+            Color::Black => PieceType::WK,
+        };
+        self.update_checks_and_pins();
+
+        #[cfg(feature = "validate_positions")]
+        self.assert_valid();
 
-        true
+        !(can_expose_king && self.is_square_attacked(self.bbs[moved_king].to_sq(), self.side.opposite()))
     }
 
+    // NOTE: The move has to be the last one made, even if it turned out to be illegal
     #[inline]
     #[cfg(feature = "revert_with_undo_move")]
-    pub fn undo_move(&mut self, bit_move: BitMove, old_castling_rights: CastlingRights) {
-        let (source, target, piece, capture, flag) = bit_move.decode();
+    pub fn undo_move(&mut self, bit_move: BitMove) {
+        let (source, target, piece, _, flag) = bit_move.decode();
+        let undo_state = self.undo_states.pop().expect("undo_move needs a move made by make_move to undo");
+        self.piece_changes.clear();
 
         // Switches side first to make it easier to conceptualize
         self.side.switch();
 
         debug_assert_eq!(piece.color(), self.side);
-        debug_assert!(capture == PieceType::None || capture.color() == self.side.opposite());
+        debug_assert!(undo_state.capture == PieceType::None || undo_state.capture.color() == self.side.opposite());
 
         self.set_piece(piece, source);
-        self.remove_piece(piece, target);
 
-        if capture != PieceType::None {
-            self.set_piece(capture, target);
+        // NOTE: After a promotion, the promoted piece is on the target square instead of the pawn
+        match bit_move.promotion() {
+            Some(promotion) => self.remove_piece(promotion.piece(self.side), target),
+            None => self.remove_piece(piece, target),
         }
 
-        self.en_passant_sq = Square::None;
+        if undo_state.capture != PieceType::None {
+            self.set_piece(undo_state.capture, target);
+        }
 
         match flag {
-            MoveFlag::None | MoveFlag::WDoublePawn | MoveFlag::BDoublePawn => (),
-            MoveFlag::WEnPassant => {
-                self.en_passant_sq = target;
-                self.set_piece(PieceType::BP, target.below())
-            }
-            MoveFlag::BEnPassant => {
-                self.en_passant_sq = target;
-                self.set_piece(PieceType::WP, target.above())
-            }
-            MoveFlag::WKCastle => {
-                self.set_piece(PieceType::WR, Square::H1);
-                self.remove_piece(PieceType::WR, Square::F1);
-            }
-            MoveFlag::WQCastle => {
-                self.set_piece(PieceType::WR, Square::A1);
-                self.remove_piece(PieceType::WR, Square::D1);
-            }
-            MoveFlag::BKCastle => {
-                self.set_piece(PieceType::BR, Square::H8);
-                self.remove_piece(PieceType::BR, Square::F8);
-            }
-            MoveFlag::BQCastle => {
-                self.set_piece(PieceType::BR, Square::A8);
-                self.remove_piece(PieceType::BR, Square::D8);
-            }
-            MoveFlag::PromoQ => {
-                self.remove_piece(
-                    match self.side {
-                        Color::White => PieceType::WQ,
-                        Color::Black => PieceType::BQ,
-                    },
-                    target,
-                );
-            }
-            MoveFlag::PromoR => {
-                self.remove_piece(
-                    match self.side {
-                        Color::White => PieceType::WR,
-                        Color::Black => PieceType::BR,
-                    },
-                    target,
-                );
-            }
-            MoveFlag::PromoN => {
-                self.remove_piece(
-                    match self.side {
-                        Color::White => PieceType::WN,
-                        Color::Black => PieceType::BN,
-                    },
-                    target,
-                );
-            }
-            MoveFlag::PromoB => {
-                self.remove_piece(
-                    match self.side {
-                        Color::White => PieceType::WB,
-                        Color::Black => PieceType::BB,
-                    },
-                    target,
-                );
+            MoveFlag::None | MoveFlag::Promotion => (),
+            MoveFlag::EnPassant => match self.side {
+                Color::White => self.set_piece(PieceType::BP, target.below()),
+                Color::Black => self.set_piece(PieceType::WP, target.above()),
+            },
+            MoveFlag::Castle => {
+                let rook = match self.side {
+                    Color::White => PieceType::WR,
+                    Color::Black => PieceType::BR,
+                };
+                let (rook_source, rook_target) = self.castling_squares.rook_move(self.side, target);
+                self.remove_piece(rook, rook_target);
+                self.set_piece(rook, rook_source);
             }
         };
 
-        self.castling_rights = old_castling_rights;
+        self.en_passant_sq = undo_state.en_passant_sq;
+        self.castling_rights = undo_state.castling_rights;
+        self.halfmove_clock = undo_state.halfmove_clock;
+        if self.side == Color::Black {
+            self.fullmove_number -= 1;
+        }
+        self.zobrist_key = undo_state.zobrist_key;
+        self.checkers = undo_state.checkers;
+        self.pinned = undo_state.pinned;
         self.populate_occupancies();
+
+        #[cfg(feature = "validate_positions")]
+        self.assert_valid();
+    }
+
+    // Cross-checks the redundant parts of the position, to catch a make or undo that corrupted it
+    // NOTE: The side that just moved may still be in check, since make_move also makes illegal moves
+    pub fn validate(&self) -> Result<(), PositionError> {
+        let mut white_pieces = Bitboard::EMPTY;
+        let mut black_pieces = Bitboard::EMPTY;
+        for piece in PieceType::ALL_PIECES {
+            if ((white_pieces | black_pieces) & self.bbs[piece]).is_not_empty() {
+                return Err(PositionError("Two pieces share a square!"));
+            }
+            match piece.color() {
+                Color::White => white_pieces |= self.bbs[piece],
+                Color::Black => black_pieces |= self.bbs[piece],
+            }
+        }
+        if self.wo != white_pieces || self.bo != black_pieces || self.ao != (white_pieces | black_pieces) {
+            return Err(PositionError("Occupancies don't match the pieces!"));
+        }
+
+        #[cfg(feature = "board_representation_array")]
+        for square in Square::ALL_SQUARES {
+            let piece = PieceType::ALL_PIECES.into_iter().find(|&piece| self.bbs[piece].is_set_sq(square));
+            if self.pps[square] != piece.unwrap_or(PieceType::None) {
+                return Err(PositionError("Piece array doesn't match the pieces!"));
+            }
+        }
+
+        if self.bbs[PieceType::WK].count_bits() != 1 || self.bbs[PieceType::BK].count_bits() != 1 {
+            return Err(PositionError("Each side needs exactly one king!"));
+        }
+
+        if ((self.bbs[PieceType::WP] | self.bbs[PieceType::BP]) & (Bitboard::RANK_1 | Bitboard::RANK_8)).is_not_empty() {
+            return Err(PositionError("Pawns can't stand on the first or last rank!"));
+        }
+
+        if self.en_passant_sq != Square::None {
+            let (expected_rank, pushed_pawn, pushed_from) = match self.side {
+                Color::White => (Rank::R6, PieceType::BP, self.en_passant_sq.above()),
+                Color::Black => (Rank::R3, PieceType::WP, self.en_passant_sq.below()),
+            };
+            let pushed_to = match self.side {
+                Color::White => self.en_passant_sq.below(),
+                Color::Black => self.en_passant_sq.above(),
+            };
+            if self.en_passant_sq.rank() != expected_rank
+                || !self.bbs[pushed_pawn].is_set_sq(pushed_to)
+                || self.ao.is_set_sq(self.en_passant_sq)
+                || self.ao.is_set_sq(pushed_from) {
+                return Err(PositionError("En-passant square doesn't follow a double pawn push!"));
+            }
+        }
+
+        if self.zobrist_key != self.compute_zobrist_key() {
+            return Err(PositionError("Zobrist key doesn't match the position!"));
+        }
+
+        if (self.checkers, self.pinned) != self.checkers_and_pinned(self.side) {
+            return Err(PositionError("Checkers and pinned pieces are out of date!"));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "validate_positions")]
+    fn assert_valid(&self) {
+        if let Err(error) = self.validate() {
+            panic!("{}", error);
+        }
+    }
+
+    // NOTE: Passing while in check would leave the king capturable, so it isn't allowed
+    #[inline]
+    pub fn make_null_move(&mut self) {
+        debug_assert!(!self.in_check());
+
+        #[cfg(feature = "revert_with_undo_move")]
+        self.undo_states.push(UndoState {
+            capture: PieceType::None,
+            en_passant_sq: self.en_passant_sq,
+            castling_rights: self.castling_rights,
+            halfmove_clock: self.halfmove_clock,
+            zobrist_key: self.zobrist_key,
+            checkers: self.checkers,
+            pinned: self.pinned,
+        });
+
+        self.zobrist_key ^= self.state_zobrist_key();
+        self.piece_changes.clear();
+        self.en_passant_sq = Square::None;
+        self.side.switch();
+        self.zobrist_key ^= self.state_zobrist_key();
+        self.update_checks_and_pins();
+
+        #[cfg(feature = "validate_positions")]
+        self.assert_valid();
+    }
+
+    #[inline]
+    #[cfg(feature = "revert_with_undo_move")]
+    pub fn undo_null_move(&mut self) {
+        let undo_state = self.undo_states.pop().expect("undo_null_move needs a null move made by make_null_move to undo");
+        self.piece_changes.clear();
+        self.side.switch();
+        self.en_passant_sq = undo_state.en_passant_sq;
+        self.zobrist_key = undo_state.zobrist_key;
+        self.checkers = undo_state.checkers;
+        self.pinned = undo_state.pinned;
+
+        #[cfg(feature = "validate_positions")]
+        self.assert_valid();
     }
 
     #[inline(always)]
-    pub fn is_square_attacked(
-        &self,
-        square: Square,
-        defending_side: Color,
-        [enemy_pawn, enemy_knight, enemy_bishop, enemy_rook, enemy_queen, enemy_king]: &[PieceType; 6]
-    ) -> bool {
-        if (move_masks::get_pawn_capture_mask(defending_side, square) & self.bbs[*enemy_pawn]).is_not_empty() {
-            return true;
+    pub fn is_square_attacked(&self, square: Square, defending_side: Color) -> bool {
+        self.is_square_attacked_through(square, defending_side, self.ao)
+    }
+
+    #[inline(always)]
+    pub fn is_square_attacked_through(&self, square: Square, defending_side: Color, occupancy: Bitboard) -> bool {
+        let enemy_occupancies = match defending_side {
+            Color::White => self.bo,
+            Color::Black => self.wo,
+        };
+        (self.attackers_to(square, occupancy) & enemy_occupancies).is_not_empty()
+    }
+
+    // NOTE: Sliders are only blocked by the given occupancy, so removing pieces reveals the sliders behind them
+    #[inline(always)]
+    pub fn attackers_to(&self, square: Square, occupancy: Bitboard) -> Bitboard {
+        let diagonal_sliders = self.bbs[PieceType::WB] | self.bbs[PieceType::BB] | self.bbs[PieceType::WQ] | self.bbs[PieceType::BQ];
+        let straight_sliders = self.bbs[PieceType::WR] | self.bbs[PieceType::BR] | self.bbs[PieceType::WQ] | self.bbs[PieceType::BQ];
+
+        move_masks::get_pawn_capture_mask(Color::White, square) & self.bbs[PieceType::BP]
+            | move_masks::get_pawn_capture_mask(Color::Black, square) & self.bbs[PieceType::WP]
+            | move_masks::get_knight_mask(square) & (self.bbs[PieceType::WN] | self.bbs[PieceType::BN])
+            | move_masks::get_king_mask(square) & (self.bbs[PieceType::WK] | self.bbs[PieceType::BK])
+            | move_masks::get_bishop_mask(square, occupancy) & diagonal_sliders
+            | move_masks::get_rook_mask(square, occupancy) & straight_sliders
+    }
+
+    #[inline(always)]
+    pub fn in_check(&self) -> bool {
+        self.checkers.is_not_empty()
+    }
+
+    #[inline(always)]
+    pub fn checkers_count(&self) -> u8 {
+        self.checkers().count_bits()
+    }
+
+    pub fn checker_squares(&self) -> Vec<Square> {
+        let mut checkers = self.checkers();
+        let mut checker_squares = Vec::new();
+        while checkers.is_not_empty() {
+            checker_squares.push(checkers.pop_lsb());
         }
-        if (move_masks::get_knight_mask(square) & self.bbs[*enemy_knight]).is_not_empty() {
-            return true;
+        checker_squares
+    }
+
+    #[inline(always)]
+    pub fn checkers(&self) -> Bitboard {
+        self.checkers
+    }
+
+    // NOTE: Only the pinned pieces of the side to move are kept, the others are worked out here
+    #[inline(always)]
+    pub fn pinned(&self, color: Color) -> Bitboard {
+        if color == self.side {
+            self.pinned
+        } else {
+            self.checkers_and_pinned(color).1
         }
-        if (move_masks::get_bishop_mask(square, self.ao) & self.bbs[*enemy_bishop]).is_not_empty() {
-            return true;
+    }
+
+    // Has to be called whenever the pieces or the side to move change outside of make_move
+    #[inline(always)]
+    pub fn update_checks_and_pins(&mut self) {
+        (self.checkers, self.pinned) = self.checkers_and_pinned(self.side);
+    }
+
+    #[inline(always)]
+    fn checkers_and_pinned(&self, color: Color) -> (Bitboard, Bitboard) {
+        let (king, own_occupancies, [enemy_pawn, enemy_knight, enemy_bishop, enemy_rook, enemy_queen, _]) = match color {
+            Color::White => (PieceType::WK, self.wo, PieceType::BLACK_PIECES),
+            Color::Black => (PieceType::BK, self.bo, PieceType::WHITE_PIECES),
+        };
+        let king_square = self.bbs[king].to_sq();
+        let mut checkers = move_masks::get_pawn_capture_mask(color, king_square) & self.bbs[enemy_pawn]
+            | move_masks::get_knight_mask(king_square) & self.bbs[enemy_knight];
+        let mut pinned = Bitboard::EMPTY;
+
+        let mut snipers = move_masks::get_check_candidate_mask(enemy_bishop, king_square) & (self.bbs[enemy_bishop] | self.bbs[enemy_queen])
+            | move_masks::get_check_candidate_mask(enemy_rook, king_square) & (self.bbs[enemy_rook] | self.bbs[enemy_queen]);
+        while snipers.is_not_empty() {
+            let sniper_square = snipers.pop_lsb();
+            let blockers = move_masks::get_between_mask(sniper_square, king_square) & self.ao;
+            if blockers.is_empty() {
+                checkers.set_sq(sniper_square);
+            } else if blockers.count_bits() == 1 {
+                pinned |= blockers & own_occupancies;
+            }
         }
-        if (move_masks::get_rook_mask(square, self.ao) & self.bbs[*enemy_rook]).is_not_empty() {
-            return true;
+        (checkers, pinned)
+    }
+
+    // NOTE: Like a generated move, the move can still leave the own king in check
+    pub fn is_pseudo_legal(&self, bit_move: BitMove) -> bool {
+        let (source, target, flag) = (bit_move.source(), bit_move.target(), bit_move.flag());
+        let (own_occupancies, enemy_occupancies, pawn, king) = match self.side {
+            Color::White => (self.wo, self.bo, PieceType::WP, PieceType::WK),
+            Color::Black => (self.bo, self.wo, PieceType::BP, PieceType::BK),
+        };
+        if bit_move == BitMove::EMPTY || !own_occupancies.is_set_sq(source) || own_occupancies.is_set_sq(target) {
+            return false;
+        }
+        let piece = self.get_piece(source);
+
+        if piece != king && self.checkers_count() > 1 {
+            return false;
+        }
+
+        #[cfg(feature = "board_representation_bitboard")]
+        {
+            let capture = if flag == MoveFlag::EnPassant { PieceType::None } else { self.get_piece(target) };
+            if bit_move.piece() != piece || bit_move.capture() != capture {
+                return false;
+            }
+        }
+
+        if piece != pawn {
+            let attacks = match piece {
+                PieceType::WN | PieceType::BN => move_masks::get_knight_mask(source),
+                PieceType::WB | PieceType::BB => move_masks::get_bishop_mask(source, self.ao),
+                PieceType::WR | PieceType::BR => move_masks::get_rook_mask(source, self.ao),
+                PieceType::WQ | PieceType::BQ => move_masks::get_queen_mask(source, self.ao),
+                _ => move_masks::get_king_mask(source),
+            };
+            // NOTE: Castling is rare enough to be looked up among the generated moves
+            return match flag {
+                MoveFlag::None => attacks.is_set_sq(target),
+                MoveFlag::Castle => piece == king && MoveGeneration::generate_pseudo_legal_moves(self).iter().any(|&m| m == bit_move),
+                MoveFlag::EnPassant | MoveFlag::Promotion => false,
+            };
         }
-        if (move_masks::get_queen_mask(square, self.ao) & self.bbs[*enemy_queen]).is_not_empty() {
+
+        let is_push = move_masks::get_pawn_quiet_mask(self.side, source).is_set_sq(target)
+            && ((move_masks::get_between_mask(source, target) | target.to_bb()) & self.ao).is_empty();
+        let is_capture = move_masks::get_pawn_capture_mask(self.side, source).is_set_sq(target);
+        let reaches_last_rank = matches!(target.rank(), Rank::R1 | Rank::R8);
+        match flag {
+            MoveFlag::None => !reaches_last_rank && (is_push || is_capture && enemy_occupancies.is_set_sq(target)),
+            MoveFlag::Promotion => reaches_last_rank && (is_push || is_capture && enemy_occupancies.is_set_sq(target)),
+            MoveFlag::EnPassant => is_capture && target == self.en_passant_sq,
+            MoveFlag::Castle => false,
+        }
+    }
+
+    // Whether the move checks the enemy king, found without making it
+    // NOTE: The move has to be pseudo-legal
+    #[inline]
+    pub fn gives_check(&self, bit_move: BitMove) -> bool {
+        let (source, target, flag) = (bit_move.source(), bit_move.target(), bit_move.flag());
+        let (enemy_king, [_, _, bishop, rook, queen, _]) = match self.side {
+            Color::White => (PieceType::BK, PieceType::WHITE_PIECES),
+            Color::Black => (PieceType::WK, PieceType::BLACK_PIECES),
+        };
+        let king_square = self.bbs[enemy_king].to_sq();
+        let mut occupancy = self.ao;
+        occupancy.pop_sq(source);
+        occupancy.set_sq(target);
+
+        let mut piece = self.get_piece(source);
+        let mut moved_sliders = source.to_bb();
+        match flag {
+            MoveFlag::None => (),
+            MoveFlag::EnPassant => match self.side {
+                Color::White => occupancy.pop_sq(target.below()),
+                Color::Black => occupancy.pop_sq(target.above()),
+            },
+            MoveFlag::Castle => {
+                let (rook_source, rook_target) = self.castling_squares.rook_move(self.side, target);
+                // NOTE: With non-standard castling squares the king can end up where the rook was
+                occupancy.pop_sq(rook_source);
+                occupancy.set_sq(target);
+                occupancy.set_sq(rook_target);
+                moved_sliders.set_sq(rook_source);
+                if move_masks::get_rook_mask(rook_target, occupancy).is_set_sq(king_square) {
+                    return true;
+                }
+            }
+            MoveFlag::Promotion => piece = bit_move.promotion_field().piece(self.side),
+        }
+
+        let target_attacks = match piece {
+            PieceType::WP | PieceType::BP => move_masks::get_pawn_capture_mask(self.side, target),
+            PieceType::WN | PieceType::BN => move_masks::get_knight_mask(target),
+            PieceType::WB | PieceType::BB => move_masks::get_bishop_mask(target, occupancy),
+            PieceType::WR | PieceType::BR => move_masks::get_rook_mask(target, occupancy),
+            PieceType::WQ | PieceType::BQ => move_masks::get_queen_mask(target, occupancy),
+            _ => Bitboard::EMPTY,
+        };
+        if target_attacks.is_set_sq(king_square) {
             return true;
         }
-        if (move_masks::get_king_mask(square) & self.bbs[*enemy_king]).is_not_empty() {
+
+        let diagonal_sliders = (self.bbs[bishop] | self.bbs[queen]) & !moved_sliders;
+        let straight_sliders = (self.bbs[rook] | self.bbs[queen]) & !moved_sliders;
+        (move_masks::get_bishop_mask(king_square, occupancy) & diagonal_sliders
+            | move_masks::get_rook_mask(king_square, occupancy) & straight_sliders).is_not_empty()
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check() && MoveGeneration::generate_legal_moves(self).len() == 0
+    }
+
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check() && MoveGeneration::generate_legal_moves(self).len() == 0
+    }
+
+    // The history holds the keys of the positions that led here, oldest first
+    pub fn game_result(&self, history: &[u64]) -> Option<GameResult> {
+        if MoveGeneration::generate_legal_moves(self).len() == 0 {
+            return Some(if self.in_check() { GameResult::Checkmate(self.side.opposite()) } else { GameResult::Stalemate });
+        }
+
+        if self.is_threefold_repetition(history) {
+            Some(GameResult::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(GameResult::FiftyMoveRule)
+        } else if self.is_insufficient_material() {
+            Some(GameResult::InsufficientMaterial)
+        } else if self.is_dead_position() {
+            Some(GameResult::DeadPosition)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_threefold_repetition(&self, history: &[u64]) -> bool {
+        let repetitions = history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|&&key| key == self.zobrist_key)
+            .count();
+        repetitions >= 2
+    }
+
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100 && !self.is_checkmate()
+    }
+
+    // NOTE: Counts pseudo-legal destination squares from the attack masks, the piece type only selects the kind of piece
+    #[inline]
+    pub fn mobility_count(&self, piece_type: PieceType, color: Color) -> u32 {
+        let (piece, inv_own_occupancies, enemy_occupancies) = match color {
+            Color::White => (PieceType::WHITE_PIECES[piece_type as usize % 6], !self.wo, self.bo),
+            Color::Black => (PieceType::BLACK_PIECES[piece_type as usize % 6], !self.bo, self.wo),
+        };
+
+        let mut count = 0;
+        let mut piece_bb = self.bbs[piece];
+        while piece_bb.is_not_empty() {
+            let source = piece_bb.pop_lsb();
+            let mobility_mask = match piece {
+                PieceType::WP | PieceType::BP => {
+                    let single_push_sq = match color {
+                        Color::White => source.above(),
+                        Color::Black => source.below(),
+                    };
+                    let quiet_mask = if self.ao.is_set_sq(single_push_sq) {
+                        Bitboard::EMPTY
+                    } else {
+                        move_masks::get_pawn_quiet_mask(color, source) & !self.ao
+                    };
+                    quiet_mask | (move_masks::get_pawn_capture_mask(color, source) & enemy_occupancies)
+                }
+                PieceType::WN | PieceType::BN => move_masks::get_knight_mask(source) & inv_own_occupancies,
+                PieceType::WB | PieceType::BB => move_masks::get_bishop_mask(source, self.ao) & inv_own_occupancies,
+                PieceType::WR | PieceType::BR => move_masks::get_rook_mask(source, self.ao) & inv_own_occupancies,
+                PieceType::WQ | PieceType::BQ => move_masks::get_queen_mask(source, self.ao) & inv_own_occupancies,
+                _ => move_masks::get_king_mask(source) & inv_own_occupancies,
+            };
+            count += mobility_mask.count_bits() as u32;
+        }
+
+        count
+    }
+
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy_pieces = self.bbs[PieceType::WR] | self.bbs[PieceType::BR] | self.bbs[PieceType::WQ] | self.bbs[PieceType::BQ];
+        let pawns = self.bbs[PieceType::WP] | self.bbs[PieceType::BP];
+        if (heavy_pieces | pawns).is_not_empty() {
+            return false;
+        }
+
+        let knights = self.bbs[PieceType::WN] | self.bbs[PieceType::BN];
+        let bishops = self.bbs[PieceType::WB] | self.bbs[PieceType::BB];
+
+        (knights | bishops).count_bits() <= 1
+            || knights.is_empty()
+                && ((bishops & Bitboard::WHITE_SQUARES).is_empty() || (bishops & Bitboard::BLACK_SQUARES).is_empty())
+    }
+
+    // NOTE: Only recognizes the common dead positions, cheaply enough for every node of the search
+    pub fn is_dead_position(&self) -> bool {
+        if self.is_insufficient_material() {
             return true;
         }
-        false
+
+        let pieces = self.ao
+            & !(self.bbs[PieceType::WK] | self.bbs[PieceType::BK] | self.bbs[PieceType::WP] | self.bbs[PieceType::BP]);
+
+        pieces.is_empty() && self.is_pawn_wall_impassable()
     }
 
-    pub fn in_check(&self) -> bool {
-        match self.side {
-            Color::White => self.is_square_attacked(self.bbs[PieceType::WK].to_sq(), Color::White, &PieceType::BLACK_PIECES),
-            Color::Black => self.is_square_attacked(self.bbs[PieceType::BK].to_sq(), Color::Black, &PieceType::WHITE_PIECES),
+    // Only kings and blocked pawns that can't capture, with neither king able to reach an enemy pawn
+    fn is_pawn_wall_impassable(&self) -> bool {
+        let white_pawns = self.bbs[PieceType::WP];
+        let black_pawns = self.bbs[PieceType::BP];
+        let pawns = white_pawns | black_pawns;
+
+        if self.en_passant_sq != Square::None
+            || (white_pawns.shift_upwards(8) & !pawns).is_not_empty()
+            || (black_pawns.shift_downwards(8) & !pawns).is_not_empty() {
+            return false;
+        }
+
+        let white_pawn_attacks = Self::pawn_attacks(white_pawns, Color::White);
+        let black_pawn_attacks = Self::pawn_attacks(black_pawns, Color::Black);
+        if (white_pawn_attacks & black_pawns).is_not_empty() || (black_pawn_attacks & white_pawns).is_not_empty() {
+            return false;
+        }
+
+        let white_king_region = Self::king_region(self.bbs[PieceType::WK], pawns | black_pawn_attacks);
+        let black_king_region = Self::king_region(self.bbs[PieceType::BK], pawns | white_pawn_attacks);
+
+        (Self::king_attacks(white_king_region) & black_pawns).is_empty()
+            && (Self::king_attacks(black_king_region) & white_pawns).is_empty()
+    }
+
+    fn pawn_attacks(mut pawns: Bitboard, color: Color) -> Bitboard {
+        let mut attacks = Bitboard::EMPTY;
+        while pawns.is_not_empty() {
+            attacks |= move_masks::get_pawn_capture_mask(color, pawns.pop_lsb());
+        }
+        attacks
+    }
+
+    fn king_attacks(mut squares: Bitboard) -> Bitboard {
+        let mut attacks = Bitboard::EMPTY;
+        while squares.is_not_empty() {
+            attacks |= move_masks::get_king_mask(squares.pop_lsb());
+        }
+        attacks
+    }
+
+    fn king_region(king: Bitboard, blocked: Bitboard) -> Bitboard {
+        let mut region = king;
+        loop {
+            let expanded = region | (Self::king_attacks(region) & !blocked);
+            if expanded == region {
+                return region;
+            }
+            region = expanded;
         }
     }
 
@@ -355,7 +826,9 @@ impl Position {
         self.pps[square]
     }
 
+    // NOTE: If the occupancy bitboards are out of sync, no piece is found, which is caught in debug builds
     #[inline(always)]
+    #[cfg(feature = "board_representation_bitboard")]
     pub fn get_target_piece(&self, enemy_piece_types: [PieceType; 6], target: Square) -> PieceType {
         for piece_type in enemy_piece_types {
             if self.bbs[piece_type].is_set_sq(target) {
@@ -363,7 +836,15 @@ impl Position {
             }
         }
 
-        panic!("There seems to be something wrong with the occupancy bitboards!")
+        debug_assert!(false, "There seems to be something wrong with the occupancy bitboards!");
+        PieceType::None
+    }
+
+    #[inline(always)]
+    #[cfg(feature = "board_representation_array")]
+    pub fn get_target_piece(&self, enemy_piece_types: [PieceType; 6], target: Square) -> PieceType {
+        debug_assert!(enemy_piece_types.contains(&self.pps[target]));
+        self.pps[target]
     }
 
     #[inline(always)]
@@ -375,7 +856,70 @@ impl Position {
         self.get_target_piece(enemy_piece_types, target)
     }
 
-    fn to_fen_string(&self) -> String {
+    // Only disambiguates when another piece of the same kind can move to the same square
+    pub fn move_to_san(&self, bit_move: BitMove) -> String {
+        let (source, target, flag) = (bit_move.source(), bit_move.target(), bit_move.flag());
+        let piece = self.get_piece(source);
+
+        let mut san = match flag {
+            MoveFlag::Castle if target == self.castling_squares.king_side_king_target(self.side) => "O-O".to_owned(),
+            MoveFlag::Castle => "O-O-O".to_owned(),
+            _ => {
+                let is_capture = self.get_piece(target) != PieceType::None || flag == MoveFlag::EnPassant;
+                let mut san = String::new();
+
+                if piece == PieceType::WP || piece == PieceType::BP {
+                    if is_capture {
+                        san.push_str(&source.file().to_string());
+                    }
+                } else {
+                    san.push(char::from(piece).to_ascii_uppercase());
+
+                    let ambiguous_sources: Vec<Square> = MoveGeneration::generate_legal_moves(self)
+                        .iter()
+                        .filter(|m| m.target() == target && m.source() != source && self.get_piece(m.source()) == piece)
+                        .map(|m| m.source())
+                        .collect();
+
+                    if !ambiguous_sources.is_empty() {
+                        if ambiguous_sources.iter().all(|sq| sq.file() != source.file()) {
+                            san.push_str(&source.file().to_string());
+                        } else if ambiguous_sources.iter().all(|sq| sq.rank() != source.rank()) {
+                            san.push_str(&source.rank().to_string());
+                        } else {
+                            san.push_str(&source.to_string());
+                        }
+                    }
+                }
+
+                if is_capture {
+                    san.push('x');
+                }
+                san.push_str(&target.to_string());
+
+                if let Some(promotion) = bit_move.promotion() {
+                    san.push('=');
+                    san.push(promotion.to_char().to_ascii_uppercase());
+                }
+                san
+            }
+        };
+
+        if self.gives_check(bit_move) {
+            let mut position_copy = self.clone();
+            position_copy.make_move(bit_move);
+            san.push(if position_copy.is_checkmate() { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    pub fn to_fen(&self) -> String {
+        format!("{} {} {}", self.to_fen_without_clocks(), self.halfmove_clock, self.fullmove_number)
+    }
+
+    // NOTE: Leaves out the clocks, so positions that only differ in move counters are equal
+    pub fn to_fen_without_clocks(&self) -> String {
         let mut fen_str = String::new();
         let mut curr_width = 0;
         let mut curr_empty = 0;
@@ -432,6 +976,50 @@ impl Position {
         );
         fen_str
     }
+
+    // The colors swapped and the board turned upside down
+    pub fn mirror(&self) -> Position {
+        self.transformed(|square| Square::from(square as u8 ^ 56), true)
+    }
+
+    pub fn flip(&self) -> Position {
+        self.transformed(|square| Square::from(square as u8 ^ 7), false)
+    }
+
+    fn transformed(&self, transform_square: fn(Square) -> Square, swap_colors: bool) -> Position {
+        let mut position = Position::default();
+        for piece in PieceType::ALL_PIECES {
+            let transformed_piece = if swap_colors { PieceType::from((piece as u8 + 6) % 12) } else { piece };
+            let mut bb = self.bbs[piece];
+            while bb.is_not_empty() {
+                position.set_piece(transformed_piece, transform_square(bb.pop_lsb()));
+            }
+        }
+
+        position.side = if swap_colors { self.side.opposite() } else { self.side };
+        if self.en_passant_sq != Square::None {
+            position.en_passant_sq = transform_square(self.en_passant_sq);
+        }
+
+        // NOTE: Castling always ends on the c- and g-files, so reflecting the files drops the rights
+        if swap_colors {
+            let rights = self.castling_rights.0;
+            position.castling_rights = CastlingRights((rights & 0b0011) << 2 | rights >> 2);
+            let swap_sides = |squares: [Square; 2]| [transform_square(squares[1]), transform_square(squares[0])];
+            position.castling_squares = CastlingSquares::new(
+                swap_sides(self.castling_squares.king),
+                swap_sides(self.castling_squares.king_side_rook),
+                swap_sides(self.castling_squares.queen_side_rook),
+            );
+        }
+        position.halfmove_clock = self.halfmove_clock;
+        position.fullmove_number = self.fullmove_number;
+        position.populate_occupancies();
+        position.zobrist_key = position.compute_zobrist_key();
+        position.piece_changes.clear();
+        position.update_checks_and_pins();
+        position
+    }
 }
 
 impl Default for Position {
@@ -447,26 +1035,51 @@ impl Default for Position {
             side: Color::White,
             en_passant_sq: Square::None,
             castling_rights: CastlingRights::NONE,
+            castling_squares: CastlingSquares::STANDARD,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            zobrist_key: 0,
+            piece_changes: PieceChanges::default(),
+            checkers: Bitboard::EMPTY,
+            pinned: Bitboard::EMPTY,
+            #[cfg(feature = "revert_with_undo_move")]
+            undo_states: Vec::new(),
         }
     }
 }
 
+// NOTE: Occupancies, checkers and pins follow from the rest, while undo states and piece changes are history
+impl PartialEq for Position {
+    fn eq(&self, other: &Position) -> bool {
+        self.bbs == other.bbs
+            && self.side == other.side
+            && self.en_passant_sq == other.en_passant_sq
+            && self.castling_rights == other.castling_rights
+            && self.castling_squares == other.castling_squares
+            && self.halfmove_clock == other.halfmove_clock
+            && self.fullmove_number == other.fullmove_number
+            && self.zobrist_key == other.zobrist_key
+    }
+}
+
+impl Eq for Position { }
+
+// NOTE: Equal positions have equal keys, so the key alone is enough to hash them
+impl Hash for Position {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.zobrist_key.hash(state);
+    }
+}
+
 impl fmt::Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut s = String::from("\n");
         for rank in 0..8_u8 {
             s += &format!("  {}  ", 8 - rank);
             for file in 0..8_u8 {
-                let mut is_occupied = false;
-                let sq = Square::from(rank * 8 + file);
-                for piece_type in PieceType::ALL_PIECES {
-                    if Bitboard::is_set_sq(&self.bbs[piece_type], sq) {
-                        s += &format!("{} ", piece_type);
-                        is_occupied = true;
-                    }
-                }
-                if !is_occupied {
-                    s += ". ";
+                match self.get_piece(Square::from(rank * 8 + file)) {
+                    PieceType::None => s += ". ",
+                    piece_type => s += &format!("{} ", piece_type),
                 }
             }
             s += "\n";
@@ -478,12 +1091,473 @@ impl fmt::Display for Position {
   FEN:        {}
   Side        {}
   En-passant: {}
-  Castling:   {}\n",
-            self.to_fen_string(),
+  Castling:   {}
+  Key:        {:016x}\n",
+            self.to_fen(),
             self.side,
             self.en_passant_sq,
-            self.castling_rights
+            self.castling_rights,
+            self.zobrist_key
         );
         f.pad(&s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{bb, fen::Fen, move_masks};
+
+    use super::*;
+
+    #[test]
+    fn mobility_count_matches_starting_position() {
+        move_masks::init();
+        let position = Position::starting_position();
+
+        assert_eq!(position.mobility_count(PieceType::WP, Color::White), 16);
+        assert_eq!(position.mobility_count(PieceType::WN, Color::White), 4);
+        assert_eq!(position.mobility_count(PieceType::BN, Color::Black), 4);
+        assert_eq!(position.mobility_count(PieceType::WB, Color::Black), 0);
+        assert_eq!(position.mobility_count(PieceType::WQ, Color::White), 0);
+    }
+
+    #[test]
+    fn mobility_count_respects_blockers() {
+        move_masks::init();
+        let position = Fen::parse("4k3/8/8/8/8/4p3/4P3/R3K3 w - -").unwrap();
+
+        assert_eq!(position.mobility_count(PieceType::WP, Color::White), 0);
+        assert_eq!(position.mobility_count(PieceType::WR, Color::White), 10);
+        assert_eq!(position.mobility_count(PieceType::WK, Color::White), 4);
+    }
+
+    #[test]
+    fn castling_follows_non_standard_castling_squares() {
+        move_masks::init();
+        let mut position = Fen::parse("4k3/8/8/8/8/8/8/1R3KR1 w KQ - 0 1").unwrap();
+        position.castling_squares = CastlingSquares::new([Square::F1, Square::E8], [Square::G1, Square::H8], [Square::B1, Square::A8]);
+        let moves = MoveGeneration::generate_legal_moves(&position);
+
+        // NOTE: Castling king side, the king lands on the square its rook castles from
+        for (king_target, rook_target) in [(Square::G1, Square::F1), (Square::C1, Square::D1)] {
+            let castle = *moves.iter().find(|bit_move| bit_move.flag() == MoveFlag::Castle && bit_move.target() == king_target).unwrap();
+            let mut castled = position.clone();
+            assert!(castled.make_move(castle));
+            assert!(castled.get_piece(king_target) == PieceType::WK && castled.get_piece(rook_target) == PieceType::WR);
+            assert_eq!(castled.bbs[PieceType::WR].count_bits(), 2);
+            assert_eq!(castled.castling_rights.0, CastlingRights::NONE.0);
+            assert_eq!(castled.zobrist_key, castled.compute_zobrist_key());
+
+            #[cfg(feature = "revert_with_undo_move")]
+            {
+                castled.undo_move(castle);
+                assert!(castled == position);
+            }
+        }
+
+        let rook_move = *moves.iter().find(|bit_move| bit_move.source() == Square::G1 && bit_move.target() == Square::G2).unwrap();
+        let mut moved = position.clone();
+        moved.make_move(rook_move);
+        assert_eq!(moved.castling_rights.0, CastlingRights::WQ.0);
+    }
+
+    #[test]
+    fn game_result_recognizes_the_endings() {
+        move_masks::init();
+        let mated = Fen::parse("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert!(mated.is_checkmate() && !mated.is_stalemate());
+        assert_eq!(mated.game_result(&[]), Some(GameResult::Checkmate(Color::White)));
+
+        let stalemated = Fen::parse("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(stalemated.is_stalemate() && !stalemated.is_checkmate());
+        assert_eq!(stalemated.game_result(&[]), Some(GameResult::Stalemate));
+
+        let results = [
+            ("6k1/5ppp/8/8/8/8/8/R5K1 b - - 100 80", Some(GameResult::FiftyMoveRule)),
+            ("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80", Some(GameResult::Checkmate(Color::White))),
+            ("8/8/4k3/8/8/3KB3/8/8 w - - 0 1", Some(GameResult::InsufficientMaterial)),
+            ("8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/8 w - - 0 1", Some(GameResult::DeadPosition)),
+            (Fen::STARTING_POSITION, None),
+        ];
+        for (fen, result) in results {
+            assert_eq!(Fen::parse(fen).unwrap().game_result(&[]), result, "{fen}");
+        }
+
+        let mut position = Position::starting_position();
+        let mut history = Vec::new();
+        for uci_move in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"] {
+            assert_eq!(position.game_result(&history), None, "before {uci_move}");
+            history.push(position.zobrist_key);
+            let bit_move = *MoveGeneration::generate_legal_moves(&position).iter().find(|bit_move| bit_move.to_uci_string() == uci_move).unwrap();
+            position.make_move(bit_move);
+        }
+        assert_eq!(position.game_result(&history), Some(GameResult::ThreefoldRepetition));
+    }
+
+    #[test]
+    fn is_insufficient_material_recognizes_the_drawn_endings() {
+        move_masks::init();
+        let insufficient = [
+            "8/8/4k3/8/8/3K4/8/8 w - -",
+            "8/8/4k3/8/8/3KB3/8/8 w - -",
+            "8/8/4k3/8/8/3K4/8/5n2 b - -",
+            "8/3b4/4k3/8/8/3K4/4B3/8 w - -",
+            "8/8/4k3/8/2B5/3K4/4B3/8 w - -",
+        ];
+        let sufficient = [
+            "8/8/4k3/8/8/3KNN2/8/8 w - -",
+            "8/2b5/4k3/8/8/3K4/4B3/8 w - -",
+            "8/8/4k3/8/8/3KB3/8/5n2 w - -",
+            "8/8/4k3/8/8/3K4/4P3/8 w - -",
+            "8/8/4k3/8/8/3K4/8/7R w - -",
+            "8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/8 w - -",
+        ];
+
+        for fen in insufficient {
+            assert!(Fen::parse(fen).unwrap().is_insufficient_material(), "{fen}");
+        }
+        for fen in sufficient {
+            assert!(!Fen::parse(fen).unwrap().is_insufficient_material(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn is_dead_position_recognizes_common_cases() {
+        move_masks::init();
+        let dead = [
+            "8/8/4k3/8/8/3K4/8/8 w - -",
+            "8/8/4k3/8/8/3KN3/8/8 w - -",
+            "8/3b4/4k3/8/8/3K4/4B3/8 w - -",
+            "8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/8 w - -",
+        ];
+        let alive = [
+            "8/8/4k3/8/8/3KNN2/8/8 w - -",
+            "8/2b5/4k3/8/8/3K4/4B3/8 w - -",
+            "8/8/1k6/p1p1p1p1/P1P1P1P1/8/3K4/7R w - -",
+            "8/8/1k6/p1p1p1p1/P1P1P3/8/3K4/8 w - -",
+            "8/8/1k6/p1p3p1/P1P1P1P1/8/3K4/8 w - -",
+        ];
+
+        for fen in dead {
+            assert!(Fen::parse(fen).unwrap().is_dead_position(), "{fen}");
+        }
+        for fen in alive {
+            assert!(!Fen::parse(fen).unwrap().is_dead_position(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn checkers_finds_every_checking_piece() {
+        move_masks::init();
+
+        assert!(Position::starting_position().checkers().is_empty());
+        let double_check = Fen::parse("4k3/8/5N2/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert!(double_check.checkers() == bb!(F6, E1));
+        let pawn_check = Fen::parse("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1").unwrap();
+        assert!(pawn_check.checkers() == bb!(D2));
+
+        assert_eq!(Position::starting_position().checkers_count(), 0);
+        assert_eq!(pawn_check.checkers_count(), 1);
+        assert_eq!(double_check.checkers_count(), 2);
+        assert_eq!(double_check.checker_squares(), [Square::F6, Square::E1]);
+    }
+
+    #[test]
+    fn pinned_finds_pieces_between_the_king_and_a_slider() {
+        move_masks::init();
+
+        // The queen on e3 and the rook on e7 pin each other, and the bishop on g3 shields the pawn on f2
+        let position = Fen::parse("4k3/4r3/8/8/1b5q/4Q1B1/3N1P2/4K3 w - - 0 1").unwrap();
+        assert!(position.pinned(Color::White) == bb!(D2, E3));
+        assert!(position.pinned(Color::Black) == bb!(E7));
+        assert!(Position::starting_position().pinned(Color::White).is_empty());
+
+        for fen in [Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, Fen::TRICKY_POSITION_2] {
+            let position = Fen::parse(fen).unwrap();
+            for &bit_move in MoveGeneration::generate_pseudo_legal_moves(&position).iter() {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                let king = match position_copy.side {
+                    Color::White => PieceType::WK,
+                    Color::Black => PieceType::BK,
+                };
+                let is_attacked = position_copy.is_square_attacked(position_copy.bbs[king].to_sq(), position_copy.side);
+                assert_eq!(position_copy.in_check(), is_attacked, "{fen} {}", bit_move.to_uci_string());
+            }
+        }
+    }
+
+    #[test]
+    fn is_pseudo_legal_accepts_exactly_the_generated_moves() {
+        move_masks::init();
+
+        let fens = [
+            Fen::STARTING_POSITION,
+            Fen::KIWIPETE_POSITION,
+            Fen::TRICKY_POSITION,
+            Fen::TRICKY_POSITION_2,
+            Fen::ROOK_POSITION,
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            "4k3/8/5N2/8/8/8/3P4/4R1K1 b - - 0 1",
+        ];
+        let positions: Vec<Position> = fens.iter().map(|fen| Fen::parse(fen).unwrap()).collect();
+        let all_moves: Vec<BitMove> = positions
+            .iter()
+            .flat_map(|position| MoveGeneration::generate_pseudo_legal_moves(position).iter().copied().collect::<Vec<_>>())
+            .collect();
+
+        for (fen, position) in fens.iter().zip(&positions) {
+            let generated = MoveGeneration::generate_pseudo_legal_moves(position);
+            for &bit_move in all_moves.iter().chain([BitMove::EMPTY].iter()) {
+                assert_eq!(position.is_pseudo_legal(bit_move), generated.iter().any(|&m| m == bit_move), "{fen} {}", bit_move.to_uci_string());
+            }
+        }
+    }
+
+    #[test]
+    fn gives_check_matches_making_the_move() {
+        move_masks::init();
+
+        for fen in [
+            Fen::KIWIPETE_POSITION,
+            Fen::TRICKY_POSITION,
+            Fen::TRICKY_POSITION_2,
+            // Discovered checks by en passant, along the rank and the diagonal
+            "8/8/8/k2pP2Q/8/8/8/7K w - d6 0 1",
+            "6B1/8/8/3pP3/8/1k6/8/7K w - d6 0 1",
+            // Checks by the castling rook and by promotions, also with a capture
+            "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1",
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "1n2k3/P1P5/8/8/8/8/8/4K3 w - - 0 1",
+            // Discovered checks by knight and king moves
+            "4k3/8/8/8/4N3/8/8/4RK2 w - - 0 1",
+            "7k/8/8/8/3K4/8/1B6/8 w - - 0 1",
+        ] {
+            let position = Fen::parse(fen).unwrap();
+            for &bit_move in MoveGeneration::generate_pseudo_legal_moves(&position).iter() {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                assert_eq!(position.gives_check(bit_move), position_copy.in_check(), "{fen} {}", bit_move.to_uci_string());
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "board_representation_array")]
+    fn piece_array_follows_the_bitboards() {
+        move_masks::init();
+
+        for fen in [Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, Fen::TRICKY_POSITION_2, "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"] {
+            let position = Fen::parse(fen).unwrap();
+            for &bit_move in MoveGeneration::generate_pseudo_legal_moves(&position).iter() {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                for square in Square::ALL_SQUARES {
+                    let piece = PieceType::ALL_PIECES.into_iter().find(|&piece| position_copy.bbs[piece].is_set_sq(square));
+                    assert_eq!(position_copy.pps[square], piece.unwrap_or(PieceType::None), "{fen} {}", bit_move.to_uci_string());
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "revert_with_undo_move")]
+    fn undo_move_restores_the_position() {
+        move_masks::init();
+
+        for fen in [Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, Fen::TRICKY_POSITION_2, "4k3/8/8/3pP3/8/8/8/4K3 w - d6 3 40"] {
+            let mut position = Fen::parse(fen).unwrap();
+            let state = |position: &Position| (position.to_fen(), position.zobrist_key, position.checkers(), position.pinned(position.side));
+            let before = state(&position);
+            for &bit_move in MoveGeneration::generate_pseudo_legal_moves(&position.clone()).iter() {
+                // NOTE: Replies to an illegal move can capture the king, so only legal moves are answered
+                if position.make_move(bit_move) {
+                    let after = state(&position);
+                    for &reply in MoveGeneration::generate_pseudo_legal_moves(&position.clone()).iter() {
+                        position.make_move(reply);
+                        position.undo_move(reply);
+                        assert!(state(&position) == after, "{fen} {} {}", bit_move.to_uci_string(), reply.to_uci_string());
+                    }
+                }
+                position.undo_move(bit_move);
+                assert!(state(&position) == before, "{fen} {}", bit_move.to_uci_string());
+            }
+        }
+    }
+
+    #[test]
+    fn validate_accepts_played_positions_and_finds_corruption() {
+        move_masks::init();
+
+        for fen in [Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, Fen::TRICKY_POSITION_2, "4k3/8/8/3pP3/8/8/8/4K3 w - d6 3 40"] {
+            let position = Fen::parse(fen).unwrap();
+            assert!(position.validate().is_ok(), "{fen}");
+            for &bit_move in MoveGeneration::generate_legal_moves(&position).iter() {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                assert!(position_copy.validate().is_ok(), "{fen} {}", bit_move.to_uci_string());
+            }
+        }
+
+        let invalid = [
+            "4k3/8/8/8/8/8/8/4K2P w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - d6 0 1",
+            "4k3/3p4/8/3p4/8/8/8/4K3 w - d6 0 1",
+        ];
+        for fen in invalid {
+            assert!(Fen::parse(fen).unwrap().validate().is_err(), "{fen}");
+        }
+
+        let mut position = Position::starting_position();
+        position.bbs[PieceType::WN].set_sq(Square::E4);
+        assert!(position.validate().is_err());
+        position.populate_occupancies();
+        assert!(position.validate().is_err());
+    }
+
+    #[test]
+    fn move_to_san_disambiguates_only_when_needed() {
+        move_masks::init();
+
+        for (fen, uci_move, san) in [
+            ("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "b1d2", "Nbd2"),
+            ("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1", "f3d2", "Nfd2"),
+            ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3", "R1a3"),
+            ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a5a3", "R5a3"),
+            ("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2", "Qa1b2"),
+            ("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a3b2", "Q3b2"),
+            ("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "c1b2", "Qcb2"),
+            // The knight on e2 is pinned, so it can't go to c3 as well
+            ("4r1k1/8/8/8/8/8/4N3/1N2K3 w - - 0 1", "b1c3", "Nc3"),
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", "exd6"),
+            ("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q", "a8=Q+"),
+            ("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8n", "a8=N"),
+            ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8r", "axb8=R+"),
+            ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", "Ra8#"),
+            ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1", "O-O"),
+            ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1c1", "O-O-O"),
+            ("r3k3/8/8/8/8/8/8/4K3 b q - 0 1", "e8c8", "O-O-O"),
+        ] {
+            let position = Fen::parse(fen).unwrap();
+            let bit_move = BitMove::from_uci(&position, uci_move).unwrap();
+            assert_eq!(position.move_to_san(bit_move), san, "{fen} {uci_move}");
+        }
+    }
+
+    #[test]
+    fn equal_positions_compare_and_hash_equal() {
+        move_masks::init();
+
+        let mut positions = std::collections::HashSet::new();
+        for fen in [Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, Fen::TRICKY_POSITION_2] {
+            let position = Fen::parse(fen).unwrap();
+            for &bit_move in MoveGeneration::generate_legal_moves(&position).iter() {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                let parsed = Fen::parse(&position_copy.to_fen()).unwrap();
+                assert!(position_copy == parsed, "{fen} {}", bit_move.to_uci_string());
+                assert!(positions.insert(position_copy));
+                assert!(!positions.insert(parsed));
+            }
+        }
+
+        let position = Position::starting_position();
+        let mut later = position.clone();
+        later.fullmove_number += 1;
+        assert!(position != later);
+    }
+
+    #[test]
+    fn mirror_and_flip_reflect_the_board() {
+        move_masks::init();
+
+        let position = Fen::parse("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 2 9").unwrap();
+        let mirrored = position.mirror();
+        assert_eq!(mirrored.to_fen(), "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b Qk - 2 9");
+        assert!(mirrored.mirror() == position);
+        assert_eq!(MoveGeneration::generate_legal_moves(&mirrored).len(), MoveGeneration::generate_legal_moves(&position).len());
+
+        let position = Fen::parse("4k3/8/8/3pP3/8/8/8/1N2K3 w - d6 0 1").unwrap();
+        let flipped = position.flip();
+        assert_eq!(flipped.to_fen(), "3k4/8/8/3Pp3/8/8/8/3K2N1 w - e6 0 1");
+        assert!(flipped.flip() == position);
+        assert!(flipped.validate().is_ok());
+        assert!(Position::starting_position().flip().castling_rights == CastlingRights::NONE);
+    }
+
+    #[test]
+    fn null_move_passes_the_turn() {
+        move_masks::init();
+
+        let mut position = Fen::parse("rnbqkbnr/pppp1ppp/8/8/3pP3/5N2/PPP2PPP/RNBQKB1R b KQkq e3 0 3").unwrap();
+        #[cfg(feature = "revert_with_undo_move")]
+        let before = (position.to_fen(), position.zobrist_key);
+        position.make_null_move();
+
+        let passed = Fen::parse("rnbqkbnr/pppp1ppp/8/8/3pP3/5N2/PPP2PPP/RNBQKB1R w KQkq - 0 3").unwrap();
+        assert_eq!(position.to_fen(), passed.to_fen());
+        assert_eq!(position.zobrist_key, passed.zobrist_key);
+        assert!(position.checkers() == passed.checkers() && position.pinned(Color::White) == passed.pinned(Color::White));
+
+        #[cfg(feature = "revert_with_undo_move")]
+        {
+            position.undo_null_move();
+            assert_eq!((position.to_fen(), position.zobrist_key), before);
+        }
+    }
+
+    #[test]
+    fn clocks_follow_the_moves() {
+        move_masks::init();
+
+        let mut position = Position::starting_position();
+        let mut moves = Vec::new();
+        for (move_string, clocks) in [("e2e4", (0, 1)), ("g8f6", (1, 2)), ("g1f3", (2, 2)), ("f6e4", (0, 3)), ("f1c4", (1, 3))] {
+            let bit_move = BitMove::from_uci(&position, move_string).unwrap();
+            assert!(position.make_move(bit_move));
+            assert_eq!((position.halfmove_clock, position.fullmove_number), clocks, "{move_string}");
+            moves.push(bit_move);
+        }
+        assert_eq!(position.to_fen(), "rnbqkb1r/pppppppp/8/8/2B1n3/5N2/PPPP1PPP/RNBQK2R b KQkq - 1 3");
+        assert_eq!(Fen::parse(&position.to_fen()).unwrap().to_fen(), position.to_fen());
+
+        #[cfg(feature = "revert_with_undo_move")]
+        {
+            for &bit_move in moves.iter().rev() {
+                position.undo_move(bit_move);
+            }
+            assert_eq!(position.to_fen(), Position::starting_position().to_fen());
+        }
+    }
+
+    #[test]
+    fn zobrist_key_is_updated_incrementally() {
+        move_masks::init();
+
+        for fen in [Fen::KIWIPETE_POSITION, Fen::TRICKY_POSITION, Fen::TRICKY_POSITION_2, "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"] {
+            let position = Fen::parse(fen).unwrap();
+            for &bit_move in MoveGeneration::generate_pseudo_legal_moves(&position).iter() {
+                let mut position_copy = position.clone();
+                position_copy.make_move(bit_move);
+                assert_eq!(position_copy.zobrist_key, position_copy.compute_zobrist_key(), "{fen} {}", bit_move.to_uci_string());
+
+                #[cfg(feature = "revert_with_undo_move")]
+                {
+                    position_copy.undo_move(bit_move);
+                    assert_eq!(position_copy.zobrist_key, position_copy.compute_zobrist_key(), "{fen} {}", bit_move.to_uci_string());
+                }
+            }
+        }
+
+        let mut position = Position::starting_position();
+        let mut transposed = Position::starting_position();
+        for move_string in ["g1f3", "g8f6", "b1c3"] {
+            position.make_move(BitMove::from_uci(&position, move_string).unwrap());
+        }
+        for move_string in ["b1c3", "g8f6", "g1f3"] {
+            transposed.make_move(BitMove::from_uci(&transposed, move_string).unwrap());
+        }
+        assert_eq!(position.zobrist_key, transposed.zobrist_key);
+        assert_ne!(position.zobrist_key, Position::starting_position().zobrist_key);
+    }
+}
@@ -0,0 +1,89 @@
+use core::fmt;
+
+use crate::{piece::PieceType, position::Position, square::Square};
+
+// NOTE: When several pieces of a kind moved, they are paired up by square order
+#[derive(Default, PartialEq, Debug)]
+pub struct PositionDiff {
+    pub added: Vec<(PieceType, Square)>,
+    pub removed: Vec<(PieceType, Square)>,
+    pub moved: Vec<(PieceType, Square, Square)>,
+    pub state_changes: Vec<String>,
+}
+
+impl PositionDiff {
+    pub fn new(from: &Position, to: &Position) -> PositionDiff {
+        let mut diff = PositionDiff::default();
+
+        for piece in PieceType::ALL_PIECES {
+            let mut left = from.bbs[piece] & !to.bbs[piece];
+            let mut arrived = to.bbs[piece] & !from.bbs[piece];
+            while left.is_not_empty() && arrived.is_not_empty() {
+                diff.moved.push((piece, left.pop_lsb(), arrived.pop_lsb()));
+            }
+            while left.is_not_empty() {
+                diff.removed.push((piece, left.pop_lsb()));
+            }
+            while arrived.is_not_empty() {
+                diff.added.push((piece, arrived.pop_lsb()));
+            }
+        }
+
+        if from.side != to.side {
+            diff.state_changes.push(format!("side to move: {} -> {}", from.side, to.side));
+        }
+        if from.castling_rights != to.castling_rights {
+            diff.state_changes.push(format!("castling rights: {} -> {}", from.castling_rights, to.castling_rights));
+        }
+        if from.en_passant_sq != to.en_passant_sq {
+            diff.state_changes.push(format!("en-passant square: {} -> {}", from.en_passant_sq, to.en_passant_sq));
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty() && self.state_changes.is_empty()
+    }
+}
+
+impl fmt::Display for PositionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return f.pad("positions are identical");
+        }
+
+        let mut lines = Vec::new();
+        for (piece, source, target) in &self.moved {
+            lines.push(format!("moved {} {} -> {}", char::from(*piece), source, target));
+        }
+        for (piece, square) in &self.removed {
+            lines.push(format!("removed {} {}", char::from(*piece), square));
+        }
+        for (piece, square) in &self.added {
+            lines.push(format!("added {} {}", char::from(*piece), square));
+        }
+        lines.extend(self.state_changes.iter().cloned());
+        f.pad(&lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fen::Fen;
+
+    use super::*;
+
+    #[test]
+    fn diff_finds_moved_captured_and_promoted_pieces() {
+        let from = Fen::parse("4k3/1P6/8/3p4/4N3/8/8/4K3 w - -").unwrap();
+        let to = Fen::parse("1Q2k3/8/8/3N4/8/8/8/4K3 b - -").unwrap();
+        let diff = PositionDiff::new(&from, &to);
+
+        assert_eq!(diff.moved, [(PieceType::WN, Square::E4, Square::D5)]);
+        assert_eq!(diff.removed, [(PieceType::WP, Square::B7), (PieceType::BP, Square::D5)]);
+        assert_eq!(diff.added, [(PieceType::WQ, Square::B8)]);
+        assert_eq!(diff.state_changes, ["side to move: White -> Black"]);
+        assert!(PositionDiff::new(&from, &from).is_empty());
+    }
+}
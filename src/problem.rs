@@ -0,0 +1,212 @@
+use core::fmt;
+
+use crate::{bit_move::BitMove, fen::{Fen, FenError}, impl_error, impl_error_from, move_generation::MoveGeneration, position::Position};
+
+#[derive(Debug)]
+pub struct ProblemError(pub &'static str);
+
+impl_error!(ProblemError);
+impl_error_from!(FenError, ProblemError);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StipulationKind {
+    // The side to move mates against any defense
+    Direct,
+    // Both sides cooperate, so the side to move gets mated after its last move
+    Help,
+    // The side to move forces the other side to mate it against any defense
+    SelfMate,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stipulation {
+    pub kind: StipulationKind,
+    pub moves: u8,
+}
+
+impl TryFrom<&str> for Stipulation {
+    type Error = ProblemError;
+
+    fn try_from(stipulation: &str) -> Result<Self, Self::Error> {
+        let (kind, moves) = if let Some(moves) = stipulation.strip_prefix("h#") {
+            (StipulationKind::Help, moves)
+        } else if let Some(moves) = stipulation.strip_prefix("s#") {
+            (StipulationKind::SelfMate, moves)
+        } else if let Some(moves) = stipulation.strip_prefix('#') {
+            (StipulationKind::Direct, moves)
+        } else {
+            return Err(ProblemError("Couldn't parse stipulation kind!"));
+        };
+
+        match moves.parse::<u8>() {
+            Ok(moves) if (1..=Problem::MAX_MOVES).contains(&moves) => Ok(Stipulation { kind, moves }),
+            _ => Err(ProblemError("Couldn't parse stipulation length!")),
+        }
+    }
+}
+
+impl fmt::Display for Stipulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = match self.kind {
+            StipulationKind::Direct => "",
+            StipulationKind::Help => "h",
+            StipulationKind::SelfMate => "s",
+        };
+        f.pad(&format!("{}#{}", prefix, self.moves))
+    }
+}
+
+// "<board> <side> <castling> <en-passant> h#2" or "... stip \"h#2\";"
+pub struct Problem {
+    pub position: Position,
+    pub stipulation: Stipulation,
+}
+
+impl Problem {
+    // NOTE: The solver tries every line, so longer problems would take far too long
+    pub const MAX_MOVES: u8 = 4;
+
+    pub fn parse(epd: &str) -> Result<Problem, ProblemError> {
+        let fields: Vec<&str> = epd.split_whitespace().collect();
+        if fields.len() < 5 {
+            return Err(ProblemError("Problem needs a position and a stipulation!"));
+        }
+
+        let position = Fen::parse(&fields[..4].join(" "))?;
+        let stipulation = match fields[4] {
+            "stip" => fields.get(5).ok_or(ProblemError("Didn't find stipulation!"))?,
+            stipulation => stipulation,
+        };
+        let stipulation = Stipulation::try_from(stipulation.trim_end_matches(';').trim_matches('"'))?;
+        Ok(Problem { position, stipulation })
+    }
+
+    // Every solution, where a solution of a helpmate is the whole line, while direct mates and
+    // selfmates are solved by their key move alone
+    pub fn solve(&self) -> Vec<Vec<BitMove>> {
+        let moves = self.stipulation.moves;
+        match self.stipulation.kind {
+            StipulationKind::Help => {
+                let mut solutions = Vec::new();
+                Self::help_lines(&self.position, moves * 2, &mut Vec::new(), &mut solutions);
+                solutions
+            },
+            StipulationKind::Direct => Self::key_moves(&self.position, |position| Self::defense_fails(position, moves - 1, Self::is_checkmate)),
+            StipulationKind::SelfMate => Self::key_moves(&self.position, |position| Self::selfmate_forced(position, moves)),
+        }
+    }
+
+    fn key_moves(position: &Position, solves: impl Fn(&Position) -> bool) -> Vec<Vec<BitMove>> {
+        MoveGeneration::generate_legal_moves(position)
+            .into_iter()
+            .filter(|&key_move| {
+                let mut position_copy = position.clone();
+                position_copy.make_move(key_move);
+                solves(&position_copy)
+            })
+            .map(|key_move| vec![key_move])
+            .collect()
+    }
+
+    fn is_checkmate(position: &Position) -> bool {
+        position.in_check() && MoveGeneration::generate_legal_moves(position).len() == 0
+    }
+
+    fn after(position: &Position, bit_move: BitMove) -> Position {
+        let mut position_copy = position.clone();
+        position_copy.make_move(bit_move);
+        position_copy
+    }
+
+    // The defender is to move. The attacker reaches the goal in the remaining moves against any
+    // defense, which is checked after each of the attacker's moves.
+    fn defense_fails(position: &Position, moves_left: u8, goal: fn(&Position) -> bool) -> bool {
+        if goal(position) {
+            return true;
+        }
+        if moves_left == 0 {
+            return false;
+        }
+
+        let defenses = MoveGeneration::generate_legal_moves(position);
+        defenses.len() != 0 && defenses.into_iter().all(|defense| {
+            let position = Self::after(position, defense);
+            MoveGeneration::generate_legal_moves(&position)
+                .into_iter()
+                .any(|attack| Self::defense_fails(&Self::after(&position, attack), moves_left - 1, goal))
+        })
+    }
+
+    // The side that has to get mated just moved, so the other side has to mate it now if this was
+    // the last move. Otherwise, every defense either mates right away, which the defender would
+    // avoid if it could, or runs into another forcing move.
+    fn selfmate_forced(position: &Position, moves_left: u8) -> bool {
+        let defenses = MoveGeneration::generate_legal_moves(position);
+        if defenses.len() == 0 {
+            return false;
+        }
+
+        defenses.into_iter().all(|defense| {
+            let position = Self::after(position, defense);
+            if Self::is_checkmate(&position) {
+                return true;
+            }
+            moves_left > 1 && MoveGeneration::generate_legal_moves(&position)
+                .into_iter()
+                .any(|attack| Self::selfmate_forced(&Self::after(&position, attack), moves_left - 1))
+        })
+    }
+
+    // Both sides play along, and the last move has to mate the side that started
+    fn help_lines(position: &Position, plies_left: u8, line: &mut Vec<BitMove>, solutions: &mut Vec<Vec<BitMove>>) {
+        if plies_left == 0 {
+            if Self::is_checkmate(position) {
+                solutions.push(line.clone());
+            }
+            return;
+        }
+
+        for bit_move in MoveGeneration::generate_legal_moves(position) {
+            let position = Self::after(position, bit_move);
+            // NOTE: A mate before the last move ends the line without solving the problem
+            if plies_left > 1 && MoveGeneration::generate_legal_moves(&position).len() == 0 {
+                continue;
+            }
+            line.push(bit_move);
+            Self::help_lines(&position, plies_left - 1, line, solutions);
+            line.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    fn solutions(epd: &str) -> Vec<String> {
+        Problem::parse(epd)
+            .unwrap()
+            .solve()
+            .iter()
+            .map(|line| line.iter().map(|m| m.to_uci_string()).collect::<Vec<_>>().join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn solves_direct_help_and_selfmates() {
+        move_masks::init();
+
+        assert_eq!(solutions("6k1/5ppp/8/8/8/8/8/R5K1 w - - #1"), ["a1a8"]);
+        assert_eq!(solutions("7k/8/6K1/8/8/8/8/R7 b - - stip \"h#1\";"), ["h8g8 a1a8"]);
+        // The check leaves black nothing but taking the queen, which mates white along the h-file
+        assert_eq!(solutions("7k/5Q2/8/8/1r4Q1/1P4r1/8/7K w - - s#1"), ["g4h4"]);
+        assert!(solutions("7k/8/8/8/8/8/8/R5K1 w - - #1").is_empty());
+
+        assert!(Problem::parse("7k/8/8/8/8/8/8/R5K1 w - -").is_err());
+        assert!(Problem::parse("7k/8/8/8/8/8/8/R5K1 w - - x#2").is_err());
+        assert!(Problem::parse("7k/8/8/8/8/8/8/R5K1 w - - #9").is_err());
+    }
+}
+
@@ -0,0 +1,88 @@
+use std::{env, fs, io, path::{Path, PathBuf}};
+
+use crate::{engine_options::EngineOptions, impl_error};
+
+#[derive(Debug)]
+pub struct ProfileError(pub &'static str);
+
+impl_error!(ProfileError);
+
+impl From<io::Error> for ProfileError {
+    fn from(_: io::Error) -> Self {
+        ProfileError("Couldn't access profile!")
+    }
+}
+
+// The setoption commands loaded at startup unless started with --no-profile
+// NOTE: Lines starting with # are skipped
+pub struct Profile { }
+
+impl Profile {
+    const FILE_NAME: &'static str = ".sisyphus32-profile";
+
+    // The profile in the home directory, or in the working directory if there is no home directory
+    pub fn default_path() -> PathBuf {
+        env::var_os("HOME")
+            .or_else(|| env::var_os("USERPROFILE"))
+            .map_or_else(PathBuf::new, PathBuf::from)
+            .join(Self::FILE_NAME)
+    }
+
+    pub fn save(path: &Path, options: &EngineOptions) -> Result<(), ProfileError> {
+        let mut contents = format!("# Sisyphus32 {} settings profile\n", env!("CARGO_PKG_VERSION"));
+        for command in options.setoption_commands() {
+            contents.push_str(&command);
+            contents.push('\n');
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    // Returns whether there was a profile to load. Settings that can't be applied, for example
+    // because the option was removed since the profile was saved, are skipped, so one stale line
+    // doesn't throw away the rest of the profile.
+    pub fn load(path: &Path, options: &mut EngineOptions) -> Result<bool, ProfileError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut found_invalid_setting = false;
+        for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let applied = line.starts_with("setoption") && options.apply_setoption(line).is_ok();
+            found_invalid_setting |= !applied;
+        }
+
+        match found_invalid_setting {
+            true => Err(ProfileError("Skipped invalid settings in profile!")),
+            false => Ok(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_round_trips_and_skips_invalid_settings() {
+        let mut options = EngineOptions::default();
+        options.set("MultiPV", "3").unwrap();
+        options.set("Preset", "Club").unwrap();
+
+        let path = env::temp_dir().join("sisyphus32-profile-test");
+        Profile::save(&path, &options).unwrap();
+        let mut loaded = EngineOptions::default();
+        assert!(Profile::load(&path, &mut loaded).unwrap());
+        assert_eq!(loaded.setoption_commands(), options.setoption_commands());
+
+        fs::write(&path, "setoption name SyzygyPath value tb\nsetoption name MultiPV value 2\n").unwrap();
+        let mut loaded = EngineOptions::default();
+        assert!(Profile::load(&path, &mut loaded).is_err());
+        assert_eq!(loaded.spin(EngineOptions::MULTIPV), 2);
+
+        fs::remove_file(&path).unwrap();
+        assert!(!Profile::load(&path, &mut loaded).unwrap());
+    }
+}
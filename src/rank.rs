@@ -1,6 +1,8 @@
 use core::fmt;
 use std::mem::transmute;
 
+use crate::impl_error;
+
 #[derive(Clone, Copy, PartialEq)]
 #[repr(u8)]
 pub enum Rank {
@@ -24,6 +26,8 @@ impl From<u8> for Rank {
 #[derive(Debug)]
 pub struct RankParseError(pub &'static str);
 
+impl_error!(RankParseError);
+
 impl TryFrom<char> for Rank {
     type Error = RankParseError;
 
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{bit_move::BitMove, color::Color, eval::Eval, fen::Fen, impl_error, move_generation::MoveGeneration, pl, position::Position, search::{Search, SearchOutput}, timer::Timer};
+
+#[derive(Debug)]
+pub struct RegressionError(pub &'static str);
+
+impl_error!(RegressionError);
+
+impl From<io::Error> for RegressionError {
+    fn from(_: io::Error) -> Self {
+        RegressionError("Couldn't access params file!")
+    }
+}
+
+// Every opening is played twice, with the current parameters playing each color once
+const OPENINGS: [&str; 4] = [
+    Fen::STARTING_POSITION,
+    "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+    "rnbqkb1r/pppppppp/5n2/8/2PP4/8/PP2PPPP/RNBQKBNR b KQkq - 0 2",
+    "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+];
+
+// NOTE: Games that reach this length are adjudicated as draws, which keeps the suite quick
+const MAX_PLIES: u32 = 160;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameResult {
+    CurrentWins,
+    PreviousWins,
+    Draw,
+}
+
+pub struct GoldenGame {
+    pub opening: &'static str,
+    pub current_color: Color,
+    pub result: GameResult,
+    pub plies: u32,
+    // Positions where the two parameter sets would have played different moves
+    pub divergent_plies: u32,
+    pub first_divergence: Option<u32>,
+}
+
+// "<feature name> <weight>" lines, where missing features keep their current weight
+pub struct RegressionParams { }
+
+impl RegressionParams {
+    pub fn parse(contents: &str) -> Result<[i16; Eval::FEATURE_COUNT], RegressionError> {
+        let mut weights = Eval::FEATURE_WEIGHTS;
+        for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (name, value) = line.split_once(char::is_whitespace).ok_or(RegressionError("Found parameter without value!"))?;
+            let index = Eval::FEATURE_NAMES
+                .iter()
+                .position(|&feature_name| feature_name == name)
+                .ok_or(RegressionError("Found unknown parameter!"))?;
+            weights[index] = value.trim().parse::<i16>().map_err(|_| RegressionError("Couldn't parse parameter value!"))?;
+        }
+        Ok(weights)
+    }
+
+    pub fn format(weights: &[i16; Eval::FEATURE_COUNT]) -> String {
+        Eval::FEATURE_NAMES
+            .iter()
+            .zip(weights)
+            .map(|(name, weight)| format!("{} {}\n", name, weight))
+            .collect()
+    }
+
+    pub fn load(path: &Path) -> Result<[i16; Eval::FEATURE_COUNT], RegressionError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(path: &Path, weights: &[i16; Eval::FEATURE_COUNT]) -> Result<(), RegressionError> {
+        fs::write(path, Self::format(weights))?;
+        Ok(())
+    }
+}
+
+pub struct Regression { }
+
+impl Regression {
+    pub const DEFAULT_NODES: u64 = 2_000;
+
+    // Plays the golden games between the current and the previous parameters. Every move is a
+    // fixed-node search without randomness, so the games only change when the play does. Both
+    // parameter sets search every position, which shows where they would disagree even in
+    // positions only one of them gets to play.
+    pub fn run(current: &[i16; Eval::FEATURE_COUNT], previous: &[i16; Eval::FEATURE_COUNT], nodes: u64) -> Vec<GoldenGame> {
+        let timer = Timer::new();
+        let mut games = Vec::new();
+
+        for opening in OPENINGS {
+            for current_color in [Color::White, Color::Black] {
+                games.push(Self::play_game(opening, current_color, current, previous, nodes));
+            }
+        }
+
+        Self::print_report(&games, nodes, timer.get_time_passed_millis());
+        games
+    }
+
+    fn play_game(opening: &'static str, current_color: Color, current: &[i16; Eval::FEATURE_COUNT], previous: &[i16; Eval::FEATURE_COUNT], nodes: u64) -> GoldenGame {
+        let mut position = Fen::parse(opening).expect("Openings are valid FENs");
+        let mut repetitions: HashMap<u64, u8> = HashMap::new();
+        let mut game = GoldenGame {
+            opening,
+            current_color,
+            result: GameResult::Draw,
+            plies: 0,
+            divergent_plies: 0,
+            first_divergence: None,
+        };
+
+        while game.plies < MAX_PLIES {
+            if MoveGeneration::generate_legal_moves(&position).len() == 0 {
+                if position.in_check() {
+                    game.result = if position.side == current_color { GameResult::PreviousWins } else { GameResult::CurrentWins };
+                }
+                break;
+            }
+
+            let repetition_count = repetitions.entry(position.zobrist_key).or_insert(0);
+            *repetition_count += 1;
+            if *repetition_count >= 3 || position.is_dead_position() {
+                break;
+            }
+
+            let current_move = Self::search(&position, current, nodes);
+            let previous_move = Self::search(&position, previous, nodes);
+            if current_move != previous_move {
+                game.divergent_plies += 1;
+                game.first_divergence.get_or_insert(game.plies);
+            }
+
+            position.make_move(if position.side == current_color { current_move } else { previous_move });
+            game.plies += 1;
+        }
+
+        game
+    }
+
+    fn search(position: &Position, weights: &[i16; Eval::FEATURE_COUNT], nodes: u64) -> BitMove {
+        Search::new(u128::MAX)
+            .with_output(SearchOutput::Silent)
+            .with_node_limit(nodes)
+            .with_eval_weights(*weights)
+            .go_isolated(position, Search::MAX_DEPTH)
+    }
+
+    fn print_report(games: &[GoldenGame], nodes: u64, time: u128) {
+        pl!(format!("\n    Golden games at {} nodes per move:", nodes));
+        pl!("  |---------------------------------------------------------------------------------|");
+        pl!(format!("  | {:<4} | {:<7} | {:<14} | {:<5} | {:<10} | {:<24} |", "Game", "Current", "Result", "Plies", "Divergent", "First divergence (ply)"));
+        pl!("  |---------------------------------------------------------------------------------|");
+
+        let (mut wins, mut draws, mut losses, mut divergent_plies, mut total_plies) = (0, 0, 0, 0, 0);
+        for (index, game) in games.iter().enumerate() {
+            let result = match game.result {
+                GameResult::CurrentWins => { wins += 1; "current wins" }
+                GameResult::PreviousWins => { losses += 1; "previous wins" }
+                GameResult::Draw => { draws += 1; "draw" }
+            };
+            divergent_plies += game.divergent_plies;
+            total_plies += game.plies;
+            let first_divergence = game.first_divergence.map_or("-".to_owned(), |ply| ply.to_string());
+            pl!(format!("  | {:<4} | {:<7} | {:<14} | {:<5} | {:<10} | {:<24} |", index + 1, game.current_color, result, game.plies, game.divergent_plies, first_divergence));
+        }
+
+        pl!("  |---------------------------------------------------------------------------------|");
+        pl!(format!("  Current vs previous: +{} ={} -{}", wins, draws, losses));
+        pl!(format!("  Divergent moves: {} of {}", divergent_plies, total_plies));
+        if divergent_plies == 0 {
+            pl!("  No divergence, the parameters play identically");
+        }
+        pl!(format!("  Time: {} milliseconds\n", time));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_round_trip_through_file_format() {
+        let mut weights = Eval::FEATURE_WEIGHTS;
+        weights[1] = 320;
+        assert_eq!(RegressionParams::parse(&RegressionParams::format(&weights)).unwrap(), weights);
+        assert_eq!(RegressionParams::parse("# old eval\nknight 320\n").unwrap(), weights);
+        assert!(RegressionParams::parse("dragon 5").is_err());
+        assert!(RegressionParams::parse("knight many").is_err());
+    }
+
+    // NOTE: The random search picks different moves every time, so it can't play golden games
+    #[cfg(not(feature = "search_random"))]
+    #[test]
+    fn identical_params_never_diverge() {
+        crate::move_masks::init();
+
+        let games = Regression::run(&Eval::FEATURE_WEIGHTS, &Eval::FEATURE_WEIGHTS, 200);
+        assert_eq!(games.len(), OPENINGS.len() * 2);
+        assert!(games.iter().all(|game| game.divergent_plies == 0 && game.first_divergence.is_none()));
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fs, io, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{engine_options::EngineOptions, impl_error};
+
+#[derive(Debug)]
+pub struct ReproError(pub &'static str);
+
+impl_error!(ReproError);
+
+impl From<io::Error> for ReproError {
+    fn from(_: io::Error) -> Self {
+        ReproError("Couldn't read reproduction bundle!")
+    }
+}
+
+// The UCI commands that recreate a search, so a crash can be reproduced
+// NOTE: Lines starting with # are skipped
+#[derive(Clone)]
+pub struct ReproBundle {
+    commands: Vec<String>,
+}
+
+impl ReproBundle {
+    // The seed is the one the search actually used, which differs from the Seed option if that was empty
+    pub fn new(options: &EngineOptions, seed: u64, root_fen: &str, moves: &[String], go_command: &str) -> ReproBundle {
+        let mut commands = options.setoption_commands();
+        commands.push(format!("setoption name {} value {}", EngineOptions::SEED, seed));
+        commands.push(match moves.is_empty() {
+            true => format!("position fen {}", root_fen),
+            false => format!("position fen {} moves {}", root_fen, moves.join(" ")),
+        });
+        commands.push(go_command.trim().to_owned());
+        ReproBundle { commands }
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    // Writes the bundle to a new file in the temporary directory and returns its path
+    pub fn write(&self, message: &str) -> io::Result<PathBuf> {
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis());
+        let path = std::env::temp_dir().join(format!("sisyphus32-repro-{}.txt", millis));
+        self.write_to(&path, message)?;
+        Ok(path)
+    }
+
+    pub fn write_to(&self, path: &Path, message: &str) -> io::Result<()> {
+        let mut contents = format!(
+            "# Sisyphus32 {} reproduction bundle\n# error: {}\n",
+            env!("CARGO_PKG_VERSION"),
+            message.replace('\n', " ")
+        );
+        for command in &self.commands {
+            contents.push_str(command);
+            contents.push('\n');
+        }
+        fs::write(path, contents)
+    }
+
+    pub fn read(path: &Path) -> Result<ReproBundle, ReproError> {
+        let commands: Vec<String> = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_owned)
+            .collect();
+
+        if commands.iter().any(|command| command.starts_with("repro")) {
+            return Err(ReproError("Reproduction bundle can't replay other bundles!"));
+        }
+        Ok(ReproBundle { commands })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_round_trips_through_file() {
+        let mut options = EngineOptions::default();
+        options.set("Preset", "Club").unwrap();
+        let moves = vec!["e2e4".to_owned(), "e7e5".to_owned()];
+        let bundle = ReproBundle::new(&options, 42, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &moves, "go depth 3\n");
+
+        let path = std::env::temp_dir().join("sisyphus32-repro-test.txt");
+        bundle.write_to(&path, "index out of bounds\nat search.rs").unwrap();
+        let read_bundle = ReproBundle::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read_bundle.commands(), bundle.commands());
+        let commands = read_bundle.commands();
+        assert!(commands.contains(&"setoption name NpsLimit value 20000".to_owned()));
+        assert!(!commands.iter().any(|command| command.contains("Preset")));
+        assert_eq!(commands[commands.len() - 3], "setoption name Seed value 42");
+        assert_eq!(commands[commands.len() - 2], "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4 e7e5");
+        assert_eq!(commands[commands.len() - 1], "go depth 3");
+    }
+}
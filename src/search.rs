@@ -1,45 +1,374 @@
 extern crate rand;
 
-use rand::Rng;
+use std::{io, panic::{self, AssertUnwindSafe}, path::PathBuf, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread, time::Duration};
 
-use crate::{bit_move::ScoringMove, eval::Eval, move_generation::MoveGeneration, pl, position::Position, timer::Timer};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{bit_move::{BitMove, ScoringMove}, checkpoint::{AnalysisCheckpoint, CheckpointLine}, eval::{Eval, EvalBlend}, history::{ContinuationHistory, PieceTarget}, move_generation::MoveGeneration, move_list::MoveList, move_ordering::MoveOrdering, move_picker::MovePicker, see::See, nnue::{Accumulator, Network}, node_counter::{NodeCounter, NpsMeter}, pl, position::Position, repro::ReproBundle, search_shared::SearchShared, timer::{Clock, MonotonicClock, TimeLimits, TimeManager}, tt::{Bound, TranspositionTable, TtEntry}, wdl::Wdl};
+
+// NOTE: With xboard, the caller reports the move itself
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchOutput {
+    Uci,
+    Xboard,
+    Silent,
+}
+
+// A MultiPV line: the root move, its score and the expected continuation
+#[derive(Clone)]
+struct SearchLine {
+    best_move: ScoringMove,
+    pv: Vec<BitMove>,
+}
+
+impl SearchLine {
+    fn blank(score: i16) -> SearchLine {
+        SearchLine { best_move: ScoringMove::blank(score), pv: Vec::new() }
+    }
+}
 
 pub struct Search {
-    timer: Timer,
-    stop_time: u128,
+    time_manager: TimeManager<Box<dyn Clock + Send>>,
+    output: SearchOutput,
+    line_stop_time: u128,
     stop_calculating: bool,
+    shared: Arc<SearchShared>,
+    ponder_flag: Arc<AtomicBool>,
+    pondering: bool,
+    ponder_credit_percent: u128,
     nodes: u64,
-    // pv, killer_moves, etc...
+    total_nodes: u64,
+    node_counter: NodeCounter,
+    nps_meter: NpsMeter,
+    node_limit: u64,
+    nps_limit: u64,
+    // NOTE: Reading the clock is slow, so the limits are only checked every so many nodes
+    check_interval: u64,
+    nodes_until_check: u64,
+    root_depth: u16,
+    seldepth: u16,
+    multipv: usize,
+    temperature: u16,
+    rng: StdRng,
+    root_moves: Vec<ScoringMove>,
+    show_wdl: bool,
+    best_score: i16,
+    repro_bundle: Option<ReproBundle>,
+    eval_weights: Option<[i16; Eval::FEATURE_COUNT]>,
+    // How much worse than equal a draw is for the side to move at the root
+    contempt: i16,
+    use_nnue: bool,
+    network: Option<Arc<Network>>,
+    eval_blend: EvalBlend,
+    accumulators: Vec<Accumulator>,
+    pv_table: Vec<Vec<BitMove>>,
+    root_pv: Vec<BitMove>,
+    previous_pv: Vec<BitMove>,
+    following_pv: bool,
+    previous_score: Option<i16>,
+    transposition_table: Option<Arc<TranspositionTable>>,
+    // Counting this one, while the helpers only fill the shared table and counters
+    threads: usize,
+    // NOTE: Only the main thread stops the shared search, so a helper that panics leaves the others searching
+    main_thread: bool,
+    #[cfg(test)]
+    panicking_helpers: usize,
+    #[cfg(test)]
+    panics: bool,
+    continuation_history: ContinuationHistory,
+    move_stack: Vec<Option<PieceTarget>>,
+    killer_moves: Vec<[BitMove; 2]>,
+    pruning: bool,
+    quiescence: bool,
+    checkpoint_path: Option<PathBuf>,
+    checkpoint_interval: u128,
+    last_checkpoint_time: u128,
+    checkpointed_depth: u16,
+    // A resumed analysis, with the time and nodes it had already spent
+    resumed_checkpoint: Option<AnalysisCheckpoint>,
+    time_offset: u128,
+    nodes_offset: u64,
 }
 
 impl Search {
     pub fn new(stop_time: u128) -> Search {
+        let shared = Arc::new(SearchShared::default());
         Search {
-            timer: Timer::new(),
-            stop_time,
+            time_manager: TimeManager::with_clock(Box::new(MonotonicClock::default()), TimeLimits::fixed(stop_time)),
+            output: SearchOutput::Uci,
+            line_stop_time: stop_time,
             stop_calculating: false,
+            node_counter: NodeCounter::with_shared(shared.clone()),
+            shared,
+            ponder_flag: Arc::new(AtomicBool::new(false)),
+            pondering: false,
+            ponder_credit_percent: 0,
             nodes: 0,
+            total_nodes: 0,
+            nps_meter: NpsMeter::default(),
+            node_limit: u64::MAX,
+            nps_limit: 0,
+            check_interval: Self::DEFAULT_CHECK_INTERVAL,
+            nodes_until_check: Self::DEFAULT_CHECK_INTERVAL,
+            root_depth: 0,
+            seldepth: 0,
+            multipv: 1,
+            temperature: 0,
+            rng: StdRng::from_rng(&mut rand::rng()),
+            root_moves: Vec::new(),
+            show_wdl: false,
+            best_score: 0,
+            repro_bundle: None,
+            eval_weights: None,
+            contempt: 0,
+            use_nnue: true,
+            network: None,
+            eval_blend: EvalBlend::Off,
+            accumulators: Vec::new(),
+            pv_table: Vec::new(),
+            root_pv: Vec::new(),
+            previous_pv: Vec::new(),
+            following_pv: false,
+            previous_score: None,
+            transposition_table: None,
+            threads: 1,
+            main_thread: true,
+            #[cfg(test)]
+            panicking_helpers: 0,
+            #[cfg(test)]
+            panics: false,
+            continuation_history: ContinuationHistory::new(),
+            move_stack: Vec::new(),
+            killer_moves: Vec::new(),
+            pruning: true,
+            quiescence: true,
+            checkpoint_path: None,
+            checkpoint_interval: 0,
+            last_checkpoint_time: 0,
+            checkpointed_depth: 0,
+            resumed_checkpoint: None,
+            time_offset: 0,
+            nodes_offset: 0,
+        }
+    }
+
+    pub fn with_output(mut self, output: SearchOutput) -> Search {
+        self.output = output;
+        self
+    }
+
+    // The search can extend its time up to the hard limit when the score drops
+    pub fn with_hard_time_limit(mut self, hard_limit: u128) -> Search {
+        let soft_limit = self.time_manager.soft_limit();
+        self.time_manager.set_limits(TimeLimits { soft: soft_limit, hard: hard_limit });
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + Send + 'static) -> Search {
+        let limits = TimeLimits { soft: self.time_manager.soft_limit(), hard: self.time_manager.hard_limit() };
+        self.time_manager = TimeManager::with_clock(Box::new(clock), limits);
+        self
+    }
+
+    // Written to a file if the search panics, so the crash can be reproduced
+    pub fn with_repro_bundle(mut self, repro_bundle: ReproBundle) -> Search {
+        self.repro_bundle = Some(repro_bundle);
+        self
+    }
+
+    // Writes the completed lines at most once per interval, and once more when the search stops
+    pub fn with_checkpoint(mut self, path: PathBuf, interval_millis: u128) -> Search {
+        self.checkpoint_path = Some(path);
+        self.checkpoint_interval = interval_millis;
+        self
+    }
+
+    // NOTE: The checkpoint has to be of the position that is searched
+    pub fn with_resumed_checkpoint(mut self, checkpoint: AnalysisCheckpoint) -> Search {
+        self.resumed_checkpoint = Some(checkpoint);
+        self
+    }
+
+    pub fn with_eval_weights(mut self, eval_weights: [i16; Eval::FEATURE_COUNT]) -> Search {
+        self.eval_weights = Some(eval_weights);
+        self
+    }
+
+    pub fn with_contempt(mut self, contempt: i16) -> Search {
+        self.contempt = contempt;
+        self
+    }
+
+    pub fn with_show_wdl(mut self, show_wdl: bool) -> Search {
+        self.show_wdl = show_wdl;
+        self
+    }
+
+    pub fn with_nnue(mut self, use_nnue: bool) -> Search {
+        self.use_nnue = use_nnue;
+        self
+    }
+
+    pub fn with_eval_blend(mut self, eval_blend: EvalBlend) -> Search {
+        self.eval_blend = eval_blend;
+        self
+    }
+
+    pub fn with_network(mut self, network: Option<Arc<Network>>) -> Search {
+        self.network = network;
+        self
+    }
+
+    pub fn with_multipv(mut self, multipv: usize) -> Search {
+        self.multipv = multipv.max(1);
+        self
+    }
+
+    // NOTE: A move worse than the best by the temperature in centipawns is picked e times less often
+    pub fn with_temperature(mut self, temperature: u16, seed: Option<u64>) -> Search {
+        self.temperature = temperature;
+        if let Some(seed) = seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+        self
+    }
+
+    pub fn with_shared(mut self, shared: Arc<SearchShared>) -> Search {
+        self.node_counter = NodeCounter::with_shared(shared.clone());
+        self.shared = shared;
+        self
+    }
+
+    // NOTE: The time budget is counted from the ponderhit, when the own clock starts running
+    pub fn with_ponder_flag(mut self, ponder_flag: Arc<AtomicBool>) -> Search {
+        self.pondering = ponder_flag.load(Ordering::Relaxed);
+        self.ponder_flag = ponder_flag;
+        self
+    }
+
+    // NOTE: At most half of the budget is credited
+    pub fn with_ponder_credit(mut self, ponder_credit_percent: u128) -> Search {
+        self.ponder_credit_percent = ponder_credit_percent;
+        self
+    }
+
+    fn check_ponderhit(&mut self) {
+        if self.pondering && !self.ponder_flag.load(Ordering::Relaxed) {
+            let credit = (self.time_manager.elapsed() * self.ponder_credit_percent / 100).min(self.time_manager.soft_limit() / 2);
+            self.time_manager.shorten(credit);
+            self.line_stop_time = self.line_stop_time.saturating_sub(credit);
+            self.pondering = false;
+            self.time_manager.reset();
+        }
+    }
+
+    #[inline(always)]
+    fn should_stop(&mut self) -> bool {
+        self.check_ponderhit();
+        !self.pondering && self.time_manager.elapsed() > self.line_stop_time
+            || self.shared.is_stopped()
+    }
+
+    // NOTE: A pondering search may not report its move before ponderhit or stop
+    fn wait_while_pondering(&mut self) {
+        while self.pondering && !self.shared.is_stopped() {
+            thread::sleep(Duration::from_millis(1));
+            self.check_ponderhit();
         }
     }
 
-    fn random_best_move(&self, position: &Position, _depth: u8) -> ScoringMove {
-        let moves = MoveGeneration::generate_legal_moves(position);
+    pub fn with_transposition_table(mut self, transposition_table: Arc<TranspositionTable>) -> Search {
+        self.transposition_table = Some(transposition_table);
+        self
+    }
+
+    // NOTE: Helper threads need a shared table, without one they would only repeat this thread's work
+    pub fn with_threads(mut self, threads: usize) -> Search {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_node_limit(mut self, node_limit: u64) -> Search {
+        self.node_limit = node_limit;
+        self
+    }
+
+    pub fn with_nps_limit(mut self, nps_limit: u64) -> Search {
+        self.nps_limit = nps_limit;
+        // NOTE: Polling about every 10 milliseconds keeps the sleeps from overshooting the stop time
+        self.check_interval = if nps_limit == 0 {
+            Self::DEFAULT_CHECK_INTERVAL
+        } else {
+            (nps_limit / 100).clamp(1, Self::DEFAULT_CHECK_INTERVAL)
+        };
+        self.nodes_until_check = self.check_interval;
+        self
+    }
+
+    fn throttle(&self) {
+        let expected_millis = self.total_nodes as u128 * 1000 / self.nps_limit as u128;
+        let sleep_until = expected_millis.min(self.line_stop_time);
+        let time_passed = self.time_manager.elapsed();
+        if sleep_until > time_passed {
+            thread::sleep(Duration::from_millis((sleep_until - time_passed) as u64));
+        }
+    }
+
+    // NOTE: Line i may use 2 / (i + 2) of the budget, which leaves most of the time to the first line
+    fn line_stop_time(&self, line_index: usize) -> u128 {
+        self.time_manager.soft_limit().saturating_mul(2) / (line_index as u128 + 2)
+    }
+
+    fn random_best_move(&self, position: &Position, _depth: u16, excluded_moves: &[BitMove], _window: (i16, i16)) -> ScoringMove {
+        let moves: Vec<BitMove> = MoveGeneration::generate_legal_moves(position)
+            .into_iter()
+            .filter(|m| !excluded_moves.contains(m))
+            .collect();
         ScoringMove::from(moves[rand::rng().random_range(0..moves.len())])
     }
     
-    fn minimax_best_move(&mut self, position: &Position, depth: u8) -> ScoringMove {
+    #[inline(always)]
+    fn visit_node(&mut self, depth: u16) {
         self.nodes += 1;
+        self.total_nodes += 1;
+        self.node_counter.increment();
+        self.seldepth = self.seldepth.max(self.root_depth - depth);
 
-        if self.nodes % 5000 == 0 && self.timer.get_time_passed_millis() > self.stop_time {
+        if self.total_nodes >= self.node_limit {
             self.stop_calculating = true;
         }
 
+        #[cfg(test)]
+        if self.panics && self.total_nodes == 1000 {
+            panic!("helper panicked");
+        }
+
+        self.nodes_until_check -= 1;
+        if self.nodes_until_check == 0 {
+            self.nodes_until_check = self.check_interval;
+            self.update_node_stats();
+            if self.nps_limit != 0 {
+                self.throttle();
+            }
+            if self.should_stop() {
+                self.stop_calculating = true;
+            }
+        }
+    }
+
+    fn minimax_best_move(&mut self, position: &Position, depth: u16) -> ScoringMove {
+        self.visit_node(depth);
+
         if self.stop_calculating {
             return ScoringMove::blank(12345)
         }
+
+        if position.is_dead_position() || position.is_fifty_move_draw() {
+            return ScoringMove::blank(self.draw_score((self.root_depth - depth) as usize));
+        }
         
         if depth == 0 {
-            return Eval::basic(position);
+            return self.evaluate(position, (self.root_depth - depth) as usize);
         }
     
         MoveGeneration::generate_pseudo_legal_scoring_moves(position)
@@ -47,6 +376,7 @@ impl Search {
             .filter_map(|mut m: ScoringMove| {
                 let mut position_copy = position.clone();
                 if position_copy.make_move(m.bit_move) {
+                    self.update_accumulator((self.root_depth - depth) as usize + 1, &position_copy);
                     m.score = -self.minimax_best_move(&position_copy, depth - 1).score;
                     Some(m)
                 } else {
@@ -56,52 +386,1245 @@ impl Search {
             .max()
             .unwrap_or_else(|| {
                 if position.in_check() {
-                    ScoringMove::blank(-10000)
+                    ScoringMove::blank(Self::mated_score((self.root_depth - depth) as usize))
+                } else {
+                    ScoringMove::blank(self.draw_score((self.root_depth - depth) as usize))
+                }
+            })
+    }
+
+    #[inline(always)]
+    fn evaluate(&self, position: &Position, ply: usize) -> ScoringMove {
+        match (&self.eval_weights, &self.network) {
+            (Some(eval_weights), _) => Eval::weighted(position, eval_weights),
+            (None, Some(network)) => Eval::blended(position, &self.accumulators[ply], network, self.eval_blend),
+            (None, None) => Eval::basic(position),
+        }
+    }
+
+    // Sets up the root accumulator, or drops the network if the search doesn't evaluate with it
+    fn set_up_network(&mut self, position: &Position) {
+        if !self.use_nnue || self.eval_weights.is_some() {
+            self.network = None;
+        }
+        if let Some(network) = &self.network {
+            self.accumulators = vec![Accumulator::new(network, position)];
+        }
+    }
+
+    #[inline(always)]
+    fn update_accumulator(&mut self, ply: usize, position: &Position) {
+        let Some(network) = self.network.as_deref() else {
+            return;
+        };
+        if self.accumulators.len() <= ply {
+            self.accumulators.resize(ply + 1, Accumulator::default());
+        }
+        let mut accumulator = self.accumulators[ply - 1];
+        accumulator.apply(network, &position.piece_changes);
+        self.accumulators[ply] = accumulator;
+    }
+
+    // NOTE: Scores outside of the window are only bounds
+    fn alpha_beta(&mut self, position: &Position, depth: u16, mut alpha: i16, mut beta: i16) -> i16 {
+        let following_pv = std::mem::take(&mut self.following_pv);
+        self.visit_node(depth);
+        let ply = (self.root_depth - depth) as usize;
+        self.clear_pv(ply);
+
+        if self.stop_calculating {
+            return 0;
+        }
+
+        if position.is_dead_position() || position.is_fifty_move_draw() {
+            return self.draw_score(ply);
+        }
+
+        if ply >= Self::MAX_PLY {
+            return self.evaluate(position, ply).score;
+        }
+
+        // Mate distance pruning
+        alpha = alpha.max(Self::mated_score(ply));
+        beta = beta.min(-Self::mated_score(ply + 1));
+        if alpha >= beta {
+            return alpha;
+        }
+
+        if depth == 0 {
+            return match self.quiescence {
+                true => self.quiescence_search(position, ply, 0, alpha, beta),
+                false => self.evaluate(position, ply).score,
+            };
+        }
+
+        // NOTE: The line of the previous iteration is searched in full, so it still gets a principal variation
+        let tt_entry = self.probe_transposition_table(position, ply);
+        if let Some(entry) = tt_entry.filter(|entry| !following_pv && entry.depth >= depth) {
+            if let Some(score) = entry.cutoff_score(alpha, beta) {
+                return score;
+            }
+        }
+        let original_alpha = alpha;
+
+        let mut hash_move = self.hash_move(ply, following_pv, tt_entry);
+        if hash_move == BitMove::EMPTY && depth >= Self::IID_DEPTH {
+            hash_move = self.internal_iterative_deepening(position, depth, alpha, beta);
+        }
+
+        // NOTE: The children start out without killers, since the ones there were found below other positions
+        if let Some(killers) = self.killer_moves.get_mut(ply + 1) {
+            *killers = [BitMove::EMPTY; 2];
+        }
+        let previous_moves = self.previous_moves(ply);
+        let mut move_picker = MovePicker::new(position, hash_move, self.killers(ply));
+
+        let in_check = position.in_check();
+        let mut found_legal_move = false;
+        let mut quiets_tried = MoveList::<BitMove>::new();
+        while let Some(bit_move) = move_picker.next(|bit_move| {
+            self.continuation_score(previous_moves, (position.get_piece(bit_move.source()), bit_move.target()))
+        }) {
+            let mut position_copy = position.clone();
+            if !position_copy.make_move(bit_move) {
+                continue;
+            }
+            self.update_accumulator(ply + 1, &position_copy);
+
+            let is_quiet = MoveOrdering::is_quiet(position, bit_move);
+            let piece_target = (position.get_piece(bit_move.source()), bit_move.target());
+            let prunes = self.prunes_late_move(depth, in_check, quiets_tried.len())
+                || self.prunes_by_history(depth, in_check, previous_moves, piece_target);
+            if found_legal_move && is_quiet && prunes && !position_copy.in_check() {
+                continue;
+            }
+            found_legal_move = true;
+
+            self.set_move_stack(ply, piece_target);
+            self.following_pv = following_pv && bit_move == hash_move;
+            let score = -self.alpha_beta(&position_copy, depth - 1, -beta, -alpha);
+            if self.stop_calculating {
+                return 0;
+            }
+            if score >= beta {
+                if is_quiet {
+                    self.update_continuation_history(position, depth, previous_moves, piece_target, &quiets_tried);
+                    self.store_killer(ply, bit_move);
+                }
+                // NOTE: The cutoff move is where internal iterative deepening finds it
+                self.update_pv(ply, bit_move);
+                self.store_transposition_table(position, ply, TtEntry { best_move: bit_move, score: beta, depth, bound: Bound::Lower });
+                return beta;
+            }
+            if is_quiet {
+                quiets_tried.add(bit_move);
+            }
+            if score > alpha {
+                alpha = score;
+                self.update_pv(ply, bit_move);
+            }
+        }
+
+        if !found_legal_move {
+            return if position.in_check() { Self::mated_score(ply) } else { self.draw_score(ply) };
+        }
+
+        let entry = match alpha > original_alpha {
+            true => TtEntry { best_move: self.pv_table[ply][0], score: alpha, depth, bound: Bound::Exact },
+            false => TtEntry { best_move: BitMove::EMPTY, score: alpha, depth, bound: Bound::Upper },
+        };
+        self.store_transposition_table(position, ply, entry);
+        alpha
+    }
+
+    // Searches captures, and quiet checks and evasions in the first plies, until the position is quiet
+    // NOTE: Deeper down, checks aren't resolved, searching every evasion made the search tree explode
+    fn quiescence_search(&mut self, position: &Position, ply: usize, quiescence_ply: u16, mut alpha: i16, beta: i16) -> i16 {
+        self.visit_node(self.root_depth);
+        self.seldepth = self.seldepth.max(ply as u16);
+
+        if self.stop_calculating {
+            return 0;
+        }
+
+        if ply >= Self::MAX_PLY {
+            return self.evaluate(position, ply).score;
+        }
+
+        let evading = quiescence_ply <= Self::QUIESCENCE_CHECK_PLIES && position.in_check();
+        if !evading {
+            let stand_pat = self.evaluate(position, ply).score;
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = alpha.max(stand_pat);
+        }
+
+        // NOTE: Only evasions need the quiet moves, otherwise the captures are all that is searched
+        let mut moves = match evading {
+            true => MoveGeneration::generate_pseudo_legal_scoring_moves(position),
+            false => MoveGeneration::generate_pseudo_legal_scoring_captures(position),
+        };
+        MoveOrdering::score_moves(position, &mut moves, BitMove::EMPTY, |_| 0);
+        let quiet_checks = match !evading && quiescence_ply < Self::QUIESCENCE_CHECK_PLIES {
+            true => MoveGeneration::generate_quiet_checks(position),
+            false => MoveList::new(),
+        };
+
+        let mut found_legal_move = false;
+        for index in 0..moves.len() + quiet_checks.len() {
+            let bit_move = if index < moves.len() {
+                let bit_move = moves.pick_best(index).bit_move;
+                if !evading && See::see(position, bit_move) < 0 {
+                    continue;
+                }
+                bit_move
+            } else {
+                quiet_checks[index - moves.len()]
+            };
+            let mut position_copy = position.clone();
+            if !position_copy.make_move(bit_move) {
+                continue;
+            }
+            self.update_accumulator(ply + 1, &position_copy);
+            found_legal_move = true;
+
+            let score = -self.quiescence_search(&position_copy, ply + 1, quiescence_ply + 1, -beta, -alpha);
+            if self.stop_calculating {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+
+        if evading && !found_legal_move {
+            return Self::mated_score(ply);
+        }
+
+        alpha
+    }
+
+    // Like alpha_beta, but skips the moves of the other lines
+    // NOTE: With a temperature, every move gets a full window, since sampling needs exact scores
+    fn alpha_beta_root(&mut self, position: &Position, depth: u16, excluded_moves: &[BitMove], (window_alpha, window_beta): (i16, i16)) -> ScoringMove {
+        self.nodes += 1;
+        self.total_nodes += 1;
+        self.node_counter.increment();
+
+        let mut moves = MoveGeneration::generate_pseudo_legal_scoring_moves(position);
+        MoveOrdering::score_moves(position, &mut moves, self.hash_move(0, true, None), |_| 0);
+
+        let mut best_move: Option<ScoringMove> = None;
+        let mut root_moves = Vec::new();
+        for index in 0..moves.len() {
+            let mut root_move = moves.pick_best(index);
+            if excluded_moves.contains(&root_move.bit_move) {
+                continue;
+            }
+            let mut position_copy = position.clone();
+            if !position_copy.make_move(root_move.bit_move) {
+                continue;
+            }
+            self.update_accumulator(1, &position_copy);
+            self.report_current_move(depth, root_move.bit_move, root_moves.len() + 1);
+
+            let alpha = match best_move {
+                Some(best_move) if self.temperature == 0 => best_move.score.max(window_alpha),
+                _ => window_alpha,
+            };
+            self.set_move_stack(0, (position.get_piece(root_move.bit_move.source()), root_move.bit_move.target()));
+            let following_pv = self.previous_pv.first() == Some(&root_move.bit_move);
+            self.following_pv = following_pv;
+            root_move.score = -self.alpha_beta(&position_copy, depth - 1, -window_beta, -alpha);
+
+            // The previous best move got refuted, so panic time is granted before the iteration runs out
+            if following_pv && root_move.score <= window_alpha && !self.stop_calculating && self.is_near_time_limit() {
+                self.grant_panic_time();
+            }
+
+            // NOTE: Moves that fail low score exactly alpha, so only strictly better moves replace the best one
+            if best_move.is_none_or(|best_move| root_move.score > best_move.score) {
+                best_move = Some(root_move);
+                self.update_pv(0, root_move.bit_move);
+                self.root_pv = self.pv_table[0].clone();
+            }
+            root_moves.push(root_move);
+            if root_move.score >= window_beta {
+                break;
+            }
+        }
+
+        let best_move = best_move.unwrap_or_else(|| {
+            if !excluded_moves.is_empty() {
+                ScoringMove::blank(i16::MIN)
+            } else if position.in_check() {
+                ScoringMove::blank(Self::mated_score(0))
+            } else {
+                ScoringMove::blank(self.draw_score(0))
+            }
+        });
+
+        if excluded_moves.is_empty() && !self.stop_calculating {
+            self.root_moves = root_moves;
+        }
+
+        best_move
+    }
+
+    fn report_current_move(&self, depth: u16, bit_move: BitMove, move_number: usize) {
+        if self.output == SearchOutput::Uci && !self.stop_calculating && self.time_manager.elapsed() >= Self::CURRMOVE_DELAY_MILLIS {
+            pl!(format!("info depth {} currmove {} currmovenumber {}", depth, bit_move.to_uci_string(), move_number));
+        }
+    }
+
+    #[inline(always)]
+    fn killers(&self, ply: usize) -> [BitMove; 2] {
+        self.killer_moves.get(ply).copied().unwrap_or([BitMove::EMPTY; 2])
+    }
+
+    fn store_killer(&mut self, ply: usize, bit_move: BitMove) {
+        if self.killer_moves.len() <= ply {
+            self.killer_moves.resize(ply + 1, [BitMove::EMPTY; 2]);
+        }
+        let killers = &mut self.killer_moves[ply];
+        if killers[0] != bit_move {
+            *killers = [bit_move, killers[0]];
+        }
+    }
+
+    #[inline(always)]
+    fn previous_moves(&self, ply: usize) -> [Option<PieceTarget>; 2] {
+        [
+            ply.checked_sub(1).and_then(|index| self.move_stack[index]),
+            ply.checked_sub(2).and_then(|index| self.move_stack[index]),
+        ]
+    }
+
+    #[inline(always)]
+    fn set_move_stack(&mut self, ply: usize, piece_target: PieceTarget) {
+        if self.move_stack.len() <= ply {
+            self.move_stack.resize(ply + 1, None);
+        }
+        self.move_stack[ply] = Some(piece_target);
+    }
+
+    // NOTE: Stays within ContinuationHistory::MAX, so below the promotions in the move ordering
+    #[inline(always)]
+    fn continuation_score(&self, [previous, before_previous]: [Option<PieceTarget>; 2], piece_target: PieceTarget) -> i16 {
+        let score = self.continuation_history.get(previous, piece_target) as i32
+            + self.continuation_history.get(before_previous, piece_target) as i32;
+        (score / 2) as i16
+    }
+
+    // History pruning, which skips quiet moves that keep failing after the same previous moves
+    #[inline(always)]
+    fn prunes_by_history(&self, depth: u16, in_check: bool, previous_moves: [Option<PieceTarget>; 2], piece_target: PieceTarget) -> bool {
+        self.pruning
+            && depth <= Self::HISTORY_PRUNING_DEPTH
+            && !in_check
+            && (self.continuation_score(previous_moves, piece_target) as i32) < -Self::HISTORY_PRUNING_MARGIN * depth as i32
+    }
+
+    // Late move pruning, which searches more quiet moves the deeper the node is
+    #[inline(always)]
+    fn prunes_late_move(&self, depth: u16, in_check: bool, quiets_tried: usize) -> bool {
+        self.pruning
+            && depth <= Self::LATE_MOVE_PRUNING_DEPTH
+            && !in_check
+            && quiets_tried >= Self::LATE_MOVE_PRUNING_BASE + (depth * depth) as usize
+    }
+
+    // The cutoff move gets a bonus and the quiet moves searched before it the same amount as a malus
+    fn update_continuation_history(
+        &mut self,
+        position: &Position,
+        depth: u16,
+        [previous, before_previous]: [Option<PieceTarget>; 2],
+        piece_target: PieceTarget,
+        quiets_tried: &MoveList<BitMove>,
+    ) {
+        let bonus = (depth as i32 * depth as i32 * 32).min(1536);
+        for earlier_move in [previous, before_previous] {
+            self.continuation_history.update(earlier_move, piece_target, bonus);
+            for quiet in quiets_tried.iter() {
+                let quiet_piece_target = (position.get_piece(quiet.source()), quiet.target());
+                self.continuation_history.update(earlier_move, quiet_piece_target, -bonus);
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn hash_move(&self, ply: usize, following_pv: bool, tt_entry: Option<TtEntry>) -> BitMove {
+        match following_pv {
+            true => self.previous_pv.get(ply).copied().unwrap_or(BitMove::EMPTY),
+            false => tt_entry.map_or(BitMove::EMPTY, |entry| entry.best_move),
+        }
+    }
+
+    #[inline(always)]
+    fn probe_transposition_table(&self, position: &Position, ply: usize) -> Option<TtEntry> {
+        let mut entry = self.transposition_table.as_ref()?.probe(position.zobrist_key)?;
+        entry.score = Self::score_from_tt(entry.score, ply);
+        Some(entry)
+    }
+
+    #[inline(always)]
+    fn store_transposition_table(&self, position: &Position, ply: usize, mut entry: TtEntry) {
+        if let Some(transposition_table) = &self.transposition_table {
+            entry.score = Self::score_to_tt(entry.score, ply);
+            transposition_table.store(position.zobrist_key, entry);
+        }
+    }
+
+    // Internal iterative deepening
+    fn internal_iterative_deepening(&mut self, position: &Position, depth: u16, alpha: i16, beta: i16) -> BitMove {
+        let ply = (self.root_depth - depth) as usize;
+        // NOTE: The ply is derived from the depth, so the root moves up to keep it the same
+        self.root_depth -= Self::IID_REDUCTION;
+        self.alpha_beta(position, depth - Self::IID_REDUCTION, alpha, beta);
+        self.root_depth += Self::IID_REDUCTION;
+        self.pv_table[ply].first().copied().unwrap_or(BitMove::EMPTY)
+    }
+
+    #[inline(always)]
+    fn clear_pv(&mut self, ply: usize) {
+        if self.pv_table.len() <= ply + 1 {
+            self.pv_table.resize_with(ply + 2, Vec::new);
+        }
+        self.pv_table[ply].clear();
+    }
+
+    #[inline(always)]
+    fn update_pv(&mut self, ply: usize, bit_move: BitMove) {
+        if self.pv_table.len() <= ply + 1 {
+            self.pv_table.resize_with(ply + 2, Vec::new);
+        }
+        let (current, next) = self.pv_table.split_at_mut(ply + 1);
+        current[ply].clear();
+        current[ply].push(bit_move);
+        current[ply].extend_from_slice(&next[0]);
+    }
+
+    // NOTE: The random and minimax searches don't collect lines, so only the move is returned
+    fn take_root_pv(&mut self, root_move: BitMove) -> Vec<BitMove> {
+        let root_pv = std::mem::take(&mut self.root_pv);
+        if root_pv.first() == Some(&root_move) {
+            root_pv
+        } else {
+            vec![root_move]
+        }
+    }
+
+    // Like minimax_best_move, but skips the moves of the other lines and keeps the root scores
+    fn minimax_root(&mut self, position: &Position, depth: u16, excluded_moves: &[BitMove], _window: (i16, i16)) -> ScoringMove {
+        self.nodes += 1;
+        self.total_nodes += 1;
+        self.node_counter.increment();
+
+        let mut move_number = 0;
+        let root_moves: Vec<ScoringMove> = MoveGeneration::generate_pseudo_legal_scoring_moves(position)
+            .into_iter()
+            .filter(|m| !excluded_moves.contains(&m.bit_move))
+            .filter_map(|mut m: ScoringMove| {
+                let mut position_copy = position.clone();
+                if position_copy.make_move(m.bit_move) {
+                    move_number += 1;
+                    self.update_accumulator(1, &position_copy);
+                    self.report_current_move(depth, m.bit_move, move_number);
+                    m.score = -self.minimax_best_move(&position_copy, depth - 1).score;
+                    Some(m)
                 } else {
-                    ScoringMove::blank(0)
+                    None
                 }
             })
+            .collect();
+
+        let best_move = root_moves
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or_else(|| {
+                if !excluded_moves.is_empty() {
+                    ScoringMove::blank(i16::MIN)
+                } else if position.in_check() {
+                    ScoringMove::blank(Self::mated_score(0))
+                } else {
+                    ScoringMove::blank(self.draw_score(0))
+                }
+            });
+
+        if excluded_moves.is_empty() && !self.stop_calculating {
+            self.root_moves = root_moves;
+        }
+
+        best_move
     }
 
-    fn best_scoring_move(&mut self, position: &mut Position, depth: u8) -> ScoringMove {
+    // Samples a root move from a softmax over the root scores, which diversifies games
+    fn select_root_move(&mut self, best_move: ScoringMove) -> ScoringMove {
+        if self.temperature == 0 || self.root_moves.is_empty() {
+            return best_move;
+        }
+
+        let temperature = self.temperature as f64;
+        let max_score = self.root_moves.iter().max().unwrap().score as f64;
+        let weights: Vec<f64> = self.root_moves
+            .iter()
+            .map(|m| ((m.score as f64 - max_score) / temperature).exp())
+            .collect();
+
+        let mut pick = self.rng.random::<f64>() * weights.iter().sum::<f64>();
+        for (root_move, weight) in self.root_moves.iter().zip(weights) {
+            if pick < weight {
+                return *root_move;
+            }
+            pick -= weight;
+        }
+
+        best_move
+    }
+
+    fn best_scoring_move(&mut self, position: &mut Position, depth: u16, excluded_moves: &[BitMove], window: (i16, i16)) -> ScoringMove {
         #[cfg(feature = "search_random")]
-        return self.random_best_move(position, depth);
+        return self.random_best_move(position, depth, excluded_moves, window);
         
         #[cfg(feature = "search_minimax")]
-        return self.minimax_best_move(position, depth);
+        return self.minimax_root(position, depth, excluded_moves, window);
+
+        #[cfg(feature = "search_alpha_beta")]
+        return self.alpha_beta_root(position, depth, excluded_moves, window);
+    }
+
+    // Aspiration windows around the previous score, searched again with an open window on a fail
+    fn search_line(&mut self, position: &mut Position, depth: u16, excluded_moves: &[BitMove], line_index: usize) -> ScoringMove {
+        let window = self.aspiration_window(depth, line_index);
+        let line = self.best_scoring_move(position, depth, excluded_moves, window);
+        if self.stop_calculating || window.0 < line.score && line.score < window.1 {
+            return line;
+        }
+        self.best_scoring_move(position, depth, excluded_moves, Self::FULL_WINDOW)
+    }
+
+    // NOTE: Mates and sampled moves need exact scores, so they get an open window
+    fn aspiration_window(&self, depth: u16, line_index: usize) -> (i16, i16) {
+        match self.previous_score {
+            Some(score) if cfg!(feature = "search_alpha_beta")
+                && line_index == 0
+                && depth >= Self::ASPIRATION_DEPTH
+                && self.temperature == 0
+                && !Self::is_mate_score(score) => (score - Self::ASPIRATION_WINDOW, score + Self::ASPIRATION_WINDOW),
+            _ => Self::FULL_WINDOW,
+        }
+    }
+
+    fn is_near_time_limit(&self) -> bool {
+        !self.pondering && self.time_manager.elapsed().saturating_mul(2) >= self.time_manager.soft_limit()
+    }
+
+    // Returns whether the first line was completed, since the lines can only be used then
+    fn search_lines(&mut self, position: &mut Position, depth: u16, lines: &mut Vec<SearchLine>) -> bool {
+        let legal_moves = MoveGeneration::generate_legal_moves(position).len();
+        let mut excluded_moves = Vec::new();
+
+        self.root_depth = depth;
+        let mut first_line_completed = false;
+
+        for line_index in 0..self.multipv.min(legal_moves.max(1)) {
+            self.line_stop_time = self.line_stop_time(line_index);
+            self.stop_calculating = false;
+
+            let line = self.search_line(position, depth, &excluded_moves, line_index);
+            if self.stop_calculating || line.bit_move == BitMove::EMPTY && line_index > 0 {
+                lines.truncate(line_index.max(1));
+                break;
+            }
+
+            excluded_moves.push(line.bit_move);
+            let line = SearchLine { pv: self.take_root_pv(line.bit_move), best_move: line };
+            if line_index < lines.len() {
+                lines[line_index] = line;
+            } else {
+                lines.push(line);
+            }
+            first_line_completed = true;
+        }
+
+        self.check_ponderhit();
+        self.stop_calculating = !self.pondering && self.time_manager.should_stop()
+            || self.shared.is_stopped()
+            || self.total_nodes >= self.node_limit
+            || lines.is_empty();
+
+        first_line_completed
+    }
+
+    fn update_node_stats(&mut self) {
+        self.node_counter.flush();
+        self.nps_meter.record(self.time_manager.elapsed(), self.node_counter.total());
+    }
+
+    fn print_lines(&mut self, position: &Position, depth: u16, lines: &[SearchLine]) {
+        if self.output == SearchOutput::Silent {
+            return;
+        }
+
+        // NOTE: The node count covers all iterations and threads so far, like the time does
+        self.update_node_stats();
+        let time = self.time_manager.elapsed();
+        let nodes = self.node_counter.total();
+        let nps = self.nps_meter.nps(time, nodes);
+        // NOTE: A resumed analysis reports the time and nodes of the whole analysis, not just this run
+        let (time, nodes) = (time + self.time_offset, nodes + self.nodes_offset);
+        let hashfull = match &self.transposition_table {
+            Some(transposition_table) => format!(" hashfull {}", transposition_table.hashfull()),
+            None => String::new(),
+        };
+
+        for (line_index, line) in lines.iter().enumerate() {
+            let score = line.best_move.score;
+            let pv: Vec<String> = Self::verified_pv(position, &line.pv)
+                .iter()
+                .map(|m| m.to_uci_string())
+                .collect();
+
+            // NOTE: Xboard expects "ply score time nodes pv" with the time in centiseconds, and only knows one line
+            if self.output == SearchOutput::Xboard {
+                if line_index == 0 {
+                    pl!(format!("{} {} {} {} {}", depth, score, time / 10, nodes, pv.join(" ")));
+                }
+                continue;
+            }
+
+            let pv = if pv.is_empty() { String::new() } else { format!(" pv {}", pv.join(" ")) };
+            let score = if self.show_wdl {
+                format!("{} {}", Self::uci_score(score), Wdl::from_score(score, Wdl::game_ply(position)).to_uci_string())
+            } else {
+                Self::uci_score(score)
+            };
+            // NOTE: The engine has no tablebases, so tbhits is always 0
+            if self.multipv > 1 {
+                pl!(format!("info depth {} seldepth {} multipv {} score {} nodes {} nps {}{} tbhits 0 time {}{}", depth, self.seldepth, line_index + 1, score, nodes, nps, hashfull, time, pv));
+            } else {
+                pl!(format!("info depth {} seldepth {} score {} nodes {} nps {}{} tbhits 0 time {}{}", depth, self.seldepth, score, nodes, nps, hashfull, time, pv));
+            }
+        }
+    }
+
+    // Cuts the line off at the first illegal move, so GUIs are never sent an illegal PV
+    fn verified_pv(position: &Position, pv: &[BitMove]) -> Vec<BitMove> {
+        let mut scratch_position = position.clone();
+        let mut verified_pv = Vec::new();
+
+        for &bit_move in pv {
+            let is_legal = MoveGeneration::generate_legal_moves(&scratch_position)
+                .iter()
+                .any(|&m| m == bit_move);
+            if !is_legal {
+                break;
+            }
+            scratch_position.make_move(bit_move);
+            verified_pv.push(bit_move);
+        }
+
+        verified_pv
     }
     
-    pub fn go(&mut self, position: &mut Position, depth: u8) {
-        //TODO: Implement conditional iterative deepening here
-        println!("Searching for best move within {} milliseconds", self.stop_time);
-
-        #[cfg(feature = "iterative_deepening")]
-        {
-            let mut best_scoring_move = ScoringMove::blank(13243);
-            for current_depth in 1..=depth {
-                self.nodes = 0;
-                let new_best_move = self.best_scoring_move(position, current_depth);
-                if self.stop_calculating {
-                    break
+    // NOTE: Passing Search::MAX_DEPTH searches until the time runs out or MAX_PLY is reached
+    pub fn go(&mut self, position: &mut Position, depth: u16) -> BitMove {
+        if self.time_manager.soft_limit() != u128::MAX && self.output == SearchOutput::Uci {
+            pl!(format!("info string searching for best move within {} milliseconds", self.time_manager.soft_limit()));
+        }
+
+        self.set_up_network(position);
+        if let Some(transposition_table) = &self.transposition_table {
+            transposition_table.new_search();
+        }
+
+        // NOTE: Only the main thread stops the helpers, whatever the limits
+        let shared = self.shared.clone();
+        let lines = thread::scope(|scope| {
+            let _stop_helpers = self.main_thread.then(|| shared.stop_on_drop());
+            for _index in 1..self.threads {
+                let mut helper = self.helper();
+                #[cfg(test)]
+                {
+                    helper.panics = _index <= self.panicking_helpers;
+                }
+                let helper_position = position.clone();
+                scope.spawn(move || helper.go_isolated(&helper_position, Self::MAX_DEPTH));
+            }
+
+            #[cfg(feature = "iterative_deepening")]
+            let lines = self.iterative_deepening(position, depth);
+
+            #[cfg(feature = "no_iterative_deepening")]
+            let lines = self.fixed_depth_search(position, depth);
+
+            self.wait_while_pondering();
+            lines
+        });
+
+        let best_move = self.select_root_move(lines[0].best_move);
+        self.best_score = best_move.score;
+        let best_move = best_move.bit_move;
+        let best_move = if best_move == BitMove::EMPTY { Self::fallback_move(position) } else { best_move };
+        self.print_best_move(best_move);
+        best_move
+    }
+
+    // Lazy SMP: the helpers only share the table, the node count and the stop with this thread
+    fn helper(&self) -> Search {
+        let mut helper = Search::new(u128::MAX)
+            .with_output(SearchOutput::Silent)
+            .with_shared(self.shared.clone())
+            .with_contempt(self.contempt)
+            .with_nnue(self.use_nnue)
+            .with_network(self.network.clone())
+            .with_eval_blend(self.eval_blend);
+        helper.main_thread = false;
+        helper.transposition_table = self.transposition_table.clone();
+        helper.eval_weights = self.eval_weights;
+        helper
+    }
+
+    // NOTE: A stopped search returns the lines of the last completed iteration
+    fn iterative_deepening(&mut self, position: &mut Position, depth: u16) -> Vec<SearchLine> {
+        let (mut lines, first_depth) = match self.resumed_checkpoint.take() {
+            Some(checkpoint) => self.resume(checkpoint),
+            None => (vec![SearchLine::blank(13243)], 1),
+        };
+        let mut completed_depth = first_depth - 1;
+        let mut scores = Vec::new();
+        let only_move = MoveGeneration::generate_legal_moves(position).len() == 1;
+        let (mut previous_best_move, mut stable_iterations) = (BitMove::EMPTY, 0);
+        for current_depth in first_depth..=depth.min(Self::MAX_PLY as u16) {
+            self.nodes = 0;
+            self.seldepth = 0;
+            self.previous_pv = lines[0].pv.clone();
+            self.previous_score = (completed_depth > 0).then_some(lines[0].best_move.score);
+            let mut new_lines = lines.clone();
+            if self.search_lines(position, current_depth, &mut new_lines) {
+                lines = new_lines;
+                self.print_lines(position, current_depth, &lines);
+                completed_depth = current_depth;
+                if self.time_manager.elapsed() >= self.last_checkpoint_time + self.checkpoint_interval {
+                    self.write_checkpoint(position, completed_depth, &lines);
+                }
+
+                scores.push(lines[0].best_move.score);
+                if let [.., earlier_score, _, score] = scores[..] {
+                    if score <= earlier_score.saturating_sub(Self::PANIC_SCORE_DROP) {
+                        self.grant_panic_time();
+                    }
+                }
+
+                let best_move = lines[0].best_move.bit_move;
+                stable_iterations = if best_move == previous_best_move { stable_iterations + 1 } else { 0 };
+                previous_best_move = best_move;
+                self.adapt_time(stable_iterations, only_move);
+            }
+            if self.stop_calculating {
+                break
+            }
+        }
+        if completed_depth > self.checkpointed_depth {
+            self.write_checkpoint(position, completed_depth, &lines);
+        }
+        lines
+    }
+
+    fn resume(&mut self, checkpoint: AnalysisCheckpoint) -> (Vec<SearchLine>, u16) {
+        self.time_offset = checkpoint.time;
+        self.nodes_offset = checkpoint.nodes;
+        self.checkpointed_depth = checkpoint.depth;
+        let lines = checkpoint.lines
+            .into_iter()
+            .filter(|line| !line.pv.is_empty())
+            .map(|line| SearchLine { best_move: ScoringMove { bit_move: line.pv[0], score: line.score }, pv: line.pv })
+            .collect();
+        (lines, checkpoint.depth.saturating_add(1))
+    }
+
+    fn write_checkpoint(&mut self, position: &Position, depth: u16, lines: &[SearchLine]) {
+        let Some(path) = &self.checkpoint_path else {
+            return;
+        };
+
+        self.node_counter.flush();
+        let checkpoint = AnalysisCheckpoint {
+            fen: position.to_fen(),
+            depth,
+            time: self.time_manager.elapsed() + self.time_offset,
+            nodes: self.node_counter.total() + self.nodes_offset,
+            lines: lines.iter().map(|line| CheckpointLine { score: line.best_move.score, pv: line.pv.clone() }).collect(),
+        };
+        // NOTE: A failed write doesn't stop the analysis, the next checkpoint may still succeed
+        if let Err(error) = checkpoint.write(path) {
+            match self.output {
+                SearchOutput::Uci => pl!(format!("info string couldn't write checkpoint: {}", error)),
+                _ => eprintln!("Couldn't write checkpoint: {}", error),
+            }
+        }
+        self.last_checkpoint_time = self.time_manager.elapsed();
+        self.checkpointed_depth = depth;
+    }
+
+    // NOTE: Scores swing between odd and even depths, so the drop is measured two plies back
+    fn grant_panic_time(&mut self) {
+        if !self.time_manager.extend() {
+            return;
+        }
+
+        if self.output == SearchOutput::Uci {
+            pl!(format!("info string score dropped, extending search to {} milliseconds", self.time_manager.soft_limit()));
+        }
+        // NOTE: Only granted during the first line or between iterations
+        self.line_stop_time = self.line_stop_time(0);
+        if !self.time_manager.should_stop() && !self.shared.is_stopped() {
+            self.stop_calculating = false;
+        }
+    }
+
+    // Takes more time while the best move keeps changing, and less once it's stable
+    fn adapt_time(&mut self, stable_iterations: usize, only_move: bool) {
+        let percent = match only_move {
+            true => 0,
+            false => Self::STABILITY_TIME_PERCENTS[stable_iterations.min(Self::STABILITY_TIME_PERCENTS.len() - 1)],
+        };
+        self.time_manager.scale(percent);
+        if !self.pondering {
+            self.stop_calculating = self.time_manager.should_stop()
+                || self.shared.is_stopped()
+                || self.total_nodes >= self.node_limit;
+        }
+    }
+
+    fn fixed_depth_search(&mut self, position: &mut Position, depth: u16) -> Vec<SearchLine> {
+        let mut lines = vec![SearchLine::blank(13243)];
+        if self.search_lines(position, depth, &mut lines) {
+            self.print_lines(position, depth, &lines);
+        }
+        lines
+    }
+
+    // Contains panics in the search, and still reports a legal fallback move
+    pub fn go_isolated(&mut self, position: &Position, depth: u16) -> BitMove {
+        let mut position_copy = position.clone();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.go(&mut position_copy, depth)));
+
+        match result {
+            Ok(best_move) => best_move,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown panic");
+                if self.output == SearchOutput::Uci {
+                    pl!(format!("info string search panicked: {} (fen {})", message, position.to_fen()));
+                } else {
+                    eprintln!("Search panicked: {} (fen {})", message, position.to_fen());
+                }
+                if let Some(repro_bundle) = &self.repro_bundle {
+                    self.report_repro_bundle(repro_bundle.write(message));
                 }
-                best_scoring_move = new_best_move;
-                pl!(format!("info depth {} score cp {} nodes {} time {} pv {}", current_depth, best_scoring_move.score, self.nodes, self.timer.get_time_passed_millis(), best_scoring_move.bit_move.to_uci_string()));
+
+                let fallback_move = Self::fallback_move(position);
+                self.print_best_move(fallback_move);
+                fallback_move
+            }
+        }
+    }
+
+    fn report_repro_bundle(&self, written: io::Result<PathBuf>) {
+        let report = match written {
+            Ok(path) => format!("reproduction bundle written to {}, replay it with repro {}", path.display(), path.display()),
+            Err(error) => format!("couldn't write reproduction bundle: {}", error),
+        };
+        match self.output {
+            SearchOutput::Uci => pl!(format!("info string {}", report)),
+            _ => eprintln!("{}", report),
+        }
+    }
+
+    fn print_best_move(&self, best_move: BitMove) {
+        if self.output == SearchOutput::Uci {
+            pl!(format!("bestmove {}", Self::move_to_uci_string(best_move)));
+        }
+    }
+
+    // Any legal move, used when the search couldn't come up with one, or BitMove::EMPTY if there are none
+    fn fallback_move(position: &Position) -> BitMove {
+        let legal_moves = MoveGeneration::generate_legal_moves(position);
+        if legal_moves.len() > 0 {
+            legal_moves[0]
+        } else {
+            BitMove::EMPTY
+        }
+    }
+
+    // NOTE: UCI uses 0000 for the null move, which is reported when there are no legal moves
+    fn move_to_uci_string(bit_move: BitMove) -> String {
+        if bit_move == BitMove::EMPTY {
+            "0000".to_owned()
+        } else {
+            bit_move.to_uci_string()
+        }
+    }
+
+    pub const MAX_DEPTH: u16 = u16::MAX;
+    const HISTORY_PRUNING_DEPTH: u16 = 2;
+    const HISTORY_PRUNING_MARGIN: i32 = 2048;
+    const LATE_MOVE_PRUNING_DEPTH: u16 = 3;
+    const LATE_MOVE_PRUNING_BASE: usize = 3;
+    const IID_DEPTH: u16 = 5;
+    const QUIESCENCE_CHECK_PLIES: u16 = 1;
+    const IID_REDUCTION: u16 = 2;
+
+    // NOTE: Even a slow search gets through this many nodes in about a millisecond
+    const DEFAULT_CHECK_INTERVAL: u64 = 2048;
+    // NOTE: Earlier iterations finish too quickly for the current move to be worth reporting
+    const CURRMOVE_DELAY_MILLIS: u128 = 3000;
+
+    const PANIC_SCORE_DROP: i16 = 50;
+    // NOTE: With the window as wide as the panic drop, a root fail-low means the score dropped that much
+    const ASPIRATION_DEPTH: u16 = 4;
+    const ASPIRATION_WINDOW: i16 = Self::PANIC_SCORE_DROP;
+    const STABILITY_TIME_PERCENTS: [u128; 8] = [150, 125, 110, 100, 90, 80, 70, 60];
+
+    const INFINITY: i16 = 30000;
+    const FULL_WINDOW: (i16, i16) = (-Self::INFINITY, Self::INFINITY);
+
+    // Mated at a later ply scores -MATE + ply, so shorter mates are preferred
+    pub const MATE: i16 = 10000;
+    // NOTE: The search stops at MAX_PLY, which keeps every mate score within the mate band
+    pub const MAX_PLY: usize = 512;
+    const MAX_MATE_PLY: i16 = Self::MAX_PLY as i16;
+
+    #[inline(always)]
+    fn mated_score(ply: usize) -> i16 {
+        -Self::MATE + ply as i16
+    }
+
+    // The contempt is from the perspective of the side to move at the root
+    #[inline(always)]
+    fn draw_score(&self, ply: usize) -> i16 {
+        if ply.is_multiple_of(2) { -self.contempt } else { self.contempt }
+    }
+
+    // NOTE: Mate scores are stored relative to the node, since the position can be reached at another ply
+    #[inline(always)]
+    fn score_to_tt(score: i16, ply: usize) -> i16 {
+        match score {
+            _ if score >= Self::MATE - Self::MAX_MATE_PLY => score + ply as i16,
+            _ if score <= -Self::MATE + Self::MAX_MATE_PLY => score - ply as i16,
+            _ => score,
+        }
+    }
+
+    #[inline(always)]
+    fn score_from_tt(score: i16, ply: usize) -> i16 {
+        match score {
+            _ if score >= Self::MATE - Self::MAX_MATE_PLY => score - ply as i16,
+            _ if score <= -Self::MATE + Self::MAX_MATE_PLY => score + ply as i16,
+            _ => score,
+        }
+    }
+
+    pub fn best_score(&self) -> i16 {
+        self.best_score
+    }
+
+    #[inline(always)]
+    pub fn is_mate_score(score: i16) -> bool {
+        score.abs() >= Self::MATE - Self::MAX_MATE_PLY
+    }
+
+    fn uci_score(score: i16) -> String {
+        if score >= Self::MATE - Self::MAX_MATE_PLY {
+            format!("mate {}", (Self::MATE - score + 1) / 2)
+        } else if score <= -Self::MATE + Self::MAX_MATE_PLY {
+            format!("mate {}", -(Self::MATE + score) / 2)
+        } else {
+            format!("cp {}", score)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_masks};
+
+    use super::*;
+
+    #[test]
+    fn stopped_search_still_returns_legal_move() {
+        move_masks::init();
+        let position = Position::starting_position();
+        let shared = Arc::new(SearchShared::default());
+        shared.stop();
+
+        let best_move = Search::new(u128::MAX)
+            .with_shared(shared)
+            .go_isolated(&position, Search::MAX_DEPTH);
+
+        assert!(MoveGeneration::generate_legal_moves(&position).iter().any(|&m| m == best_move));
+    }
+
+    #[test]
+    fn stop_is_polled_every_check_interval() {
+        let shared = Arc::new(SearchShared::default());
+        let mut search = Search::new(u128::MAX).with_shared(shared.clone());
+        for _ in 0..Search::DEFAULT_CHECK_INTERVAL {
+            search.visit_node(0);
+        }
+
+        shared.stop();
+        for _ in 1..Search::DEFAULT_CHECK_INTERVAL {
+            search.visit_node(0);
+        }
+        search.visit_node(0);
+        assert!(search.stop_calculating);
+    }
+
+    #[test]
+    fn search_on_the_clock_stops_early_with_a_single_legal_move() {
+        move_masks::init();
+        let position = Position::from_fen("6rk/8/8/8/8/8/8/r6K w - - 0 1").unwrap();
+        assert_eq!(MoveGeneration::generate_legal_moves(&position).len(), 1);
+
+        let timer = std::time::Instant::now();
+        let best_move = Search::new(20_000)
+            .with_output(SearchOutput::Silent)
+            .with_hard_time_limit(60_000)
+            .go_isolated(&position, Search::MAX_DEPTH);
+        assert_eq!(best_move, MoveGeneration::generate_legal_moves(&position)[0]);
+        assert!(timer.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn pondering_search_ignores_time_until_ponderhit() {
+        move_masks::init();
+        let position = Position::starting_position();
+        let ponder_flag = Arc::new(AtomicBool::new(true));
+
+        let mut search = Search::new(0).with_ponder_flag(ponder_flag.clone());
+        let search_thread = thread::spawn(move || search.go_isolated(&position, 2));
+
+        thread::sleep(Duration::from_millis(100));
+        assert!(!search_thread.is_finished());
+
+        ponder_flag.store(false, Ordering::Relaxed);
+        let best_move = search_thread.join().unwrap();
+        assert_ne!(best_move, BitMove::EMPTY);
+    }
+
+    #[test]
+    fn alpha_beta_finds_minimax_score() {
+        move_masks::init();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            for depth in 1..=3 {
+                let mut minimax = Search::new(u128::MAX);
+                minimax.root_depth = depth;
+                // NOTE: Pruning and the quiescence search change the scores
+                let mut alpha_beta = Search::new(u128::MAX);
+                alpha_beta.root_depth = depth;
+                alpha_beta.pruning = false;
+                alpha_beta.quiescence = false;
+
+                let expected = minimax.minimax_root(&position, depth, &[], Search::FULL_WINDOW).score;
+                assert_eq!(alpha_beta.alpha_beta_root(&position, depth, &[], Search::FULL_WINDOW).score, expected, "{fen} at depth {depth}");
+                assert!(alpha_beta.total_nodes <= minimax.total_nodes);
             }
-            pl!(format!("bestmove {}", best_scoring_move.bit_move.to_uci_string()));
         }
+    }
+
+    #[test]
+    fn verified_pv_stops_at_first_illegal_move() {
+        move_masks::init();
+        let position = Position::starting_position();
+        let e2e4 = BitMove::from_uci(&position, "e2e4").unwrap();
+        let mut position_after_e2e4 = position.clone();
+        position_after_e2e4.make_move(e2e4);
+        let e7e5 = BitMove::from_uci(&position_after_e2e4, "e7e5").unwrap();
+
+        assert_eq!(Search::verified_pv(&position, &[e2e4, e7e5]), vec![e2e4, e7e5]);
+        assert_eq!(Search::verified_pv(&position, &[e2e4, e2e4, e7e5]), vec![e2e4]);
+        assert_eq!(Search::verified_pv(&position, &[BitMove::EMPTY, e2e4]), vec![]);
+    }
 
-        #[cfg(feature = "no_iterative_deepening")]
-        {
-            let best_scoring_move = self.best_scoring_move(position, depth);
-            pl!(format!("info depth {} score cp {} nodes {} time {} pv {}", depth, best_scoring_move.score, self.nodes, self.timer.get_time_passed_millis(), best_scoring_move.bit_move.to_uci_string()));
-            pl!(format!("bestmove {}", best_scoring_move.bit_move.to_uci_string()));
+    #[test]
+    fn mate_scores_count_the_moves_to_mate() {
+        move_masks::init();
+        let position = Position::from_fen("6k1/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        // NOTE: At depth 3, the mate at the leaf is found by the quiescence search
+        for depth in 3..=5 {
+            let mut search = Search::new(u128::MAX);
+            search.root_depth = depth;
+            let best_move = search.alpha_beta_root(&position, depth, &[], Search::FULL_WINDOW);
+            assert_eq!(best_move.score, Search::MATE - 3, "at depth {depth}");
+            assert_eq!(Search::uci_score(best_move.score), "mate 2");
         }
+        assert_eq!(Search::uci_score(Search::mated_score(2)), "mate -1");
+        assert_eq!(Search::uci_score(-35), "cp -35");
     }
 
-    const AVERAGE_AMOUNT_OF_MOVES: u128 = 30;
-    const TIME_OFFSET: u128 = 100;
+    // NOTE: Only the alpha-beta search uses the table
+    #[cfg(feature = "search_alpha_beta")]
+    #[test]
+    fn helper_threads_share_the_table_and_keep_the_mate_distance() {
+        move_masks::init();
+        let position = Position::from_fen("6k1/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        for threads in [1, 4] {
+            let transposition_table = Arc::new(TranspositionTable::new(1));
+            let mut search = Search::new(u128::MAX)
+                .with_output(SearchOutput::Silent)
+                .with_transposition_table(transposition_table.clone())
+                .with_threads(threads);
+            let best_move = search.go_isolated(&position, 6);
+            assert_eq!(search.best_score(), Search::MATE - 3, "with {threads} threads");
 
-    pub fn calculate_stop_time(total_time: u128, increment: u128) -> u128 {
-        total_time / Self::AVERAGE_AMOUNT_OF_MOVES + increment - Self::TIME_OFFSET
+            let mut position_copy = position.clone();
+            position_copy.make_move(best_move);
+            let entry = transposition_table.probe(position_copy.zobrist_key).unwrap();
+            assert_eq!(Search::score_from_tt(entry.score, 1), -Search::MATE + 3);
+        }
+        assert_eq!(Search::score_to_tt(Search::MATE - 7, 4), Search::MATE - 3);
+        assert_eq!(Search::score_from_tt(-Search::MATE + 3, 4), -Search::MATE + 7);
+        assert_eq!(Search::score_to_tt(-120, 4), -120);
+    }
+
+    #[test]
+    fn mates_past_ply_256_keep_their_distance_through_the_tt() {
+        let mated = Search::mated_score(300);
+        assert!(Search::is_mate_score(mated));
+        assert_eq!(Search::uci_score(mated), "mate -150");
+        assert_eq!(Search::score_to_tt(mated, 300), -Search::MATE);
+        assert_eq!(Search::score_from_tt(Search::score_to_tt(mated, 300), 300), mated);
+        assert_eq!(Search::score_from_tt(Search::score_to_tt(-mated, 290), 10), Search::MATE - 20);
+        assert!(Search::is_mate_score(Search::mated_score(Search::MAX_PLY)));
+    }
+
+    // NOTE: Only the alpha-beta search counts the nodes the helper panics at
+    #[cfg(feature = "search_alpha_beta")]
+    #[test]
+    fn panicking_helper_leaves_the_other_threads_searching() {
+        move_masks::init();
+        let position = Position::starting_position();
+        let mut search = Search::new(u128::MAX)
+            .with_output(SearchOutput::Silent)
+            .with_transposition_table(Arc::new(TranspositionTable::new(1)))
+            .with_threads(3);
+        search.panicking_helpers = 1;
+
+        let best_move = search.go_isolated(&position, 6);
+        assert_eq!(search.root_depth, 6);
+        assert!(MoveGeneration::generate_legal_moves(&position).iter().any(|&m| m == best_move));
+    }
+
+    // NOTE: Only the alpha-beta search uses aspiration windows
+    #[cfg(feature = "search_alpha_beta")]
+    #[test]
+    fn root_fail_low_takes_panic_time_and_searches_again() {
+        use crate::timer::VirtualClock;
+
+        move_masks::init();
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let blunder = BitMove::from_uci(&position, "d1d7").unwrap();
+        // NOTE: Without pruning, the score doesn't depend on the move ordering
+        let mut open_search = Search::new(u128::MAX);
+        open_search.root_depth = 4;
+        open_search.pruning = false;
+        let expected = open_search.alpha_beta_root(&position, 4, &[], Search::FULL_WINDOW).score;
+
+        // The previous iteration played a blunder and expected too much, so every move fails low
+        let clock = VirtualClock::default();
+        let mut search = Search::new(100)
+            .with_hard_time_limit(300)
+            .with_clock(clock.clone())
+            .with_output(SearchOutput::Silent);
+        search.pruning = false;
+        search.previous_pv = vec![blunder];
+        search.previous_score = Some(expected + 200);
+        clock.advance_millis(120);
+
+        let mut lines = vec![SearchLine::blank(0)];
+        assert!(search.search_lines(&mut position.clone(), 4, &mut lines));
+        assert_eq!(search.time_manager.soft_limit(), 300);
+        assert_eq!(lines[0].best_move.score, expected);
+        assert_ne!(lines[0].best_move.bit_move, blunder);
+
+        let mut search = Search::new(100)
+            .with_hard_time_limit(300)
+            .with_clock(VirtualClock::default())
+            .with_output(SearchOutput::Silent);
+        search.previous_pv = vec![blunder];
+        search.previous_score = Some(expected + 200);
+        assert!(search.search_lines(&mut position.clone(), 4, &mut lines));
+        assert_eq!(search.time_manager.soft_limit(), 100);
+        assert_eq!(lines[0].best_move.score, expected);
+    }
+
+    #[test]
+    fn fifty_move_rule_draws_unless_the_last_move_mates() {
+        move_masks::init();
+        // The mate in two comes on the hundredth ply, while a clock one higher runs out the move before
+        for (clock, score) in [(97, Search::MATE - 3), (98, 0)] {
+            let position = Position::from_fen(&format!("6k1/8/8/8/8/8/R7/1R4K1 w - - {clock} 80")).unwrap();
+            let mut search = Search::new(u128::MAX);
+            search.root_depth = 4;
+            assert_eq!(search.alpha_beta_root(&position, 4, &[], Search::FULL_WINDOW).score, score, "with the clock at {clock}");
+        }
+
+        let mated = Position::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80").unwrap();
+        assert!(!mated.is_fifty_move_draw());
+        let drawn = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 b - - 100 80").unwrap();
+        assert!(drawn.is_fifty_move_draw());
+    }
+
+    #[test]
+    fn contempt_scores_draws_below_equal_for_the_root_side() {
+        move_masks::init();
+        // Every move leads to a dead position, and the stalemate is found on the first ply
+        let dead = Position::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 1").unwrap();
+        let stalemate = Position::from_fen("k7/8/1QK5/8/8/8/8/8 b - - 0 1").unwrap();
+        for contempt in [0, 25, -25] {
+            let mut search = Search::new(u128::MAX).with_contempt(contempt);
+            search.root_depth = 2;
+            assert_eq!(search.alpha_beta_root(&dead, 2, &[], Search::FULL_WINDOW).score, -contempt);
+            assert_eq!(search.alpha_beta_root(&stalemate, 2, &[], Search::FULL_WINDOW).score, -contempt);
+        }
+    }
+
+    #[test]
+    fn alpha_beta_collects_full_principal_variation() {
+        move_masks::init();
+        for fen in [Fen::STARTING_POSITION, Fen::KIWIPETE_POSITION] {
+            let position = Position::from_fen(fen).unwrap();
+            let depth = 4;
+            // NOTE: The captures of the quiescence search aren't part of the line, so the leaves are evaluated directly
+            let mut search = Search::new(u128::MAX);
+            search.root_depth = depth;
+            search.quiescence = false;
+
+            let best_move = search.alpha_beta_root(&position, depth, &[], Search::FULL_WINDOW);
+            let pv = search.take_root_pv(best_move.bit_move);
+            assert_eq!(pv.len(), depth as usize, "{fen}");
+            assert_eq!(pv[0], best_move.bit_move);
+            assert_eq!(Search::verified_pv(&position, &pv), pv);
+
+            let mut pv_position = position.clone();
+            for &bit_move in &pv {
+                pv_position.make_move(bit_move);
+            }
+            assert_eq!(Eval::basic(&pv_position).score, best_move.score, "{fen}");
+        }
     }
 }
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// NOTE: Relaxed ordering is enough, since neither value guards any other data
+#[derive(Default)]
+pub struct SearchShared {
+    nodes: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl SearchShared {
+    #[inline(always)]
+    pub fn add_nodes(&self, nodes: u64) {
+        self.nodes.fetch_add(nodes, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    // Stops the search once the guard is dropped, which also happens when the thread holding it
+    // panics, so threads waiting for the search to stop are never left waiting
+    pub fn stop_on_drop(&self) -> StopOnDrop<'_> {
+        StopOnDrop(self)
+    }
+}
+
+pub struct StopOnDrop<'a>(&'a SearchShared);
+
+impl Drop for StopOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, sync::Arc, thread};
+
+    use super::*;
+
+    #[test]
+    fn threads_add_up_their_nodes_and_see_the_stop() {
+        let shared = Arc::new(SearchShared::default());
+        let threads: Vec<_> = (0..4).map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                while !shared.is_stopped() {
+                    thread::yield_now();
+                }
+                shared.add_nodes(10);
+            })
+        }).collect();
+
+        let panicking_shared = shared.clone();
+        assert!(panic::catch_unwind(move || {
+            let _stop = panicking_shared.stop_on_drop();
+            panic!("search panicked");
+        }).is_err());
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(shared.nodes(), 40);
+    }
+}
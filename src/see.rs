@@ -0,0 +1,120 @@
+use crate::{bit_move::BitMove, bitboard::Bitboard, color::Color, move_flag::MoveFlag, piece::PieceType, position::Position, square::Square};
+
+// NOTE: The king is worth more than everything else together, so it only ever captures last
+static SEE_VALUES: [i16; 6] = [100, 300, 300, 500, 900, 20000];
+
+// NOTE: Pins and checks are ignored, and a pawn that recaptures on the last rank doesn't promote
+pub struct See { }
+
+impl See {
+    #[inline(always)]
+    fn value(piece: PieceType) -> i16 {
+        SEE_VALUES[piece as usize % 6]
+    }
+
+    pub fn see(position: &Position, bit_move: BitMove) -> i16 {
+        let source = bit_move.source();
+        let target = bit_move.target();
+        let mut occupancy = position.ao;
+        let mut piece = position.get_piece(source);
+
+        // The gain of each capture in the exchange, assuming the last capturer is taken in turn
+        let mut gains = [0; 32];
+        gains[0] = match bit_move.flag() {
+            MoveFlag::EnPassant => {
+                occupancy.pop_sq(match position.side {
+                    Color::White => target.below(),
+                    Color::Black => target.above(),
+                });
+                Self::value(PieceType::WP)
+            },
+            _ => match position.get_piece(target) {
+                PieceType::None => 0,
+                victim => Self::value(victim),
+            },
+        };
+        if let Some(promotion) = bit_move.promotion() {
+            piece = promotion.piece(position.side);
+            gains[0] += Self::value(piece) - Self::value(PieceType::WP);
+        }
+
+        let mut side = position.side;
+        let mut depth = 0;
+        let mut attacker_square = source;
+        loop {
+            depth += 1;
+            gains[depth] = Self::value(piece) - gains[depth - 1];
+            if depth == gains.len() - 1 {
+                break;
+            }
+
+            occupancy.pop_sq(attacker_square);
+            side.switch();
+            let attackers = position.attackers_to(target, occupancy) & occupancy;
+            let Some((next_piece, next_square)) = Self::least_valuable_attacker(position, attackers, side) else {
+                break;
+            };
+
+            // NOTE: The king can only capture if the other side has nothing left to recapture with
+            let mut occupancy_after = occupancy;
+            occupancy_after.pop_sq(next_square);
+            let recapturers = position.attackers_to(target, occupancy_after) & occupancy_after;
+            if next_piece as usize % 6 == 5 && Self::least_valuable_attacker(position, recapturers, side.opposite()).is_some() {
+                break;
+            }
+
+            piece = next_piece;
+            attacker_square = next_square;
+        }
+
+        while depth > 1 {
+            depth -= 1;
+            gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        }
+        gains[0]
+    }
+
+    fn least_valuable_attacker(position: &Position, attackers: Bitboard, side: Color) -> Option<(PieceType, Square)> {
+        let pieces = match side {
+            Color::White => PieceType::WHITE_PIECES,
+            Color::Black => PieceType::BLACK_PIECES,
+        };
+        pieces
+            .into_iter()
+            .find(|&piece| (attackers & position.bbs[piece]).is_not_empty())
+            .map(|piece| (piece, (attackers & position.bbs[piece]).get_lsb()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fen::Fen, move_masks};
+
+    use super::*;
+
+    fn see(fen: &str, move_string: &str) -> i16 {
+        let position = Fen::parse(fen).unwrap();
+        See::see(&position, BitMove::from_uci(&position, move_string).unwrap())
+    }
+
+    #[test]
+    fn see_plays_out_exchanges() {
+        move_masks::init();
+
+        // Undefended pawn
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        // The pawn is defended by a knight and a bishop with the queen behind it, which outnumbers the rook and queen
+        assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -200);
+        // Pawn takes a defended knight
+        assert_eq!(see("4k3/8/3n4/4P3/8/8/8/4K3 w - - 0 1", "e5d6"), 300);
+        assert_eq!(see("4k3/2p5/3n4/4P3/8/8/8/4K3 w - - 0 1", "e5d6"), 200);
+        // The rook behind the rook makes the capture safe
+        assert_eq!(see("4k3/3r4/8/8/3p4/8/3R4/3RK3 w - - 0 1", "d2d4"), 100);
+        assert_eq!(see("4k3/3r4/8/8/3p4/8/3R4/4K3 w - - 0 1", "d2d4"), -400);
+        // The king can't take back while the square is still attacked
+        assert_eq!(see("4k3/8/8/8/5n2/8/R3p3/4K3 w - - 0 1", "a2e2"), -100);
+        assert_eq!(see("4k3/8/8/8/5n2/8/R3p2r/4K3 w - - 0 1", "a2e2"), -400);
+        // En passant
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+    }
+}
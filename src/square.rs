@@ -1,6 +1,7 @@
 use crate::bitboard::Bitboard;
 use crate::file::{File, FileParseError};
 use crate::rank::{Rank, RankParseError};
+use crate::{impl_error, impl_error_from};
 use core::fmt;
 use std::mem::transmute;
 use std::ops::{Index, IndexMut};
@@ -101,6 +102,10 @@ impl From<u8> for Square {
 #[derive(Debug)]
 pub struct SquareParseError(pub &'static str);
 
+impl_error!(SquareParseError);
+impl_error_from!(FileParseError, SquareParseError);
+impl_error_from!(RankParseError, SquareParseError);
+
 impl TryFrom<&str> for Square {
     type Error = SquareParseError;
 
@@ -113,8 +118,8 @@ impl TryFrom<&str> for Square {
         let file_char = chars_iter.next().ok_or(SquareParseError("Missing file character"))?;
         let rank_char = chars_iter.next().ok_or(SquareParseError("Missing rank character"))?;
 
-        let rank = Rank::try_from(rank_char).map_err(|RankParseError(msg)| SquareParseError(msg))?;
-        let file = File::try_from(file_char).map_err(|FileParseError(msg)| SquareParseError(msg))?;
+        let rank = Rank::try_from(rank_char)?;
+        let file = File::try_from(file_char)?;
 
         Ok(Self::from(rank as u8 * 8 + file as u8))
     }
@@ -0,0 +1,140 @@
+use crate::{bit_move::BitMove, epd::Epd, pl, search::{Search, SearchOutput}, timer::Timer};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SuiteLimit {
+    MoveTime(u128),
+    Depth(u16),
+}
+
+pub struct SuiteResult {
+    pub id: String,
+    pub found_move: BitMove,
+    // Whether the found move is one of the best moves and none of the moves to avoid. Positions
+    // without either of them can't be scored.
+    pub solved: Option<bool>,
+    pub time: u128,
+}
+
+// Counts the positions where the search agrees with the bm and am operations
+pub struct TestSuite { }
+
+impl TestSuite {
+    pub const DEFAULT_MOVE_TIME: u128 = 1000;
+
+    pub fn run(epds: &[Epd], limit: SuiteLimit) -> Vec<SuiteResult> {
+        let timer = Timer::new();
+        Self::print_header(epds.len(), limit);
+
+        let mut results = Vec::new();
+        for (index, epd) in epds.iter().enumerate() {
+            let position_timer = Timer::new();
+            let found_move = match limit {
+                SuiteLimit::MoveTime(move_time) => Search::new(move_time)
+                    .with_output(SearchOutput::Silent)
+                    .go_isolated(&epd.position, Search::MAX_DEPTH),
+                SuiteLimit::Depth(depth) => Search::new(u128::MAX)
+                    .with_output(SearchOutput::Silent)
+                    .go_isolated(&epd.position, depth),
+            };
+            let result = SuiteResult {
+                id: epd.id.clone().unwrap_or_else(|| (index + 1).to_string()),
+                found_move,
+                solved: Self::is_solved(epd, found_move),
+                time: position_timer.get_time_passed_millis(),
+            };
+            Self::print_result(epd, &result);
+            results.push(result);
+        }
+
+        Self::print_summary(&results, timer.get_time_passed_millis());
+        results
+    }
+
+    pub fn is_solved(epd: &Epd, found_move: BitMove) -> Option<bool> {
+        if epd.best_moves.is_empty() && epd.avoid_moves.is_empty() {
+            return None;
+        }
+
+        Some((epd.best_moves.is_empty() || epd.best_moves.contains(&found_move)) && !epd.avoid_moves.contains(&found_move))
+    }
+
+    fn print_header(positions: usize, limit: SuiteLimit) {
+        let limit = match limit {
+            SuiteLimit::MoveTime(move_time) => format!("{} milliseconds", move_time),
+            SuiteLimit::Depth(depth) => format!("depth {}", depth),
+        };
+        pl!(format!("\n    Test suite of {} positions at {} per position:", positions, limit));
+        pl!("  |-----------------------------------------------------------------------------|");
+        pl!(format!("  | {:<16} | {:<20} | {:<8} | {:<8} | {:<12} |", "Position", "Expected", "Found", "Result", "Time (ms)"));
+        pl!("  |-----------------------------------------------------------------------------|");
+    }
+
+    fn print_result(epd: &Epd, result: &SuiteResult) {
+        let san_moves = |moves: &[BitMove]| moves.iter().map(|&m| epd.position.move_to_san(m)).collect::<Vec<_>>().join(" ");
+        let expected = match (epd.best_moves.is_empty(), epd.avoid_moves.is_empty()) {
+            (false, _) => format!("bm {}", san_moves(&epd.best_moves)),
+            (true, false) => format!("am {}", san_moves(&epd.avoid_moves)),
+            (true, true) => "-".to_owned(),
+        };
+        let outcome = match result.solved {
+            Some(true) => "solved",
+            Some(false) => "failed",
+            None => "-",
+        };
+        pl!(format!("  | {:<16} | {:<20} | {:<8} | {:<8} | {:<12} |", result.id, expected, epd.position.move_to_san(result.found_move), outcome, result.time));
+    }
+
+    fn print_summary(results: &[SuiteResult], time: u128) {
+        let scored = results.iter().filter(|result| result.solved.is_some()).count();
+        let solved = results.iter().filter(|result| result.solved == Some(true)).count();
+
+        pl!("  |-----------------------------------------------------------------------------|");
+        pl!(format!("  Solved: {} of {}", solved, scored));
+        if scored != results.len() {
+            pl!(format!("  Unscored positions without bm or am: {}", results.len() - scored));
+        }
+        pl!(format!("  Time: {} milliseconds\n", time));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    #[test]
+    fn is_solved_follows_best_and_avoid_moves() {
+        move_masks::init();
+
+        let epd = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; am Ra7;").unwrap();
+        let avoid_only = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra7;").unwrap();
+        let unscored = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - id \"quiet\";").unwrap();
+        let (mate, blunder, king_move) = (epd.best_moves[0], epd.avoid_moves[0], BitMove::from_uci(&epd.position, "g1f1").unwrap());
+
+        assert_eq!(TestSuite::is_solved(&epd, mate), Some(true));
+        assert_eq!(TestSuite::is_solved(&epd, king_move), Some(false));
+        assert_eq!(TestSuite::is_solved(&avoid_only, king_move), Some(true));
+        assert_eq!(TestSuite::is_solved(&avoid_only, blunder), Some(false));
+        assert_eq!(TestSuite::is_solved(&unscored, mate), None);
+    }
+
+    // NOTE: The random search picks different moves every time, so it can't solve the positions
+    #[cfg(not(feature = "search_random"))]
+    #[test]
+    fn run_solves_simple_mates() {
+        move_masks::init();
+
+        let epds: Vec<Epd> = [
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"back rank\";",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - bm Qxf7#; id \"scholar\";",
+        ]
+            .iter()
+            .map(|epd| Epd::parse(epd).unwrap())
+            .collect();
+        let results = TestSuite::run(&epds, SuiteLimit::Depth(3));
+
+        assert_eq!(results.iter().map(|result| result.id.as_str()).collect::<Vec<_>>(), ["back rank", "scholar"]);
+        assert!(results.iter().all(|result| result.solved == Some(true)));
+    }
+}
@@ -1,25 +1,269 @@
-use std::time::Instant;
+use std::{sync::{atomic::{AtomicU64, Ordering}, Arc}, time::{Duration, Instant}};
 
-pub struct Timer {
-    start_time: Instant,
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    #[inline(always)]
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+pub struct MonotonicClock {
+    origin: Instant,
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        MonotonicClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for MonotonicClock {
+    #[inline(always)]
+    fn now(&self) -> Duration {
+        Instant::now().duration_since(self.origin)
+    }
+}
+
+// NOTE: Clones share the same time, so a copy can advance a clock owned by a timer
+#[derive(Clone, Default)]
+pub struct VirtualClock {
+    micros: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    pub fn advance(&self, duration: Duration) {
+        self.micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn advance_millis(&self, millis: u64) {
+        self.advance(Duration::from_millis(millis));
+    }
+}
+
+impl Clock for VirtualClock {
+    #[inline(always)]
+    fn now(&self) -> Duration {
+        Duration::from_micros(self.micros.load(Ordering::Relaxed))
+    }
+}
+
+pub struct Timer<C: Clock = MonotonicClock> {
+    clock: C,
+    start_time: Duration,
 }
 
 impl Timer {
     pub fn new() -> Self {
+        Timer::with_clock(MonotonicClock::default())
+    }
+}
+
+impl<C: Clock> Timer<C> {
+    pub fn with_clock(clock: C) -> Self {
+        let start_time = clock.now();
         Timer {
-            start_time: Instant::now(),
+            clock,
+            start_time,
         }
     }
 
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
     pub fn reset(&mut self) {
-        self.start_time = Instant::now();
+        self.start_time = self.clock.now();
     }
 
     pub fn get_time_passed_millis(&self) -> u128 {
-        Instant::now().duration_since(self.start_time).as_millis()
+        (self.clock.now() - self.start_time).as_millis()
     }
 
     pub fn get_time_passed_secs(&self) -> f64 {
-        Instant::now().duration_since(self.start_time).as_secs_f64()
+        (self.clock.now() - self.start_time).as_secs_f64()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeLimits {
+    pub soft: u128,
+    pub hard: u128,
+}
+
+impl TimeLimits {
+    const AVERAGE_AMOUNT_OF_MOVES: u128 = 30;
+    pub const DEFAULT_MOVE_OVERHEAD: u128 = 100;
+
+    pub const INFINITE: TimeLimits = TimeLimits { soft: u128::MAX, hard: u128::MAX };
+
+    // NOTE: A fixed move time is a hard limit
+    pub fn fixed(move_time: u128) -> TimeLimits {
+        TimeLimits { soft: move_time, hard: move_time }
+    }
+
+    // NOTE: Without moves to go, the time is spread over the moves a game usually still takes
+    pub fn from_clock(time: u128, increment: u128, moves_to_go: Option<u128>, move_overhead: u128) -> TimeLimits {
+        let moves_to_go = moves_to_go.unwrap_or(Self::AVERAGE_AMOUNT_OF_MOVES).max(1);
+        let soft = (time / moves_to_go + increment)
+            .min(time)
+            .saturating_sub(move_overhead);
+        Self::with_soft_limit(time, soft, move_overhead)
+    }
+
+    // At most three times the planned time and a quarter of the remaining time
+    pub fn with_soft_limit(time: u128, soft: u128, move_overhead: u128) -> TimeLimits {
+        let available = time.saturating_sub(move_overhead);
+        let soft = soft.min(available);
+        TimeLimits { soft, hard: soft.saturating_mul(3).min(time / 4).max(soft).min(available) }
+    }
+}
+
+pub struct TimeManager<C: Clock = MonotonicClock> {
+    timer: Timer<C>,
+    limits: TimeLimits,
+    planned: u128,
+    extended: bool,
+}
+
+impl TimeManager {
+    pub fn new(limits: TimeLimits) -> Self {
+        TimeManager::with_clock(MonotonicClock::default(), limits)
+    }
+}
+
+impl<C: Clock> TimeManager<C> {
+    pub fn with_clock(clock: C, limits: TimeLimits) -> Self {
+        TimeManager { timer: Timer::with_clock(clock), limits, planned: limits.soft, extended: false }
+    }
+
+    pub fn set_limits(&mut self, limits: TimeLimits) {
+        self.limits = TimeLimits { soft: limits.soft, hard: limits.hard.max(limits.soft) };
+        self.planned = limits.soft;
+    }
+
+    pub fn soft_limit(&self) -> u128 {
+        self.limits.soft
+    }
+
+    pub fn hard_limit(&self) -> u128 {
+        self.limits.hard
+    }
+
+    #[inline(always)]
+    pub fn elapsed(&self) -> u128 {
+        self.timer.get_time_passed_millis()
+    }
+
+    pub fn reset(&mut self) {
+        self.timer.reset();
+    }
+
+    #[inline(always)]
+    pub fn should_stop(&self) -> bool {
+        self.elapsed() >= self.limits.soft
+    }
+
+    pub fn extend(&mut self) -> bool {
+        let extended = self.limits.soft < self.limits.hard;
+        self.limits.soft = self.limits.hard;
+        self.extended = true;
+        extended
+    }
+
+    // NOTE: Only searches that may go past their planned time adapt it
+    pub fn scale(&mut self, percent: u128) {
+        if self.extended || self.limits.hard <= self.planned {
+            return;
+        }
+        self.limits.soft = (self.planned.saturating_mul(percent) / 100).min(self.limits.hard);
+    }
+
+    pub fn shorten(&mut self, millis: u128) {
+        self.limits.soft = self.limits.soft.saturating_sub(millis);
+        self.planned = self.planned.saturating_sub(millis);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_limits_follow_the_clock() {
+        let overhead = TimeLimits::DEFAULT_MOVE_OVERHEAD;
+        assert_eq!(TimeLimits::from_clock(60_000, 0, None, overhead), TimeLimits { soft: 1_900, hard: 5_700 });
+        assert_eq!(TimeLimits::from_clock(60_000, 1_000, None, overhead), TimeLimits { soft: 2_900, hard: 8_700 });
+        assert_eq!(TimeLimits::from_clock(60_000, 0, Some(2), overhead), TimeLimits { soft: 29_900, hard: 29_900 });
+        assert_eq!(TimeLimits::from_clock(10_000, 0, Some(5), overhead), TimeLimits { soft: 1_900, hard: 2_500 });
+        assert_eq!(TimeLimits::from_clock(500, 5_000, None, overhead).soft, 400);
+        assert_eq!(TimeLimits::from_clock(50, 0, None, overhead), TimeLimits { soft: 0, hard: 0 });
+
+        assert_eq!(TimeLimits::from_clock(60_000, 0, None, 1_000), TimeLimits { soft: 1_000, hard: 3_000 });
+        assert_eq!(TimeLimits::from_clock(2_000, 3_000, None, 1_500), TimeLimits { soft: 500, hard: 500 });
+        assert_eq!(TimeLimits::with_soft_limit(2_000, 1_800, 1_000), TimeLimits { soft: 1_000, hard: 1_000 });
+    }
+
+    #[test]
+    fn time_manager_stops_at_the_soft_limit_unless_extended() {
+        let clock = VirtualClock::default();
+        let mut time_manager = TimeManager::with_clock(clock.clone(), TimeLimits { soft: 100, hard: 300 });
+
+        clock.advance_millis(99);
+        assert!(!time_manager.should_stop());
+        clock.advance_millis(1);
+        assert!(time_manager.should_stop());
+
+        assert!(time_manager.extend());
+        assert!(!time_manager.should_stop());
+        assert!(!time_manager.extend());
+        clock.advance_millis(200);
+        assert!(time_manager.should_stop());
+
+        time_manager.reset();
+        time_manager.shorten(250);
+        assert_eq!(time_manager.soft_limit(), 50);
+        assert_eq!(time_manager.hard_limit(), 300);
+        clock.advance_millis(51);
+        assert!(time_manager.should_stop());
+    }
+
+    #[test]
+    fn time_manager_scales_the_planned_time_within_the_hard_limit() {
+        let mut time_manager = TimeManager::new(TimeLimits { soft: 1_000, hard: 2_000 });
+        time_manager.scale(150);
+        assert_eq!(time_manager.soft_limit(), 1_500);
+        time_manager.scale(60);
+        assert_eq!(time_manager.soft_limit(), 600);
+        time_manager.scale(300);
+        assert_eq!(time_manager.soft_limit(), 2_000);
+
+        time_manager.extend();
+        time_manager.scale(60);
+        assert_eq!(time_manager.soft_limit(), 2_000);
+
+        let mut fixed = TimeManager::new(TimeLimits::fixed(1_000));
+        fixed.scale(50);
+        assert_eq!(fixed.soft_limit(), 1_000);
+    }
+
+    #[test]
+    fn timer_with_virtual_clock_is_deterministic() {
+        let clock = VirtualClock::default();
+        let mut timer = Timer::with_clock(clock.clone());
+        assert_eq!(timer.get_time_passed_millis(), 0);
+
+        clock.advance_millis(1500);
+        assert_eq!(timer.get_time_passed_millis(), 1500);
+        assert_eq!(timer.get_time_passed_secs(), 1.5);
+
+        timer.reset();
+        clock.advance_millis(20);
+        assert_eq!(timer.get_time_passed_millis(), 20);
     }
 }
@@ -0,0 +1,252 @@
+use std::{mem, sync::atomic::{AtomicU64, AtomicU8, Ordering}};
+
+use crate::bit_move::BitMove;
+
+// NOTE: No bound is zero, so a slot that was never written doesn't hold an entry
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bound {
+    Exact = 1,
+    Lower = 2,
+    Upper = 3,
+}
+
+// NOTE: Mate scores count the plies from the node instead of from the root
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TtEntry {
+    pub best_move: BitMove,
+    pub score: i16,
+    pub depth: u16,
+    pub bound: Bound,
+}
+
+impl TtEntry {
+    #[inline(always)]
+    pub fn cutoff_score(&self, alpha: i16, beta: i16) -> Option<i16> {
+        match self.bound {
+            Bound::Exact => Some(self.score.clamp(alpha, beta)),
+            Bound::Lower if self.score >= beta => Some(beta),
+            Bound::Upper if self.score <= alpha => Some(alpha),
+            _ => None,
+        }
+    }
+}
+
+// Packed as the move in the lowest 24 bits, then the score, the depth, the bound and the generation
+const SCORE_SHIFT: u32 = 24;
+const DEPTH_SHIFT: u32 = 40;
+const BOUND_SHIFT: u32 = 56;
+const GENERATION_SHIFT: u32 = 58;
+const MOVE_MASK: u64 = (1 << SCORE_SHIFT) - 1;
+const GENERATION_MASK: u8 = 0b11_1111;
+
+// NOTE: The key is stored XOR-ed with the data, so a slot torn by two threads storing at once reads as another position
+#[derive(Default)]
+struct Slot {
+    checked_key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    #[inline(always)]
+    fn pack(entry: TtEntry, generation: u8) -> u64 {
+        entry.best_move.bits() as u64
+            | (entry.score as u16 as u64) << SCORE_SHIFT
+            | (entry.depth as u64) << DEPTH_SHIFT
+            | (entry.bound as u64) << BOUND_SHIFT
+            | ((generation & GENERATION_MASK) as u64) << GENERATION_SHIFT
+    }
+
+    #[inline(always)]
+    fn load(&self) -> Option<(u64, TtEntry, u8)> {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.checked_key.load(Ordering::Relaxed) ^ data;
+        let bound = match (data >> BOUND_SHIFT) & 0b11 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        let entry = TtEntry {
+            best_move: BitMove::from_bits((data & MOVE_MASK) as u32),
+            score: (data >> SCORE_SHIFT) as u16 as i16,
+            depth: (data >> DEPTH_SHIFT) as u16,
+            bound,
+        };
+        Some((key, entry, (data >> GENERATION_SHIFT) as u8))
+    }
+
+    #[inline(always)]
+    fn save(&self, key: u64, data: u64) {
+        self.checked_key.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    size_mb: usize,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub const DEFAULT_SIZE_MB: usize = 16;
+
+    // NOTE: The slots are rounded down to a power of two
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let slot_count = (size_mb * 1024 * 1024 / mem::size_of::<Slot>()).max(1);
+        TranspositionTable {
+            slots: (0..1 << slot_count.ilog2()).map(|_| Slot::default()).collect(),
+            size_mb,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.size_mb
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.save(0, 0);
+        }
+    }
+
+    // NOTE: The generation wraps around, which only makes entries of a search long ago look recent
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn slot(&self, key: u64) -> &Slot {
+        &self.slots[((key as u128 * self.slots.len() as u128) >> 64) as usize]
+    }
+
+    #[inline(always)]
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.slot(key)
+            .load()
+            .filter(|&(slot_key, _, _)| slot_key == key)
+            .map(|(_, entry, _)| entry)
+    }
+
+    // NOTE: Another position is only replaced if it's from an earlier search or wasn't searched deeper
+    #[inline(always)]
+    pub fn store(&self, key: u64, mut entry: TtEntry) {
+        let generation = self.generation.load(Ordering::Relaxed) & GENERATION_MASK;
+        let slot = self.slot(key);
+        if let Some((old_key, old_entry, old_generation)) = slot.load() {
+            if old_key == key {
+                if entry.best_move == BitMove::EMPTY {
+                    entry.best_move = old_entry.best_move;
+                }
+            } else if old_generation == generation && old_entry.depth > entry.depth {
+                return;
+            }
+        }
+        slot.save(key, Slot::pack(entry, generation));
+    }
+
+    // Permille of the first thousand slots holding entries of this search
+    pub fn hashfull(&self) -> u16 {
+        let generation = self.generation.load(Ordering::Relaxed) & GENERATION_MASK;
+        let sample = &self.slots[..self.slots.len().min(1000)];
+        let used = sample
+            .iter()
+            .filter(|slot| slot.load().is_some_and(|(_, _, slot_generation)| slot_generation == generation))
+            .count();
+        (used * 1000 / sample.len()) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use crate::{fen::Fen, move_generation::MoveGeneration, move_masks, search::Search};
+
+    use super::*;
+
+    #[test]
+    fn entries_are_found_by_key_and_replaced_by_depth() {
+        move_masks::init();
+        let position = Fen::parse(Fen::KIWIPETE_POSITION).unwrap();
+        let moves = MoveGeneration::generate_legal_moves(&position);
+        let entry = |depth: u16, best_move: BitMove| TtEntry { best_move, score: 35, depth, bound: Bound::Lower };
+
+        let table = TranspositionTable::new(0);
+        assert_eq!(table.probe(1), None);
+        table.store(1, entry(5, moves[0]));
+        assert_eq!(table.probe(1), Some(entry(5, moves[0])));
+        assert_eq!(table.probe(2), None);
+
+        table.store(1, entry(3, BitMove::EMPTY));
+        assert_eq!(table.probe(1), Some(entry(3, moves[0])));
+        table.store(2, entry(2, moves[1]));
+        assert_eq!(table.probe(1), Some(entry(3, moves[0])));
+        table.store(2, entry(4, moves[1]));
+        assert_eq!(table.probe(2), Some(entry(4, moves[1])));
+
+        assert_eq!(table.hashfull(), 1000);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+        table.store(1, entry(1, moves[2]));
+        assert_eq!(table.probe(1), Some(entry(1, moves[2])));
+        table.clear();
+        assert_eq!(table.probe(1), None);
+    }
+
+    #[test]
+    fn table_sizes_round_down_to_a_power_of_two() {
+        assert_eq!(TranspositionTable::new(0).slots.len(), 1);
+        assert_eq!(TranspositionTable::new(1).slots.len(), 1024 * 1024 / mem::size_of::<Slot>());
+        assert_eq!(TranspositionTable::new(3).slots.len(), 2 * 1024 * 1024 / mem::size_of::<Slot>());
+        assert_eq!(TranspositionTable::new(3).size_mb(), 3);
+    }
+
+    #[test]
+    fn bounds_only_cut_off_outside_of_the_window() {
+        let entry = |score: i16, bound: Bound| TtEntry { best_move: BitMove::EMPTY, score, depth: 1, bound };
+        assert_eq!(entry(50, Bound::Exact).cutoff_score(-100, 100), Some(50));
+        assert_eq!(entry(150, Bound::Exact).cutoff_score(-100, 100), Some(100));
+        assert_eq!(entry(100, Bound::Lower).cutoff_score(-100, 100), Some(100));
+        assert_eq!(entry(99, Bound::Lower).cutoff_score(-100, 100), None);
+        assert_eq!(entry(-100, Bound::Upper).cutoff_score(-100, 100), Some(-100));
+        assert_eq!(entry(-99, Bound::Upper).cutoff_score(-100, 100), None);
+    }
+
+    #[test]
+    fn entries_pack_into_one_word_and_torn_slots_are_rejected() {
+        move_masks::init();
+        let position = Fen::parse(Fen::KIWIPETE_POSITION).unwrap();
+        let moves = MoveGeneration::generate_legal_moves(&position);
+        let table = TranspositionTable::new(0);
+        let first = TtEntry { best_move: moves[0], score: -Search::MATE + 7, depth: 300, bound: Bound::Upper };
+        let second = TtEntry { best_move: moves[1], score: 12, depth: 1, bound: Bound::Exact };
+        table.store(1, first);
+        assert_eq!(table.probe(1), Some(first));
+
+        table.slots[0].data.store(Slot::pack(second, 0), Ordering::Relaxed);
+        assert_eq!(table.probe(1), None);
+        assert_eq!(table.probe(2), None);
+    }
+
+    #[test]
+    fn threads_share_the_table() {
+        let key = |index: u64| index.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let table = Arc::new(TranspositionTable::new(1));
+        let threads: Vec<_> = (0..4u64).map(|thread_index| {
+            let table = table.clone();
+            thread::spawn(move || {
+                for index in (thread_index * 1000)..(thread_index * 1000 + 100) {
+                    table.store(key(index), TtEntry { best_move: BitMove::EMPTY, score: index as i16, depth: 1, bound: Bound::Exact });
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let found = (0..4000).filter_map(|index| table.probe(key(index))).filter(|entry| entry.score > 0).count();
+        assert!(found > 300, "{found}");
+        assert!(table.hashfull() > 0);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{fs, io, path::Path};
+
+use rayon::{iter::{IntoParallelRefIterator, ParallelIterator}, slice::ParallelSlice};
+
+use crate::{eval::Eval, fen::Fen, impl_error, pl};
+
+#[derive(Debug)]
+pub struct TuneError(pub &'static str);
+
+impl_error!(TuneError);
+
+impl From<io::Error> for TuneError {
+    fn from(_: io::Error) -> Self {
+        TuneError("Couldn't access tuning file!")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TuneMethod {
+    LocalSearch,
+    GradientDescent,
+}
+
+// NOTE: Summed in fixed chunks and in order, so the error comes out the same on every call
+const CHUNK_SIZE: usize = 4096;
+
+const INITIAL_STEP: f64 = 16.0;
+
+const LEARNING_RATE: f64 = 1.0;
+const FIRST_MOMENT_DECAY: f64 = 0.9;
+const SECOND_MOMENT_DECAY: f64 = 0.999;
+
+pub struct TuningEntry {
+    pub features: [i32; Eval::FEATURE_COUNT],
+    pub result: f64,
+}
+
+// Texel tuning of the eval weights against the game results of the positions
+// NOTE: The positions should be quiet, since the eval doesn't see pending captures
+pub struct Tuner {
+    entries: Vec<TuningEntry>,
+}
+
+impl Tuner {
+    pub const DEFAULT_ITERATIONS: u32 = 1000;
+
+    // Lines of "<fen> <result>", where the result is 1-0, 0-1, 1/2-1/2 or white's score
+    pub fn parse(contents: &str) -> Result<Tuner, TuneError> {
+        let mut entries = Vec::new();
+        for line in contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (fen, result) = line.rsplit_once(char::is_whitespace).ok_or(TuneError("Found position without result!"))?;
+            let position = Fen::parse(fen.trim()).map_err(|_| TuneError("Couldn't parse tuning position!"))?;
+            entries.push(TuningEntry {
+                features: Eval::features(&position),
+                result: Self::parse_result(result)?,
+            });
+        }
+        if entries.is_empty() {
+            return Err(TuneError("Didn't find any tuning positions!"));
+        }
+        Ok(Tuner { entries })
+    }
+
+    pub fn load(path: &Path) -> Result<Tuner, TuneError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse_result(result: &str) -> Result<f64, TuneError> {
+        match result.trim_matches(|c| matches!(c, '[' | ']' | '"' | ';')) {
+            "1-0" => Ok(1.0),
+            "0-1" => Ok(0.0),
+            "1/2-1/2" => Ok(0.5),
+            score => score
+                .parse::<f64>()
+                .ok()
+                .filter(|score| (0.0..=1.0).contains(score))
+                .ok_or(TuneError("Couldn't parse game result!")),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline(always)]
+    fn eval(entry: &TuningEntry, weights: &[f64; Eval::FEATURE_COUNT]) -> f64 {
+        entry.features.iter().zip(weights).map(|(&feature, weight)| feature as f64 * weight).sum()
+    }
+
+    #[inline(always)]
+    fn sigmoid(eval: f64, k: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-k * eval / 400.0))
+    }
+
+    pub fn error(&self, weights: &[f64; Eval::FEATURE_COUNT], k: f64) -> f64 {
+        let chunk_errors: Vec<f64> = self.entries
+            .par_chunks(CHUNK_SIZE)
+            .map(|chunk| chunk.iter().map(|entry| (entry.result - Self::sigmoid(Self::eval(entry, weights), k)).powi(2)).sum())
+            .collect();
+        chunk_errors.iter().sum::<f64>() / self.entries.len() as f64
+    }
+
+    pub fn fit_scaling(&self, weights: &[f64; Eval::FEATURE_COUNT]) -> f64 {
+        let (mut low, mut high) = (0.0, 10.0);
+        for _ in 0..100 {
+            let third = (high - low) / 3.0;
+            if self.error(weights, low + third) < self.error(weights, high - third) {
+                high -= third;
+            } else {
+                low += third;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    pub fn tune(&self, weights: &[i16; Eval::FEATURE_COUNT], method: TuneMethod, iterations: u32) -> [i16; Eval::FEATURE_COUNT] {
+        let weights = weights.map(f64::from);
+        let k = self.fit_scaling(&weights);
+        pl!(format!("info string tuning {} positions with k {:.4}, starting error {:.6}", self.len(), k, self.error(&weights, k)));
+
+        let tuned = match method {
+            TuneMethod::LocalSearch => self.local_search(weights, k, iterations),
+            TuneMethod::GradientDescent => self.gradient_descent(weights, k, iterations),
+        };
+
+        let tuned = tuned.map(|weight| weight.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16);
+        pl!(format!("info string tuned error {:.6}", self.error(&tuned.map(f64::from), k)));
+        tuned
+    }
+
+    fn local_search(&self, mut weights: [f64; Eval::FEATURE_COUNT], k: f64, iterations: u32) -> [f64; Eval::FEATURE_COUNT] {
+        let mut best_error = self.error(&weights, k);
+        let mut step = INITIAL_STEP;
+        for pass in 1..=iterations {
+            let mut improved = false;
+            for index in 0..Eval::FEATURE_COUNT {
+                for delta in [step, -step] {
+                    let mut candidate = weights;
+                    candidate[index] += delta;
+                    let error = self.error(&candidate, k);
+                    if error < best_error {
+                        (weights, best_error, improved) = (candidate, error, true);
+                        break;
+                    }
+                }
+            }
+            pl!(format!("info string pass {} step {} error {:.6}", pass, step, best_error));
+
+            if !improved {
+                if step <= 1.0 {
+                    break;
+                }
+                step /= 2.0;
+            }
+        }
+        weights
+    }
+
+    fn gradient_descent(&self, mut weights: [f64; Eval::FEATURE_COUNT], k: f64, iterations: u32) -> [f64; Eval::FEATURE_COUNT] {
+        let mut first_moments = [0.0; Eval::FEATURE_COUNT];
+        let mut second_moments = [0.0; Eval::FEATURE_COUNT];
+        for iteration in 1..=iterations {
+            let gradient = self.gradient(&weights, k);
+            for index in 0..Eval::FEATURE_COUNT {
+                first_moments[index] = FIRST_MOMENT_DECAY * first_moments[index] + (1.0 - FIRST_MOMENT_DECAY) * gradient[index];
+                second_moments[index] = SECOND_MOMENT_DECAY * second_moments[index] + (1.0 - SECOND_MOMENT_DECAY) * gradient[index].powi(2);
+                let first_moment = first_moments[index] / (1.0 - FIRST_MOMENT_DECAY.powi(iteration as i32));
+                let second_moment = second_moments[index] / (1.0 - SECOND_MOMENT_DECAY.powi(iteration as i32));
+                weights[index] -= LEARNING_RATE * first_moment / (second_moment.sqrt() + 1e-8);
+            }
+
+            if iteration % 100 == 0 || iteration == iterations {
+                pl!(format!("info string iteration {} error {:.6}", iteration, self.error(&weights, k)));
+            }
+        }
+        weights
+    }
+
+    fn gradient(&self, weights: &[f64; Eval::FEATURE_COUNT], k: f64) -> [f64; Eval::FEATURE_COUNT] {
+        let slope = k * 10f64.ln() / 400.0;
+        let mut gradient = self.entries
+            .par_iter()
+            .map(|entry| {
+                let expected = Self::sigmoid(Self::eval(entry, weights), k);
+                let factor = 2.0 * (expected - entry.result) * expected * (1.0 - expected) * slope;
+                entry.features.map(|feature| factor * feature as f64)
+            })
+            .reduce(|| [0.0; Eval::FEATURE_COUNT], |mut sum, terms| {
+                sum.iter_mut().zip(terms).for_each(|(sum, term)| *sum += term);
+                sum
+            });
+        gradient.iter_mut().for_each(|value| *value /= self.entries.len() as f64);
+        gradient
+    }
+
+    pub fn format_constant(weights: &[i16; Eval::FEATURE_COUNT]) -> String {
+        let values: Vec<String> = weights.iter().map(i16::to_string).collect();
+        format!("    pub const FEATURE_WEIGHTS: [i16; Self::FEATURE_COUNT] = [{}];\n", values.join(", "))
+    }
+
+    pub fn save(path: &Path, weights: &[i16; Eval::FEATURE_COUNT]) -> Result<(), TuneError> {
+        fs::write(path, Self::format_constant(weights))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    const TUNING_POSITIONS: &str = "
+        # queen odds
+        4k3/pppppppp/8/8/8/8/PPPPPPPP/3QK3 w - - 1-0
+        3qk3/pppppppp/8/8/8/8/PPPPPPPP/4K3 b - - [0.0]
+        3qk3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - \"0-1\";
+        4k3/pppppppp/8/8/8/8/PPPPPPPP/3QK3 b - - 1.0
+        4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 1/2-1/2
+        3qk3/pppppppp/8/8/8/8/PPPPPPPP/3QK3 b - - [0.5]
+    ";
+
+    #[test]
+    fn tuning_lowers_the_error_of_a_bad_weight() {
+        move_masks::init();
+        let tuner = Tuner::parse(TUNING_POSITIONS).unwrap();
+        assert_eq!(tuner.len(), 6);
+        assert_eq!(tuner.entries.iter().map(|entry| entry.result).collect::<Vec<_>>(), [1.0, 0.0, 0.0, 1.0, 0.5, 0.5]);
+        assert!(Tuner::parse("4k3/8/8/8/8/8/8/4K3 w - - 2-0").is_err());
+        assert!(Tuner::parse("# nothing\n").is_err());
+
+        let mut weights = Eval::FEATURE_WEIGHTS;
+        weights[4] = 100;
+        let k = 1.0;
+        for method in [TuneMethod::LocalSearch, TuneMethod::GradientDescent] {
+            let tuned = tuner.tune(&weights, method, 200);
+            assert!(tuned[4] > weights[4], "{:?} {:?}", method, tuned);
+            assert!(tuner.error(&tuned.map(f64::from), k) < tuner.error(&weights.map(f64::from), k), "{:?}", method);
+        }
+
+        assert_eq!(Tuner::format_constant(&[1, 2, 3, 4, 5, 6, 7, 8, -9]), "    pub const FEATURE_WEIGHTS: [i16; Self::FEATURE_COUNT] = [1, 2, 3, 4, 5, 6, 7, 8, -9];\n");
+    }
+}
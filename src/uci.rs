@@ -1,66 +1,155 @@
-use std::{io::{self, BufRead}, process::exit};
+use std::{fs, io, path::{Path, PathBuf}, process::exit, str::FromStr, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread::{self, JoinHandle}};
 
-use crate::{bit_move::BitMove, color::Color, eval::Eval, fen::{Fen, FenParseError}, move_flag::MoveFlag, move_generation::MoveGeneration, perft::Perft, pl, position::Position, search::Search, square::{Square, SquareParseError}};
+use rand::Rng;
 
-pub struct UciParseError(pub &'static str);
+use crate::{bit_move::{BitMove, MoveParseError}, checkpoint::{AnalysisCheckpoint, CheckpointError}, color::Color, datagen::{Datagen, DatagenError, DatagenSettings}, distributed_perft::{PerftCoordinator, PerftWorker}, engine_options::{EngineOptions, OptionError}, epd::{Epd, EpdError}, eval::{Eval, EvalBlend}, fen::{Fen, FenError}, impl_error, impl_error_from, legality::{Legality, LegalityChecker, LegalityError}, move_generation::MoveGeneration, nnue::{self, Network}, opponent_model::OpponentModel, perft::Perft, pl, play::Play, playout::{Playout, PlayoutPolicy}, position::Position, position_diff::PositionDiff, problem::{Problem, ProblemError}, profile::{Profile, ProfileError}, regression::{Regression, RegressionError, RegressionParams}, repro::{ReproBundle, ReproError}, search::Search, search_shared::SearchShared, square::Square, test_suite::{SuiteLimit, TestSuite}, timer::TimeLimits, tt::TranspositionTable, tune::{TuneError, TuneMethod, Tuner}, xboard::Xboard};
+
+#[derive(Debug)]
+pub struct UciError(pub &'static str);
+
+impl_error!(UciError);
+impl_error_from!(FenError, UciError);
+impl_error_from!(MoveParseError, UciError);
+impl_error_from!(OptionError, UciError);
+impl_error_from!(ReproError, UciError);
+impl_error_from!(RegressionError, UciError);
+impl_error_from!(LegalityError, UciError);
+impl_error_from!(ProblemError, UciError);
+impl_error_from!(ProfileError, UciError);
+impl_error_from!(CheckpointError, UciError);
+impl_error_from!(TuneError, UciError);
+impl_error_from!(DatagenError, UciError);
+impl_error_from!(EpdError, UciError);
 
 pub struct Uci {
-    pub position: Position
+    pub position: Position,
+    // The FEN the position command started from and the moves played since
+    root_fen: String,
+    moves: Vec<String>,
+    pub options: EngineOptions,
+    pub profile_path: PathBuf,
+    // The EvalFile the network was loaded for, and the network shared with the search threads
+    eval_file: Option<String>,
+    network: Option<Arc<Network>>,
+    search_thread: Option<JoinHandle<()>>,
+    search_shared: Arc<SearchShared>,
+    ponder_flag: Arc<AtomicBool>,
+    opponent_model: OpponentModel,
+    // NOTE: The table is kept between searches
+    transposition_table: Arc<TranspositionTable>,
 }
 
 impl Default for Uci {
     fn default() -> Self {
-        Self { position: Position::starting_position() }
+        Self {
+            position: Position::starting_position(),
+            root_fen: Position::starting_position().to_fen(),
+            moves: Vec::new(),
+            options: EngineOptions::default(),
+            profile_path: Profile::default_path(),
+            eval_file: None,
+            network: None,
+            search_thread: None,
+            search_shared: Arc::new(SearchShared::default()),
+            ponder_flag: Arc::new(AtomicBool::new(false)),
+            opponent_model: OpponentModel::default(),
+            transposition_table: Arc::new(TranspositionTable::new(TranspositionTable::DEFAULT_SIZE_MB)),
+        }
     }
 }
 
 impl Uci {
     pub fn init(&mut self) {
-        Self::print_uci_info();
+        pl!("Sisyphus32 by Juules32");
 
-        let mut lines = io::stdin().lock().lines();
-        while let Some(Ok(line)) = lines.next() {
-            if let Err(UciParseError(msg)) = self.parse_line(line) {
+        // NOTE: Stdin is only locked while reading a line, since commands like play read from it too
+        loop {
+            let mut line = String::new();
+            if let Ok(0) | Err(_) = io::stdin().read_line(&mut line) {
+                self.stop_search();
+                break;
+            }
+            if let Err(UciError(msg)) = self.parse_line(line) {
                 eprintln!("{msg}");
             };
         }
     }
 
-    fn print_uci_info() {
+    fn print_uci_info(&self) {
         pl!("id name Sisyphus32");
         pl!("id author Juules32");
+        pl!(self.options);
         pl!("uciok");
     }
     
-    fn parse_line(&mut self, line: String) -> Result<(), UciParseError> {
+    fn parse_line(&mut self, line: String) -> Result<(), UciError> {
         let mut words = line.split_whitespace();
         match words.next() {
             Some(keyword) => {
                 match keyword {
-                    "quit" | "exit" => exit(0),
+                    "quit" | "exit" => {
+                        self.stop_search();
+                        exit(0)
+                    },
                     "go" => self.parse_go(&line),
+                    "position" if words.next() == Some("diff") => self.parse_position_diff(&line),
                     "position" => self.parse_position(&line),
-                    "ucinewgame" => self.parse_position("position startpos"),
+                    "fen" => self.parse_fen(&line),
+                    "setoption" => self.parse_setoption(&line),
+                    "ucinewgame" => {
+                        self.stop_search();
+                        self.opponent_model = OpponentModel::default();
+                        self.transposition_table.clear();
+                        self.parse_position("position startpos")
+                    },
                     "uci" => {
-                        Self::print_uci_info();
+                        self.print_uci_info();
                         Ok(())
                     },
                     "eval" => {
                         pl!(Eval::basic(&self.position).score);
                         Ok(())
                     },
+                    "features" => self.parse_features(&line),
+                    "legal" => self.parse_legal(&line),
+                    "solve" => self.parse_solve(&line),
                     "isready" => {
                         pl!("readyok");
                         Ok(())
                     },
+                    "stop" => {
+                        self.stop_search();
+                        Ok(())
+                    },
+                    "ponderhit" => {
+                        self.ponder_flag.store(false, Ordering::Relaxed);
+                        Ok(())
+                    },
                     "d" => {
-                        pl!(self.position);
+                        self.print_position_details();
                         Ok(())
                     },
                     "bench" | "benchlong" => {
                         Perft::long_perft_tests();
                         Ok(())
                     },
+                    "repro" => self.parse_repro(&line),
+                    "regression" => self.parse_regression(&line),
+                    "testsuite" => self.parse_testsuite(&line),
+                    "tune" => self.parse_tune(&line),
+                    "datagen" => self.parse_datagen(&line),
+                    "exportnet" => self.parse_export_net(&line),
+                    "profile" => self.parse_profile(&line),
+                    "resume" => self.parse_resume(&line),
+                    "playout" => self.parse_playout(&line),
+                    "perftworker" => self.parse_perft_worker(&line),
+                    "perftsplit" => self.parse_perft_split(&line),
+                    "play" => self.parse_play(&line),
+                    "xboard" => {
+                        self.stop_search();
+                        Xboard::default().run();
+                        exit(0)
+                    },
                     "benchmedium" => {
                         Perft::medium_perft_tests();
                         Ok(())
@@ -69,153 +158,553 @@ impl Uci {
                         Perft::short_perft_tests();
                         Ok(())
                     },
-                    _ => Err(UciParseError("Couldn't parse keyword!")),
+                    _ => Err(UciError("Couldn't parse keyword!")),
                 }
             }
             None => Ok(()),
         }
     }
 
-    fn parse_move_string(&mut self, move_string: &str) -> Result<BitMove, UciParseError> {
-        if move_string.len() == 4 || move_string.len() == 5 {
-            let source = Square::try_from(&move_string[0..2]).map_err(|SquareParseError(msg)| UciParseError(msg))?;
-            let target = Square::try_from(&move_string[2..4]).map_err(|SquareParseError(msg)| UciParseError(msg))?;
-            let promotion_piece_option = if move_string.len() == 5 {
-                Some(&move_string[4..5])
-            } else {
-                None
-            };
-
-            let ms = MoveGeneration::generate_pseudo_legal_moves(&self.position);
-            for m in ms.iter() {
-                let s = m.source();
-                let t = m.target();
-                let f = m.flag();
-                
-                if source == s && target == t {
-                    match promotion_piece_option {
-                        Some(promotion_piece_string) => {
-                            match promotion_piece_string {
-                                "q" => if f == MoveFlag::PromoQ { return Ok(*m) },
-                                "r" => if f == MoveFlag::PromoR { return Ok(*m) },
-                                "b" => if f == MoveFlag::PromoB { return Ok(*m) },
-                                "n" => if f == MoveFlag::PromoN { return Ok(*m) },
-                                _ => return Err(UciParseError("Found illegal promotion piece string!"))
-                            }
-                        },
-                        None => return Ok(*m),
-                    }
-                }
-            }
-
-            Err(UciParseError("Couldn't find a pseudo-legal move!"))
-        } else {
-            Err(UciParseError("Couldn't parse move with illegal amount of characters!"))
-        }
-    }
-    
-    fn parse_position(&mut self, line: &str) -> Result<(), UciParseError> {
+    fn parse_position(&mut self, line: &str) -> Result<(), UciError> {
         let fen_index_option = line.find("fen");
         let startpos_index_option = line.find("startpos");
         let moves_index_option = line.find("moves");
 
-        if let Some(fen_index) = fen_index_option {
+        // NOTE: The moves are applied to a copy, so a bad command leaves the position untouched
+        let mut position = if let Some(fen_index) = fen_index_option {
             let fen_string = {
                 match moves_index_option {
                     Some(moves_index) => &line[fen_index + 3..moves_index].trim(),
                     None => &line[fen_index + 3..].trim(),
                 }
             };
-            self.position = Fen::parse(fen_string).map_err(|FenParseError(msg)| UciParseError(msg))?;
+            Fen::parse(fen_string)?
         } else if startpos_index_option.is_some() {
-            self.position = Fen::parse(Fen::STARTING_POSITION).map_err(|FenParseError(msg)| UciParseError(msg))?;
+            Fen::parse(Fen::STARTING_POSITION)?
         } else {
-            return Err(UciParseError("Neither fen nor startpos found!"));
-        }
+            return Err(UciError("Neither fen nor startpos found!"));
+        };
 
+        let root_fen = position.to_fen();
+        let mut moves = Vec::new();
         if let Some(moves_index) = moves_index_option {
             for move_string in line[moves_index + 5..].split_whitespace() {
-                let pseudo_legal_move = self.parse_move_string(move_string)?;
-                if !self.position.make_move(pseudo_legal_move) {
-                    return Err(UciParseError("Found illegal move while parsing moves!"))
-                }
+                let bit_move = BitMove::from_uci(&position, move_string)?;
+                position.make_move(bit_move);
+                moves.push(move_string.to_owned());
             }
         }
 
+        self.position = position;
+        self.root_fen = root_fen;
+        self.moves = moves;
         Ok(())
     }
     
-    fn parse_go(&self, line: &str) -> Result<(), UciParseError> {
+    // position diff <fen> <fen>
+    fn parse_position_diff(&self, line: &str) -> Result<(), UciError> {
+        let fields: Vec<&str> = line.split_whitespace().skip(2).collect();
+        let second_fen_index = fields
+            .iter()
+            .skip(1)
+            .position(|field| field.contains('/'))
+            .map(|index| index + 1)
+            .ok_or(UciError("Didn't find two FENs!"))?;
+
+        let from = Fen::parse(&fields[..second_fen_index].join(" "))?;
+        let to = Fen::parse(&fields[second_fen_index..].join(" "))?;
+        pl!(PositionDiff::new(&from, &to));
+        Ok(())
+    }
+
+    fn parse_fen(&self, line: &str) -> Result<(), UciError> {
+        match line.split_once("canonicalize") {
+            Some((_, fen)) => {
+                pl!(Fen::canonicalize(fen.trim())?);
+                Ok(())
+            },
+            None => Err(UciError("Couldn't parse fen command!")),
+        }
+    }
+
+    fn parse_setoption(&mut self, line: &str) -> Result<(), UciError> {
+        if self.options.apply_setoption(line)? == EngineOptions::CLEAR_HASH {
+            self.transposition_table.clear();
+        }
+        Ok(())
+    }
+
+    fn seed(&self) -> Option<u64> {
+        self.options.string(EngineOptions::SEED).parse::<u64>().ok()
+    }
+
+    fn parse_legal(&self, line: &str) -> Result<(), UciError> {
+        let move_string = line.split_whitespace().nth(1).ok_or(UciError("Didn't find move!"))?;
+        match LegalityChecker::check(&self.position, move_string)? {
+            Legality::Legal(bit_move) => pl!(format!("info string {} is legal", bit_move.to_uci_string())),
+            Legality::Illegal(reason) => pl!(format!("info string {} is illegal: {}", move_string, reason)),
+        }
+        Ok(())
+    }
+
+    // solve <epd with stipulation>, for example solve 7k/8/6K1/8/8/8/8/R7 b - - h#1
+    fn parse_solve(&self, line: &str) -> Result<(), UciError> {
+        let epd = line.split_once("solve").map(|(_, epd)| epd.trim()).unwrap_or("");
+        let problem = Problem::parse(epd)?;
+        let solutions = problem.solve();
+
+        pl!(format!("info string {} has {} solutions", problem.stipulation, solutions.len()));
+        for solution in solutions {
+            let moves: Vec<String> = solution.iter().map(|bit_move| bit_move.to_uci_string()).collect();
+            pl!(format!("info string solution {}", moves.join(" ")));
+        }
+        Ok(())
+    }
+
+    fn parse_repro(&mut self, line: &str) -> Result<(), UciError> {
+        let path = line.split_once("repro").map(|(_, path)| path.trim()).unwrap_or("");
+        if path.is_empty() {
+            return Err(UciError("Didn't find reproduction bundle path!"));
+        }
+
+        let bundle = ReproBundle::read(Path::new(path))?;
+        for command in bundle.commands() {
+            pl!(format!("info string repro: {}", command));
+            self.parse_line(command.clone())?;
+        }
+        Ok(())
+    }
+
+    // regression <params file> [nodes <n>] and regression save <params file>
+    fn parse_regression(&self, line: &str) -> Result<(), UciError> {
         let words: Vec<_> = line.split_whitespace().collect();
-        if let Some(perft_index) = words.iter().position(|&word| word == "perft") {
-            match words.get(perft_index + 1) {
-                Some(depth_string) => {
-                    match depth_string.parse::<u8>() {
-                        Ok(depth) => {
-                            Perft::perft_test(&self.position, depth, true);
-                            Ok(())
-                        },
-                        Err(_) => Err(UciParseError("Couldn't parse depth string!")),
-                    }
-                },
-                None => Err(UciParseError("Didn't find perft depth!")),
+        match words.get(1) {
+            Some(&"save") => {
+                let path = words.get(2).ok_or(UciError("Didn't find params file path!"))?;
+                RegressionParams::save(Path::new(path), &Eval::FEATURE_WEIGHTS)?;
+                pl!(format!("info string saved params to {}", path));
+                Ok(())
+            },
+            Some(path) => {
+                let previous = RegressionParams::load(Path::new(path))?;
+                let nodes = match words.get(2) {
+                    Some(&"nodes") => words.get(3)
+                        .and_then(|nodes| nodes.parse::<u64>().ok())
+                        .ok_or(UciError("Couldn't parse node count!"))?,
+                    Some(_) => return Err(UciError("Couldn't parse regression argument!")),
+                    None => Regression::DEFAULT_NODES,
+                };
+                Regression::run(&Eval::FEATURE_WEIGHTS, &previous, nodes);
+                Ok(())
+            },
+            None => Err(UciError("Didn't find params file path!")),
+        }
+    }
+
+    // testsuite <epd file> [movetime <ms> | depth <d>]
+    fn parse_testsuite(&self, line: &str) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let path = words.get(1).ok_or(UciError("Didn't find test suite path!"))?;
+        let limit = match &words[2..] {
+            [] => SuiteLimit::MoveTime(TestSuite::DEFAULT_MOVE_TIME),
+            ["movetime", move_time] => SuiteLimit::MoveTime(move_time.parse::<u128>().map_err(|_| UciError("Couldn't parse move time!"))?),
+            ["depth", depth] => SuiteLimit::Depth(depth.parse::<u16>().map_err(|_| UciError("Couldn't parse depth!"))?),
+            _ => return Err(UciError("Couldn't parse testsuite argument!")),
+        };
+
+        TestSuite::run(&Epd::load(Path::new(path))?, limit);
+        Ok(())
+    }
+
+    // tune <positions file> [method local|gradient] [iterations <n>] [output <path>]
+    fn parse_tune(&self, line: &str) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let path = words.get(1).ok_or(UciError("Didn't find tuning file path!"))?;
+        let (mut method, mut iterations, mut output) = (TuneMethod::LocalSearch, Tuner::DEFAULT_ITERATIONS, None);
+        for arguments in words[2..].chunks(2) {
+            match arguments {
+                ["method", "local"] => method = TuneMethod::LocalSearch,
+                ["method", "gradient"] => method = TuneMethod::GradientDescent,
+                ["iterations", count] => iterations = count.parse::<u32>().map_err(|_| UciError("Couldn't parse iteration count!"))?,
+                ["output", output_path] => output = Some(Path::new(*output_path)),
+                _ => return Err(UciError("Couldn't parse tune argument!")),
             }
-        } else if let Some(depth_index) = words.iter().position(|&word| word == "depth") {
-            match words.get(depth_index + 1) {
-                Some(depth_string) => {
-                    match depth_string.parse::<u8>() {
-                        Ok(depth) => {
-                            Search::new(u128::max_value()).go(&mut self.position.clone(), depth);
-                            Ok(())
-                        },
-                        Err(_) => Err(UciParseError("Couldn't parse depth string!"))
-                    }
-                },
-                None => Err(UciParseError("Didn't find depth string!")),
+        }
+
+        let tuner = Tuner::load(Path::new(path))?;
+        let weights = tuner.tune(&Eval::FEATURE_WEIGHTS, method, iterations);
+        match output {
+            Some(output_path) => {
+                Tuner::save(output_path, &weights)?;
+                pl!(format!("info string saved tuned weights to {}", output_path.display()));
+            },
+            None => pl!(Tuner::format_constant(&weights).trim_end()),
+        }
+        Ok(())
+    }
+
+    // datagen <output file> [games <n>] [nodes <n>] [randomplies <n>]
+    fn parse_datagen(&mut self, line: &str) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let path = words.get(1).ok_or(UciError("Didn't find training data file path!"))?;
+        let mut settings = DatagenSettings::default();
+        for arguments in words[2..].chunks(2) {
+            let parse = |value: &str| value.parse::<u64>().map_err(|_| UciError("Couldn't parse datagen value!"));
+            match arguments {
+                ["games", games] => settings.games = parse(games)? as u32,
+                ["nodes", nodes] => settings.nodes = parse(nodes)?,
+                ["randomplies", plies] => settings.random_plies = parse(plies)? as u32,
+                _ => return Err(UciError("Couldn't parse datagen argument!")),
             }
+        }
+
+        self.stop_search();
+        Datagen::run(Path::new(path), &settings)?;
+        Ok(())
+    }
+
+    fn parse_export_net(&mut self, line: &str) -> Result<(), UciError> {
+        let path = line.split_whitespace().nth(1).ok_or(UciError("Didn't find network file path!"))?;
+        fs::write(path, Network::from_piece_squares().to_bytes()).map_err(|_| UciError("Couldn't write network file!"))?;
+        pl!(format!("info string wrote network to {}", path));
+        Ok(())
+    }
+
+    fn parse_profile(&mut self, line: &str) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let path = words.get(2).map_or(self.profile_path.clone(), PathBuf::from);
+        match words.get(1) {
+            Some(&"save") => {
+                Profile::save(&path, &self.options)?;
+                pl!(format!("info string saved profile to {}", path.display()));
+                Ok(())
+            },
+            Some(&"load") => match Profile::load(&path, &mut self.options)? {
+                true => {
+                    pl!(format!("info string loaded profile from {}", path.display()));
+                    Ok(())
+                },
+                false => Err(UciError("Didn't find profile!")),
+            },
+            _ => Err(UciError("Couldn't parse profile command!")),
+        }
+    }
+
+    fn temperature(&self) -> u16 {
+        self.options.spin(EngineOptions::ROOT_TEMPERATURE) as u16
+    }
+
+    fn eval_blend(&self) -> EvalBlend {
+        match self.options.string(EngineOptions::EVAL_BLEND) {
+            "Weighted" => EvalBlend::Weighted(self.options.spin(EngineOptions::EVAL_BLEND_WEIGHT) as u8),
+            "Imbalance" => EvalBlend::Imbalance(self.options.spin(EngineOptions::EVAL_BLEND_IMBALANCE) as i16),
+            _ => EvalBlend::Off,
+        }
+    }
+
+    // play [white|black] [minutes] [increment seconds], where the color is the human side
+    fn parse_play(&self, line: &str) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let human_side = match words.get(1) {
+            Some(&"black") => Color::Black,
+            Some(&"white") | None => Color::White,
+            Some(_) => return Err(UciError("Couldn't parse human side!")),
+        };
+        let minutes = match words.get(2) {
+            Some(minutes_string) => minutes_string.parse::<f64>().map_err(|_| UciError("Couldn't parse minutes!"))?,
+            None => 5.0,
+        };
+        let increment_seconds = match words.get(3) {
+            Some(increment_string) => increment_string.parse::<f64>().map_err(|_| UciError("Couldn't parse increment!"))?,
+            None => 0.0,
+        };
+
+        Play::new(self.position.clone(), human_side, (minutes * 60_000.0) as u128, (increment_seconds * 1000.0) as u128)
+            .with_temperature(self.temperature(), self.seed())
+            .run();
+        Ok(())
+    }
+
+    // perftworker [address], which serves over stdin and stdout without an address
+    fn parse_perft_worker(&self, line: &str) -> Result<(), UciError> {
+        let result = match line.split_whitespace().nth(1) {
+            Some(address) => PerftWorker::listen(address),
+            None => PerftWorker::serve(io::stdin().lock(), io::stdout()),
+        };
+        result.map_err(|_| UciError("Perft worker stopped with an error!"))
+    }
+
+    // perftsplit <depth> <worker>..., where a worker is a TCP address or local
+    fn parse_perft_split(&self, line: &str) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let depth = words
+            .get(1)
+            .and_then(|depth_string| depth_string.parse::<u8>().ok())
+            .ok_or(UciError("Couldn't parse depth string!"))?;
+
+        match PerftCoordinator::split_perft(&self.position, depth, &words[2..]) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(UciError(error.0)),
+        }
+    }
+
+    fn parse_playout(&self, line: &str) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let games = match words.get(1) {
+            Some(games_string) => games_string.parse::<u32>().map_err(|_| UciError("Couldn't parse amount of games!"))?,
+            None => 100,
+        };
+        let policy = if words.contains(&"weighted") {
+            PlayoutPolicy::Weighted
         } else {
-            let mut total_time = 1_000_000;
-            if let Some(time_index) = words.iter().position(|&word| {
-                word == match self.position.side {
-                    Color::White => "wtime",
-                    Color::Black => "btime",
-                }
-            }) {
-                match words.get(time_index + 1) {
-                    Some(time_string) => {
-                        match time_string.parse::<u128>() {
-                            Ok(time) => {
-                                total_time = time
-                            },
-                            Err(_) => return Err(UciParseError("Couldn't parse time string!")),
-                        }
-                    },
-                    None => return Err(UciParseError("Didn't find time string!")),
-                }
-            }
+            PlayoutPolicy::Random
+        };
 
-            let mut increment = 0;
-            if let Some(inc_index) = words.iter().position(|&word| {
-                word == match self.position.side {
-                    Color::White => "winc",
-                    Color::Black => "binc",
-                }
-            }) {
-                match words.get(inc_index + 1) {
-                    Some(inc_string) => {
-                        match inc_string.parse::<u128>() {
-                            Ok(inc) => {
-                                increment = inc
-                            },
-                            Err(_) => return Err(UciParseError("Couldn't parse increment string!")),
-                        }
-                    },
-                    None => return Err(UciParseError("Didn't find increment string!")),
-                }
-            }
+        Playout::playout_test(&self.position, games, policy);
+        Ok(())
+    }
+
+    fn parse_features(&self, line: &str) -> Result<(), UciError> {
+        let position = match line.find("fen") {
+            Some(fen_index) => Fen::parse(line[fen_index + 3..].trim())?,
+            None => self.position.clone(),
+        };
+
+        let features: Vec<String> = Eval::FEATURE_NAMES
+            .iter()
+            .zip(Eval::features(&position))
+            .map(|(name, feature)| format!("{} {}", name, feature))
+            .collect();
+        pl!(features.join(" "));
+        Ok(())
+    }
+
+    fn print_position_details(&self) {
+        let checker_squares: Vec<String> = self.position.checker_squares().iter().map(Square::to_string).collect();
+
+        pl!(self.position);
+        pl!(format!("  Checkers:   {}", checker_squares.join(" ")));
+        pl!(format!("  Legal moves: {}", MoveGeneration::generate_legal_moves(&self.position).len()));
+    }
+
+    fn stop_search(&mut self) {
+        self.search_shared.stop();
+        if let Some(search_thread) = self.search_thread.take() {
+            // NOTE: Panics in the search are already reported by go_isolated
+            let _ = search_thread.join();
+        }
+    }
+
+    fn parse_go(&mut self, line: &str) -> Result<(), UciError> {
+        self.go(line, None)
+    }
+
+    // resume [path] continues the analysis of the checkpoint with go infinite
+    fn parse_resume(&mut self, line: &str) -> Result<(), UciError> {
+        let path = match line.split_whitespace().nth(1) {
+            Some(path) => PathBuf::from(path),
+            None => match self.options.string(EngineOptions::CHECKPOINT_FILE) {
+                "" => return Err(UciError("Didn't find checkpoint path!")),
+                path => PathBuf::from(path),
+            },
+        };
+
+        let checkpoint = AnalysisCheckpoint::read(&path)?;
+        self.position = checkpoint.position()?;
+        self.root_fen = checkpoint.fen.clone();
+        self.moves.clear();
+        pl!(format!("info string resuming analysis at depth {} from {}", checkpoint.depth + 1, path.display()));
+        self.go("go infinite", Some((path, checkpoint)))
+    }
+
+    fn go(&mut self, line: &str, resumed: Option<(PathBuf, AnalysisCheckpoint)>) -> Result<(), UciError> {
+        let words: Vec<_> = line.split_whitespace().collect();
+        if let Some(depth) = Self::parse_go_value::<u8>(&words, "perft", "Couldn't parse perft depth!")? {
+            Perft::perft_test(&self.position, depth, true);
+            return Ok(());
+        }
+
+        let (time_keyword, increment_keyword, opponent_time_keyword, opponent_increment_keyword) = match self.position.side {
+            Color::White => ("wtime", "winc", "btime", "binc"),
+            Color::Black => ("btime", "binc", "wtime", "winc"),
+        };
+        let depth = Self::parse_go_value::<u16>(&words, "depth", "Couldn't parse depth string!")?;
+        let nodes = Self::parse_go_value::<u64>(&words, "nodes", "Couldn't parse nodes string!")?;
+        let move_time = Self::parse_go_value::<u128>(&words, "movetime", "Couldn't parse movetime string!")?;
+        let total_time = Self::parse_go_value::<u128>(&words, time_keyword, "Couldn't parse time string!")?;
+        let increment = Self::parse_go_value::<u128>(&words, increment_keyword, "Couldn't parse increment string!")?;
+        let moves_to_go = Self::parse_go_value::<u128>(&words, "movestogo", "Couldn't parse movestogo string!")?;
+        let opponent_time = Self::parse_go_value::<u128>(&words, opponent_time_keyword, "Couldn't parse time string!")?;
+        let opponent_increment = Self::parse_go_value::<u128>(&words, opponent_increment_keyword, "Couldn't parse increment string!")?;
+        let ponder = words.contains(&"ponder");
+        let move_overhead = self.options.spin(EngineOptions::MOVE_OVERHEAD) as u128;
+
+        // NOTE: The clocks sent with go ponder don't tell how long the opponent thought
+        if let (Some(opponent_time), false) = (opponent_time, ponder) {
+            self.opponent_model.observe(opponent_time, opponent_increment.unwrap_or(0));
+        }
+
+        // NOTE: The search stops at whichever limit is reached first, and only panics on the clock
+        let time_limits = if words.contains(&"infinite") {
+            TimeLimits::INFINITE
+        } else if let Some(move_time) = move_time {
+            TimeLimits::fixed(move_time)
+        } else if let Some(total_time) = total_time {
+            let stop_time = TimeLimits::from_clock(total_time, increment.unwrap_or(0), moves_to_go, move_overhead).soft;
+            TimeLimits::with_soft_limit(total_time, self.opponent_model.adjust_stop_time(stop_time), move_overhead)
+        } else if depth.is_some() || nodes.is_some() {
+            TimeLimits::INFINITE
+        } else {
+            TimeLimits::fixed(TimeLimits::from_clock(1_000_000, 0, None, move_overhead).soft)
+        };
+
+        self.stop_search();
+        self.update_network();
+        self.update_transposition_table();
+        self.search_shared = Arc::new(SearchShared::default());
+        // NOTE: With go ponder, the time limits only start to apply on ponderhit
+        self.ponder_flag = Arc::new(AtomicBool::new(ponder));
+
+        // NOTE: A seed is always drawn, so the search can be reproduced even if none was set
+        let seed = self.seed().unwrap_or_else(|| rand::rng().random());
+        let repro_bundle = ReproBundle::new(&self.options, seed, &self.root_fen, &self.moves, line);
+
+        let mut search = Search::new(time_limits.soft)
+            .with_shared(self.search_shared.clone())
+            .with_hard_time_limit(time_limits.hard)
+            .with_ponder_flag(self.ponder_flag.clone())
+            .with_ponder_credit(self.opponent_model.ponder_credit_percent(time_limits.soft))
+            .with_node_limit(nodes.unwrap_or(u64::MAX))
+            .with_multipv(self.options.spin(EngineOptions::MULTIPV) as usize)
+            .with_temperature(self.temperature(), Some(seed))
+            .with_nps_limit(self.options.spin(EngineOptions::NPS_LIMIT) as u64)
+            .with_contempt(self.options.spin(EngineOptions::CONTEMPT) as i16)
+            .with_nnue(self.options.check(EngineOptions::USE_NNUE))
+            .with_network(self.network.clone())
+            .with_eval_blend(self.eval_blend())
+            .with_show_wdl(self.options.check(EngineOptions::SHOW_WDL))
+            .with_transposition_table(self.transposition_table.clone())
+            .with_threads(self.options.spin(EngineOptions::THREADS) as usize)
+            .with_repro_bundle(repro_bundle);
+
+        let checkpoint_interval = self.options.spin(EngineOptions::CHECKPOINT_INTERVAL) as u128 * 1000;
+        match resumed {
+            Some((path, checkpoint)) => {
+                search = search.with_checkpoint(path, checkpoint_interval).with_resumed_checkpoint(checkpoint);
+            },
+            None => match self.options.string(EngineOptions::CHECKPOINT_FILE) {
+                path if !path.is_empty() && words.contains(&"infinite") => {
+                    search = search.with_checkpoint(PathBuf::from(path), checkpoint_interval);
+                },
+                _ => (),
+            },
+        }
+        let position = self.position.clone();
+        self.search_thread = Some(thread::spawn(move || {
+            search.go_isolated(&position, depth.unwrap_or(Search::MAX_DEPTH));
+        }));
+        Ok(())
+    }
+
+    // NOTE: The network is loaded on go rather than on setoption, since it mustn't change under a running search
+    fn update_network(&mut self) {
+        let eval_file = self.options.string(EngineOptions::EVAL_FILE).to_owned();
+        if self.eval_file.as_deref() == Some(eval_file.as_str()) {
+            return;
+        }
+
+        let network = match eval_file.as_str() {
+            "" => nnue::embedded_network(),
+            path => match Network::load(Path::new(path)) {
+                Ok(network) => {
+                    pl!(format!("info string loaded network {}", path));
+                    Some(network)
+                },
+                Err(error) => {
+                    let embedded_network = nnue::embedded_network();
+                    let fallback = if embedded_network.is_some() { "embedded network" } else { "handcrafted eval" };
+                    pl!(format!("info string {} Using the {} instead", error, fallback));
+                    embedded_network
+                },
+            },
+        };
+        self.network = network.map(Arc::new);
+        self.eval_file = Some(eval_file);
+    }
+
+    // NOTE: Like the network, the table is only replaced on go
+    fn update_transposition_table(&mut self) {
+        let size_mb = self.options.spin(EngineOptions::HASH) as usize;
+        if self.transposition_table.size_mb() != size_mb {
+            self.transposition_table = Arc::new(TranspositionTable::new(size_mb));
+        }
+    }
 
-            Search::new(Search::calculate_stop_time(total_time, increment)).go(&mut self.position.clone(), 255);
-            Ok(())
+    fn parse_go_value<T: FromStr>(words: &[&str], keyword: &str, error: &'static str) -> Result<Option<T>, UciError> {
+        match words.iter().position(|&word| word == keyword) {
+            Some(keyword_index) => match words.get(keyword_index + 1) {
+                Some(value_string) => value_string.parse::<T>().map(Some).map_err(|_| UciError(error)),
+                None => Err(UciError(error)),
+            },
+            None => Ok(None),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    #[test]
+    fn parse_position_applies_moves() {
+        move_masks::init();
+        let mut uci = Uci::default();
+
+        uci.parse_position("position startpos moves e2e4 e7e5 g1f3").unwrap();
+        assert_eq!(uci.position.to_fen_without_clocks(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq -");
+
+        uci.parse_position("position fen 8/P6k/8/8/8/8/8/K7 w - - 0 1 moves a7a8n").unwrap();
+        assert_eq!(uci.position.to_fen_without_clocks(), "N7/7k/8/8/8/8/8/K7 b - -");
+
+        assert!(uci.parse_position("position fen 8/P6k/8/8/8/8/8/K7 w - - 0 1 moves a7a8").is_err());
+        assert!(uci.parse_position("position startpos moves e2e4 e2e4").is_err());
+        assert_eq!(uci.position.to_fen_without_clocks(), "N7/7k/8/8/8/8/8/K7 b - -");
+    }
+
+    #[test]
+    fn ucinewgame_stops_the_running_search_first() {
+        move_masks::init();
+        let mut uci = Uci::default();
+        uci.parse_position("position startpos moves e2e4").unwrap();
+        uci.parse_line("go infinite".to_owned()).unwrap();
+
+        uci.parse_line("ucinewgame".to_owned()).unwrap();
+        assert!(uci.search_thread.is_none());
+        assert_eq!(uci.position.to_fen_without_clocks(), Fen::STARTING_POSITION);
+    }
+
+    #[test]
+    fn repro_bundle_recreates_position_and_options() {
+        move_masks::init();
+        let mut uci = Uci::default();
+        uci.parse_line("setoption name MultiPV value 3".to_owned()).unwrap();
+        uci.parse_position("position startpos moves e2e4 c7c5").unwrap();
+
+        let bundle = ReproBundle::new(&uci.options, 7, &uci.root_fen, &uci.moves, "go depth 1");
+        let path = std::env::temp_dir().join("sisyphus32-uci-repro-test.txt");
+        bundle.write_to(&path, "test").unwrap();
+
+        let mut replayed = Uci::default();
+        replayed.parse_line(format!("repro {}", path.display())).unwrap();
+        replayed.stop_search();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replayed.position.to_fen(), uci.position.to_fen());
+        assert_eq!(replayed.moves, ["e2e4", "c7c5"]);
+        assert_eq!(replayed.options.spin(EngineOptions::MULTIPV), 3);
+        assert_eq!(replayed.options.string(EngineOptions::SEED), "7");
+        assert!(replayed.parse_line("repro".to_owned()).is_err());
+    }
+}
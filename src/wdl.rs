@@ -0,0 +1,69 @@
+use crate::{color::Color, position::Position};
+
+// Stockfish's win rate model, in its score units where a pawn is 208
+const CENTER_COEFFICIENTS: [f64; 4] = [0.38036525, -2.82015070, 23.17882135, 307.36768407];
+const STEEPNESS_COEFFICIENTS: [f64; 4] = [-2.29434733, 13.27689788, -14.26828904, 63.45318330];
+const MODEL_PAWN_VALUE: f64 = 208.0;
+
+// NOTE: The model stops changing after this many plies and outside of this score
+const MAX_MODEL_PLY: u32 = 240;
+const MAX_MODEL_SCORE: f64 = 4000.0;
+
+// Expected wins, draws and losses of the side to move out of 1000 games, as sent with info wdl
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Wdl {
+    pub win: u16,
+    pub draw: u16,
+    pub loss: u16,
+}
+
+impl Wdl {
+    pub fn from_score(score: i16, game_ply: u32) -> Wdl {
+        let win = Self::win_rate(score as f64, game_ply);
+        let loss = Self::win_rate(-score as f64, game_ply);
+        Wdl { win, draw: 1000 - win - loss, loss }
+    }
+
+    // The plies played since the start of the game, going by the move number of the position
+    pub fn game_ply(position: &Position) -> u32 {
+        (position.fullmove_number.max(1) as u32 - 1) * 2 + (position.side == Color::Black) as u32
+    }
+
+    fn win_rate(score: f64, game_ply: u32) -> u16 {
+        let m = game_ply.min(MAX_MODEL_PLY) as f64 / 64.0;
+        let polynomial = |coefficients: [f64; 4]| coefficients.iter().fold(0.0, |sum, coefficient| sum * m + coefficient);
+        let center = polynomial(CENTER_COEFFICIENTS);
+        let steepness = polynomial(STEEPNESS_COEFFICIENTS);
+        let x = (score * MODEL_PAWN_VALUE / 100.0).clamp(-MAX_MODEL_SCORE, MAX_MODEL_SCORE);
+        (0.5 + 1000.0 / (1.0 + ((center - x) / steepness).exp())) as u16
+    }
+
+    pub fn to_uci_string(self) -> String {
+        format!("wdl {} {} {}", self.win, self.draw, self.loss)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wdl_follows_the_score_and_adds_up() {
+        for game_ply in [0, 40, 120, 500] {
+            let mut previous_win = 0;
+            for score in (-1000..=1000).step_by(50) {
+                let wdl = Wdl::from_score(score, game_ply);
+                assert_eq!(wdl.win + wdl.draw + wdl.loss, 1000);
+                assert!(wdl.win >= previous_win);
+                assert_eq!(Wdl::from_score(-score, game_ply), Wdl { win: wdl.loss, draw: wdl.draw, loss: wdl.win });
+                previous_win = wdl.win;
+            }
+        }
+
+        let equal = Wdl::from_score(0, 60);
+        assert!(equal.draw > 900, "{:?}", equal);
+        assert_eq!(Wdl::from_score(9990, 60), Wdl { win: 1000, draw: 0, loss: 0 });
+        assert_ne!(Wdl::from_score(150, 160), Wdl::from_score(150, 20));
+        assert_eq!(Wdl { win: 5, draw: 990, loss: 5 }.to_uci_string(), "wdl 5 990 5");
+    }
+}
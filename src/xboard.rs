@@ -0,0 +1,214 @@
+use std::io;
+
+use crate::{bit_move::BitMove, color::Color, fen::{Fen, FenError}, game::Game, impl_error, impl_error_from, pl, position::Position, search::{Search, SearchOutput}, timer::TimeLimits};
+
+#[derive(Debug)]
+pub struct XboardError(pub &'static str);
+
+impl_error!(XboardError);
+impl_error_from!(FenError, XboardError);
+
+// NOTE: The search runs on the protocol thread, so commands sent while thinking are handled afterwards
+pub struct Xboard {
+    game: Game,
+    engine_side: Option<Color>,
+    time_left: u128,
+    moves_per_session: u128,
+    increment: u128,
+    move_time: Option<u128>,
+    max_depth: u16,
+    post: bool,
+}
+
+impl Default for Xboard {
+    fn default() -> Self {
+        Self {
+            game: Game::new(Position::starting_position()),
+            engine_side: Some(Color::Black),
+            time_left: Self::DEFAULT_TIME,
+            moves_per_session: 0,
+            increment: 0,
+            move_time: None,
+            max_depth: Search::MAX_DEPTH,
+            post: false,
+        }
+    }
+}
+
+impl Xboard {
+    const DEFAULT_TIME: u128 = 300_000;
+
+    pub fn run(&mut self) {
+        loop {
+            let mut line = String::new();
+            if let Ok(0) | Err(_) = io::stdin().read_line(&mut line) {
+                return;
+            }
+            if line.trim() == "quit" {
+                return;
+            }
+            if let Err(XboardError(msg)) = self.parse_line(&line) {
+                pl!(format!("Error ({}): {}", msg, line.trim()));
+            }
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), XboardError> {
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            return Ok(());
+        };
+        let argument = words.next();
+
+        match keyword {
+            "protover" => {
+                pl!("feature myname=\"Sisyphus32\" usermove=1 setboard=1 ping=1 sigint=0 sigterm=0 colors=0 done=1");
+            }
+            "new" => {
+                *self = Xboard { post: self.post, ..Xboard::default() };
+            }
+            "force" | "result" => self.engine_side = None,
+            "go" => {
+                self.engine_side = Some(self.game.position().side);
+                self.think();
+            }
+            "playother" => self.engine_side = Some(self.game.position().side.opposite()),
+            "usermove" => self.parse_usermove(argument.ok_or(XboardError("missing move"))?),
+            "setboard" => {
+                let fen_index = line.find("setboard").unwrap() + 8;
+                self.game = Game::new(Fen::parse(line[fen_index..].trim())?);
+            }
+            "time" => self.time_left = Self::parse_value::<u128>(argument)? * 10,
+            "otim" => (),
+            "level" => {
+                let (moves_per_session, increment) = Self::parse_level(&line.split_whitespace().collect::<Vec<_>>())?;
+                self.moves_per_session = moves_per_session;
+                self.increment = increment;
+                self.move_time = None;
+            }
+            "st" => self.move_time = Some(Self::parse_value::<u128>(argument)? * 1000),
+            "sd" => self.max_depth = Self::parse_value::<u16>(argument)?,
+            "undo" => {
+                self.game.undo();
+            }
+            "remove" => {
+                self.game.undo();
+                self.game.undo();
+            }
+            "ping" => pl!(format!("pong {}", argument.unwrap_or(""))),
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "xboard" | "accepted" | "rejected" | "hard" | "easy" | "random" | "computer" | "name" | "?" => (),
+            _ => return Err(XboardError("unknown command")),
+        }
+        Ok(())
+    }
+
+    fn parse_usermove(&mut self, move_string: &str) {
+        let position = self.game.position();
+        match BitMove::from_uci(position, move_string) {
+            Ok(bit_move) if self.game.make_move(bit_move) => (),
+            _ => {
+                pl!(format!("Illegal move: {}", move_string));
+                return;
+            }
+        }
+
+        if !self.report_game_result() && self.engine_side == Some(self.game.position().side) {
+            self.think();
+        }
+    }
+
+    fn think(&mut self) {
+        if self.report_game_result() {
+            return;
+        }
+
+        let output = if self.post { SearchOutput::Xboard } else { SearchOutput::Silent };
+        let time_limits = self.time_limits();
+        let best_move = Search::new(time_limits.soft)
+            .with_hard_time_limit(time_limits.hard)
+            .with_output(output)
+            .go_isolated(self.game.position(), self.max_depth);
+
+        pl!(format!("move {}", best_move.to_uci_string()));
+        self.game.make_move(best_move);
+        self.report_game_result();
+    }
+
+    fn time_limits(&self) -> TimeLimits {
+        if let Some(move_time) = self.move_time {
+            return TimeLimits::fixed(move_time);
+        }
+
+        // NOTE: With a fixed amount of moves per session, the clock is refilled every session
+        let moves_to_go = if self.moves_per_session == 0 {
+            None
+        } else {
+            let moves_played = self.game.moves().len() as u128 / 2;
+            Some(self.moves_per_session - moves_played % self.moves_per_session)
+        };
+        TimeLimits::from_clock(self.time_left, self.increment, moves_to_go, TimeLimits::DEFAULT_MOVE_OVERHEAD)
+    }
+
+    // Sends the result and leaves the game to the GUI if the game is over. Returns whether it was.
+    fn report_game_result(&mut self) -> bool {
+        let Some(result) = self.game.game_result() else {
+            return false;
+        };
+
+        pl!(format!("{} {{{}}}", result.to_score_string(), result));
+        self.engine_side = None;
+        true
+    }
+
+    fn parse_value<T: std::str::FromStr>(argument: Option<&str>) -> Result<T, XboardError> {
+        argument
+            .and_then(|value_string| value_string.parse::<T>().ok())
+            .ok_or(XboardError("couldn't parse value"))
+    }
+
+    // level <moves per session> <base minutes or minutes:seconds> <increment seconds>.
+    // Returns the moves per session and the increment in milliseconds, the base time is sent with time.
+    fn parse_level(words: &[&str]) -> Result<(u128, u128), XboardError> {
+        if words.len() != 4 {
+            return Err(XboardError("couldn't parse level"));
+        }
+        let moves_per_session = Self::parse_value::<u128>(Some(words[1]))?;
+        let increment_seconds = Self::parse_value::<f64>(Some(words[3]))?;
+        Ok((moves_per_session, (increment_seconds * 1000.0) as u128))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::move_masks;
+
+    use super::*;
+
+    #[test]
+    fn xboard_commands_update_the_game() {
+        move_masks::init();
+        let mut xboard = Xboard::default();
+
+        assert_eq!(Xboard::parse_level(&["level", "40", "2:30", "12"]).unwrap(), (40, 12_000));
+        assert_eq!(Xboard::parse_level(&["level", "0", "5", "0.5"]).unwrap(), (0, 500));
+        assert!(Xboard::parse_level(&["level", "0", "5"]).is_err());
+
+        xboard.parse_line("force").unwrap();
+        xboard.parse_line("usermove e2e4").unwrap();
+        xboard.parse_line("usermove e7e5").unwrap();
+        xboard.parse_line("usermove e7e5").unwrap();
+        assert_eq!(xboard.game.to_san_movetext(), "1. e4 e5");
+
+        xboard.parse_line("remove").unwrap();
+        assert_eq!(xboard.game.moves().len(), 0);
+
+        xboard.parse_line("time 6000").unwrap();
+        xboard.parse_line("level 40 5 0").unwrap();
+        assert_eq!(xboard.time_limits(), TimeLimits::from_clock(60_000, 0, Some(40), TimeLimits::DEFAULT_MOVE_OVERHEAD));
+
+        assert!(xboard.parse_line("bogus").is_err());
+        assert!(xboard.parse_line("setboard 8/8/8").is_err());
+    }
+}
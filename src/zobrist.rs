@@ -0,0 +1,100 @@
+use crate::{castling_rights::CastlingRights, color::Color, piece::PieceType, square::Square};
+
+static KEYS: ZobristKeys = ZobristKeys::generate();
+
+struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+    side: u64,
+}
+
+// NOTE: The keys come from a fixed seed, so the same position hashes the same in every run
+const SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+impl ZobristKeys {
+    // NOTE: Loops in const functions have to be while loops
+    const fn generate() -> ZobristKeys {
+        let mut state = SEED;
+
+        let mut pieces = [[0; 64]; 12];
+        let mut piece = 0;
+        while piece < 12 {
+            let mut square = 0;
+            while square < 64 {
+                state = next_random(state);
+                pieces[piece][square] = state;
+                square += 1;
+            }
+            piece += 1;
+        }
+
+        // Every combination of castling rights is the XOR of the keys of its single rights,
+        // so losing one right only changes the key by that right
+        let mut single_right_keys = [0; 4];
+        let mut bit = 0;
+        while bit < 4 {
+            state = next_random(state);
+            single_right_keys[bit] = state;
+            bit += 1;
+        }
+        let mut castling = [0; 16];
+        let mut rights = 0;
+        while rights < 16 {
+            let mut bit = 0;
+            while bit < 4 {
+                if rights & (1 << bit) != 0 {
+                    castling[rights] ^= single_right_keys[bit];
+                }
+                bit += 1;
+            }
+            rights += 1;
+        }
+
+        let mut en_passant = [0; 8];
+        let mut file = 0;
+        while file < 8 {
+            state = next_random(state);
+            en_passant[file] = state;
+            file += 1;
+        }
+
+        let side = next_random(state);
+        ZobristKeys { pieces, castling, en_passant, side }
+    }
+}
+
+// Xorshift64, which is plenty for hash keys and keeps the keys independent of the rand crate
+const fn next_random(mut state: u64) -> u64 {
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
+
+#[inline(always)]
+pub fn piece_key(piece: PieceType, square: Square) -> u64 {
+    KEYS.pieces[piece as usize][square]
+}
+
+#[inline(always)]
+pub fn castling_key(castling_rights: CastlingRights) -> u64 {
+    KEYS.castling[castling_rights.0 as usize]
+}
+
+// NOTE: The en-passant square is hashed by its file, and only if there is one
+#[inline(always)]
+pub fn en_passant_key(en_passant_sq: Square) -> u64 {
+    match en_passant_sq {
+        Square::None => 0,
+        _ => KEYS.en_passant[en_passant_sq.file_as_u8() as usize],
+    }
+}
+
+#[inline(always)]
+pub fn side_key(side: Color) -> u64 {
+    match side {
+        Color::White => 0,
+        Color::Black => KEYS.side,
+    }
+}